@@ -0,0 +1,158 @@
+use palette::{LinSrgb, Srgb, Srgba};
+
+/// Non-linear sRGB with straight (not premultiplied) alpha, 8 bits per channel
+pub type Color = Srgba<u8>;
+
+pub const BLACK: Color = Color::new(0, 0, 0, 255);
+pub const WHITE: Color = Color::new(255, 255, 255, 255);
+pub const TRANSPARENT: Color = Color::new(0, 0, 0, 0);
+
+/// Packs a color into the `0x00RRGGBB` format the frame buffer uses, ignoring alpha
+pub fn to_buffer(color: Color) -> u32 {
+    (color.red as u32) << 16 | (color.green as u32) << 8 | color.blue as u32
+}
+
+/// Unpacks a color from the `0x00RRGGBB` format the frame buffer uses; it's always opaque
+pub fn from_buffer(value: u32) -> Color {
+    Color::new((value >> 16) as u8, (value >> 8) as u8, value as u8, 255)
+}
+
+/// How colors are decoded into light intensities for blending, and encoded back afterwards.
+///
+/// Blending has to happen in linear light for antialiased edges to keep their weight;
+/// blending the encoded values directly makes light text on a dark background look too thin,
+/// and dark text on a light background too bold.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Gamma {
+    /// The sRGB transfer function
+    #[default]
+    Srgb,
+
+    /// A pure power curve with the given exponent, like the traditional 2.2.
+    /// `Power(1.0)` blends the encoded values directly
+    Power(f32),
+}
+impl Gamma {
+    pub fn to_linear(self, color: Srgb<u8>) -> LinSrgb<f32> {
+        match self {
+            Gamma::Srgb => color.into_format::<f32>().into_linear(),
+            Gamma::Power(exponent) => {
+                let channel = |value: u8| (value as f32 / 255.0).powf(exponent);
+                LinSrgb::new(
+                    channel(color.red),
+                    channel(color.green),
+                    channel(color.blue),
+                )
+            }
+        }
+    }
+
+    pub fn from_linear(self, color: LinSrgb<f32>) -> Srgb<u8> {
+        match self {
+            Gamma::Srgb => Srgb::<f32>::from_linear(color).into_format(),
+            Gamma::Power(exponent) => {
+                let channel =
+                    |value: f32| (value.clamp(0.0, 1.0).powf(1.0 / exponent) * 255.0).round() as u8;
+                Srgb::new(
+                    channel(color.red),
+                    channel(color.green),
+                    channel(color.blue),
+                )
+            }
+        }
+    }
+
+    /// Like [`Self::to_linear`], for colors that aren't rounded to 8 bits
+    pub fn decode(self, color: Srgb<f32>) -> LinSrgb<f32> {
+        match self {
+            Gamma::Srgb => color.into_linear(),
+            Gamma::Power(exponent) => {
+                let channel = |value: f32| value.powf(exponent);
+                LinSrgb::new(
+                    channel(color.red),
+                    channel(color.green),
+                    channel(color.blue),
+                )
+            }
+        }
+    }
+
+    /// Like [`Self::from_linear`], without rounding to 8 bits
+    pub fn encode(self, color: LinSrgb<f32>) -> Srgb<f32> {
+        match self {
+            Gamma::Srgb => Srgb::from_linear(color),
+            Gamma::Power(exponent) => {
+                let channel = |value: f32| value.max(0.0).powf(1.0 / exponent);
+                Srgb::new(
+                    channel(color.red),
+                    channel(color.green),
+                    channel(color.blue),
+                )
+            }
+        }
+    }
+}
+
+/// Source-over blending of `color` onto an opaque `background`,
+/// with the color's alpha scaled by `coverage`, e.g. from an antialiased glyph
+pub fn blend(background: Color, color: Color, coverage: u8, gamma: Gamma) -> Color {
+    let alpha = coverage as u32 * color.alpha as u32;
+    match alpha {
+        0 => return background,
+        0xFE01 => return Color::new(color.red, color.green, color.blue, 255),
+        _ => (),
+    }
+
+    let alpha = alpha as f32 / 0xFE01 as f32;
+    let source = gamma.to_linear(color.color);
+    let destination = gamma.to_linear(background.color);
+    let blended = source * alpha + destination * (1.0 - alpha);
+
+    let blended = gamma.from_linear(blended);
+    Color::new(blended.red, blended.green, blended.blue, 255)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        [Gamma::Srgb, Gamma::Power(2.2), Gamma::Power(1.0)]
+            .into_iter()
+            .for_each(|gamma| {
+                (0..=255).for_each(|value| {
+                    let color = Srgb::new(value, value / 2, 255 - value);
+                    assert_eq!(gamma.from_linear(gamma.to_linear(color)), color);
+                });
+            });
+
+        let color = Color::new(12, 34, 56, 255);
+        assert_eq!(from_buffer(to_buffer(color)), color);
+        assert_eq!(to_buffer(Color::new(1, 2, 3, 0)), 0x010203);
+    }
+
+    #[test]
+    fn blends_in_linear_light() {
+        // Half of the light of white is much brighter than half of its encoded value
+        let half = blend(BLACK, WHITE, 128, Gamma::Srgb);
+        assert_eq!(half, Color::new(188, 188, 188, 255));
+        let half = blend(BLACK, WHITE, 128, Gamma::Power(1.0));
+        assert_eq!(half, Color::new(128, 128, 128, 255));
+
+        // Alpha scales the color just like coverage
+        let translucent = Color::new(255, 255, 255, 128);
+        let half = blend(BLACK, WHITE, 128, Gamma::Srgb);
+        assert_eq!(blend(BLACK, translucent, 255, Gamma::Srgb), half);
+        // The other way around, 127/255 of white's light is left
+        assert_eq!(blend(WHITE, BLACK, 128, Gamma::Srgb).red, 187);
+    }
+
+    #[test]
+    fn blends_the_extremes_exactly() {
+        let color = Color::new(10, 200, 30, 255);
+        assert_eq!(blend(WHITE, color, 0, Gamma::Srgb), WHITE);
+        assert_eq!(blend(WHITE, color, 255, Gamma::Srgb), color);
+        assert_eq!(blend(WHITE, TRANSPARENT, 255, Gamma::Srgb), WHITE);
+    }
+}
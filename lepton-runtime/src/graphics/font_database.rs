@@ -0,0 +1,534 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use super::typeface::{
+    is_font_file, FontBytes, FontError, FontOrigin, FontStyle, Typeface, Variant, Weight,
+};
+
+/// Font directories relative to each of the `XDG_DATA_DIRS`, or the defaults below
+const DATA_DIRS_DEFAULT: [&str; 2] = ["/usr/local/share", "/usr/share"];
+
+/// Legacy per-user font directory, relative to `HOME`
+const HOME_FONTS: &str = ".fonts";
+
+/// The CSS generic font families we know how to resolve
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GenericFamily {
+    SansSerif,
+    Serif,
+    Monospace,
+}
+impl GenericFamily {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "sans-serif" => Some(Self::SansSerif),
+            "serif" => Some(Self::Serif),
+            "monospace" => Some(Self::Monospace),
+            _ => None,
+        }
+    }
+
+    /// Commonly installed families for each generic family, in order of preference.
+    /// These can be overridden with [`FontDatabase::set_generic_family`].
+    fn default_families(&self) -> &'static [&'static str] {
+        match self {
+            GenericFamily::SansSerif => &[
+                "DejaVu Sans",
+                "Noto Sans",
+                "Liberation Sans",
+                "Roboto",
+                "Open Sans",
+                "Cantarell",
+                "Ubuntu",
+                "Arial",
+                "Helvetica",
+            ],
+            GenericFamily::Serif => &[
+                "DejaVu Serif",
+                "Noto Serif",
+                "Liberation Serif",
+                "Times New Roman",
+                "Georgia",
+            ],
+            GenericFamily::Monospace => &[
+                "DejaVu Sans Mono",
+                "Noto Sans Mono",
+                "Liberation Mono",
+                "Ubuntu Mono",
+                "Roboto Mono",
+                "Courier New",
+            ],
+        }
+    }
+}
+
+/// Everything we index about a single font face, without keeping the font itself loaded
+#[derive(Debug, Clone)]
+pub struct FaceInfo {
+    pub path: PathBuf,
+    /// The face index within a font collection, `0` otherwise
+    pub index: u32,
+    pub family: String,
+    pub weight: Weight,
+    pub style: FontStyle,
+}
+impl FaceInfo {
+    fn read(path: &Path, data: &[u8], index: u32) -> Result<Self, FontError> {
+        let face = ttf_parser::Face::from_slice(data, index).map_err(FontError::Face)?;
+
+        // The typographic family groups more than 4 styles under one name (e.g. "Roboto" instead of "Roboto Light"),
+        // so prefer that when the font has one
+        let names = face.names();
+        let family = [
+            ttf_parser::name_id::TYPOGRAPHIC_FAMILY,
+            ttf_parser::name_id::FAMILY,
+        ]
+        .into_iter()
+        .find_map(|id| {
+            (0..names.len())
+                .filter_map(|i| names.get(i))
+                .filter(|name| name.name_id == id)
+                .find_map(|name| name.to_string())
+        })
+        .ok_or(FontError::Parse("font has no family name"))?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            index,
+            family,
+            weight: face.weight().to_number(),
+            style: FontStyle::of_face(&face),
+        })
+    }
+}
+
+/// An index of font faces by family name, built by scanning font directories.
+/// Only the font tables are read while scanning; the fonts themselves are loaded by [`FontDatabase::typeface`].
+#[derive(Default)]
+pub struct FontDatabase {
+    faces: Vec<FaceInfo>,
+
+    /// Lowercase family name, to indices into `faces`.
+    /// Ordered, so that listing the families is deterministic
+    families: BTreeMap<String, Vec<usize>>,
+
+    /// So scanning the same directory twice doesn't index anything twice
+    indexed: HashSet<(PathBuf, u32)>,
+
+    generic_families: HashMap<GenericFamily, Vec<String>>,
+}
+impl FontDatabase {
+    /// An empty database; add fonts with [`FontDatabase::scan_directory`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A database of the fonts in the standard system and user font directories,
+    /// plus any `extra_directories`.
+    ///
+    /// Fonts that fail to load are logged and skipped.
+    pub fn system<P: AsRef<Path>>(extra_directories: impl IntoIterator<Item = P>) -> Self {
+        let mut database = Self::new();
+
+        let mut directories = system_font_directories();
+        directories.extend(
+            extra_directories
+                .into_iter()
+                .map(|directory| directory.as_ref().to_owned()),
+        );
+
+        let errors = directories
+            .iter()
+            .flat_map(|directory| database.scan_directory(directory))
+            .collect::<Vec<_>>();
+        errors
+            .iter()
+            .for_each(|error| log::warn!("skipping font: {error}"));
+
+        database
+    }
+
+    /// Recursively indexes every font file under `directory`.
+    /// A missing directory is not an error; everything else that fails is returned.
+    pub fn scan_directory(&mut self, directory: impl AsRef<Path>) -> Vec<FontError> {
+        let mut errors = Vec::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![directory.as_ref().to_owned()];
+
+        while let Some(directory) = pending.pop() {
+            // Symlinked directories could otherwise send us in circles
+            let Ok(canonical) = directory.canonicalize() else {
+                continue;
+            };
+            if !visited.insert(canonical) {
+                continue;
+            }
+
+            let entries = match std::fs::read_dir(&directory) {
+                Ok(entries) => entries,
+                Err(error) => {
+                    errors.push(FontError::load(
+                        FontOrigin::File(directory),
+                        FontError::Io(error),
+                    ));
+                    continue;
+                }
+            };
+
+            let mut paths = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect::<Vec<_>>();
+            paths.sort();
+
+            for path in paths {
+                if path.is_dir() {
+                    pending.push(path);
+                } else if is_font_file(&path) {
+                    if let Err(error) = self.index_file(&path) {
+                        errors.push(FontError::load(FontOrigin::File(path), error));
+                    }
+                }
+            }
+        }
+
+        errors
+    }
+
+    fn index_file(&mut self, path: &Path) -> Result<(), FontError> {
+        let data = std::fs::read(path).map_err(FontError::Io)?;
+        let collection = ttf_parser::fonts_in_collection(&data);
+
+        for index in 0..collection.unwrap_or(1) {
+            if self.indexed.contains(&(path.to_owned(), index)) {
+                continue;
+            }
+
+            // One broken face shouldn't keep the rest of a collection from being indexed
+            let info = match FaceInfo::read(path, &data, index) {
+                Ok(info) => info,
+                Err(error) if collection.is_none() => return Err(error),
+                Err(error) => {
+                    log::warn!("skipping face {index} of {}: {error}", path.display());
+                    continue;
+                }
+            };
+            self.indexed.insert((path.to_owned(), index));
+            self.families
+                .entry(info.family.to_lowercase())
+                .or_default()
+                .push(self.faces.len());
+            self.faces.push(info);
+        }
+
+        Ok(())
+    }
+
+    /// Every indexed face
+    pub fn faces(&self) -> &[FaceInfo] {
+        &self.faces
+    }
+
+    /// The names of every indexed family, as they're spelled in the fonts, in alphabetical order
+    pub fn families(&self) -> impl Iterator<Item = &str> {
+        self.families
+            .values()
+            .filter_map(|faces| faces.first())
+            .map(|&index| self.faces[index].family.as_str())
+    }
+
+    /// The name of an indexed family as it's spelled in the fonts, matched case-insensitively
+    fn family_name(&self, family: &str) -> Option<&str> {
+        self.family_faces(family)
+            .next()
+            .map(|face| face.family.as_str())
+    }
+
+    /// The faces of a family, matched case-insensitively
+    pub fn family_faces(&self, family: &str) -> impl Iterator<Item = &FaceInfo> {
+        self.families
+            .get(&family.to_lowercase())
+            .into_iter()
+            .flatten()
+            .map(|&index| &self.faces[index])
+    }
+
+    pub fn has_family(&self, family: &str) -> bool {
+        self.families.contains_key(&family.to_lowercase())
+    }
+
+    /// Replaces the list of families, in order of preference, that a generic family resolves to
+    pub fn set_generic_family(&mut self, generic: GenericFamily, families: Vec<String>) {
+        self.generic_families.insert(generic, families);
+    }
+
+    /// The installed family that a name refers to, as it's spelled in the fonts.
+    /// Generic family names (`sans-serif`, `serif`, `monospace`) resolve to the first installed family in their list.
+    pub fn resolve_family(&self, name: &str) -> Option<&str> {
+        let Some(generic) = GenericFamily::from_name(name) else {
+            return self.family_name(name);
+        };
+
+        match self.generic_families.get(&generic) {
+            Some(families) => families.iter().find_map(|family| self.family_name(family)),
+            None => generic
+                .default_families()
+                .iter()
+                .find_map(|family| self.family_name(family)),
+        }
+    }
+
+    /// Loads every face of a family into a [`Typeface`].
+    /// `family` can also be a generic family name; see [`FontDatabase::resolve_family`].
+    ///
+    /// Faces that can no longer be loaded (e.g. the file was removed since it was indexed) are logged and skipped;
+    /// this only fails if none of them can be.
+    pub fn typeface(&self, family: &str) -> Result<Typeface, FontError> {
+        let resolved = self
+            .resolve_family(family)
+            .ok_or_else(|| FontError::UnknownFamily(family.to_owned()))?;
+
+        // Group the faces by file, so font collections are only read once.
+        // Ordered by path, so that variants that tie in `Typeface::from_variants` always come out the same way
+        let mut files = BTreeMap::<&Path, Vec<u32>>::new();
+        self.family_faces(resolved).for_each(|face| {
+            files.entry(&face.path).or_default().push(face.index);
+        });
+
+        let mut variants = Vec::new();
+        let mut errors = Vec::new();
+        for (path, indices) in files {
+            let load = |error| FontError::load(FontOrigin::File(path.to_owned()), error);
+
+            match std::fs::read(path) {
+                Ok(data) => {
+                    let data = FontBytes::from(data);
+                    indices.into_iter().for_each(|index| {
+                        match Variant::from_bytes(data.clone(), index) {
+                            Ok(variant) => variants.push(variant),
+                            Err(error) => errors.push(load(error)),
+                        }
+                    })
+                }
+                Err(error) => errors.push(load(FontError::Io(error))),
+            }
+        }
+
+        if variants.is_empty() && !errors.is_empty() {
+            return Err(errors.swap_remove(0));
+        }
+        errors
+            .iter()
+            .for_each(|error| log::warn!("skipping font: {error}"));
+
+        Typeface::from_variants(variants)
+    }
+
+    /// Like [`FontDatabase::typeface`], but takes a list of families in order of preference,
+    /// like the CSS `font-family` property; the first one that's installed is loaded.
+    pub fn query(&self, families: &[&str]) -> Result<Typeface, FontError> {
+        families
+            .iter()
+            .find(|family| self.resolve_family(family).is_some())
+            .map(|family| self.typeface(family))
+            .unwrap_or_else(|| Err(FontError::UnknownFamily(families.join(", "))))
+    }
+}
+
+/// The standard font directories on Linux, following the XDG base directory spec,
+/// plus the legacy `~/.fonts`.
+/// Directories that don't exist are still included; scanning them is a no-op.
+pub fn system_font_directories() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let mut directories = Vec::new();
+
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".local/share")));
+    if let Some(data_home) = data_home {
+        directories.push(data_home.join("fonts"));
+    }
+    if let Some(home) = home {
+        directories.push(home.join(HOME_FONTS));
+    }
+
+    match std::env::var("XDG_DATA_DIRS") {
+        Ok(data_dirs) if !data_dirs.is_empty() => data_dirs
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .for_each(|dir| directories.push(Path::new(dir).join("fonts"))),
+        _ => DATA_DIRS_DEFAULT
+            .iter()
+            .for_each(|dir| directories.push(Path::new(dir).join("fonts"))),
+    }
+
+    directories
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTO: [(&str, &[u8]); 4] = [
+        (
+            "Roboto-Regular.ttf",
+            include_bytes!("../../../assets/Roboto/Roboto-Regular.ttf"),
+        ),
+        (
+            "Roboto-Italic.ttf",
+            include_bytes!("../../../assets/Roboto/Roboto-Italic.ttf"),
+        ),
+        (
+            "Roboto-Bold.ttf",
+            include_bytes!("../../../assets/Roboto/Roboto-Bold.ttf"),
+        ),
+        (
+            "Roboto-BoldItalic.ttf",
+            include_bytes!("../../../assets/Roboto/Roboto-BoldItalic.ttf"),
+        ),
+    ];
+
+    /// A directory with four Roboto faces in a subdirectory, a broken font, and a file that isn't a font
+    fn font_directory() -> tempfile::TempDir {
+        let directory = tempfile::tempdir().unwrap();
+        let fonts = directory.path().join("truetype/roboto");
+        std::fs::create_dir_all(&fonts).unwrap();
+        for (name, data) in ROBOTO {
+            std::fs::write(fonts.join(name), data).unwrap();
+        }
+        std::fs::write(directory.path().join("broken.ttf"), b"not a font").unwrap();
+        std::fs::write(directory.path().join("README.txt"), b"not a font either").unwrap();
+        directory
+    }
+
+    fn database() -> (FontDatabase, tempfile::TempDir) {
+        let directory = font_directory();
+        let mut database = FontDatabase::new();
+        database.scan_directory(directory.path());
+        (database, directory)
+    }
+
+    #[test]
+    fn scans_and_indexes() {
+        let directory = font_directory();
+        let mut database = FontDatabase::new();
+
+        let errors = database.scan_directory(directory.path());
+        assert_eq!(errors.len(), 1, "only the broken font fails: {errors:?}");
+        assert_eq!(database.families().collect::<Vec<_>>(), ["Roboto"]);
+
+        let mut faces = database
+            .family_faces("ROBOTO")
+            .map(|face| (face.weight, face.style))
+            .collect::<Vec<_>>();
+        faces.sort_by_key(|&(weight, style)| (weight, style as u8));
+        assert_eq!(
+            faces,
+            [
+                (400, FontStyle::Regular),
+                (400, FontStyle::Italic),
+                (700, FontStyle::Regular),
+                (700, FontStyle::Italic),
+            ]
+        );
+
+        // Scanning again doesn't index anything twice
+        database.scan_directory(directory.path());
+        assert_eq!(database.faces().len(), 4);
+    }
+
+    #[test]
+    fn skips_broken_faces_in_collections() {
+        // A collection of Roboto Regular, and a second face that points past the end of the file.
+        // Table offsets in a collection are from the start of the file, so they move with the font
+        let font = ROBOTO[0].1;
+        let header = 12 + 4 * 2;
+        let mut collection = b"ttcf\0\x01\0\0\0\0\0\x02".to_vec();
+        collection.extend((header as u32).to_be_bytes());
+        collection.extend(u32::MAX.to_be_bytes());
+        let mut font = font.to_vec();
+        let tables = u16::from_be_bytes([font[4], font[5]]) as usize;
+        for table in 0..tables {
+            let offset = 12 + 16 * table + 8;
+            let moved =
+                u32::from_be_bytes(font[offset..offset + 4].try_into().unwrap()) + header as u32;
+            font[offset..offset + 4].copy_from_slice(&moved.to_be_bytes());
+        }
+        collection.extend(font);
+
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::write(directory.path().join("roboto.ttc"), collection).unwrap();
+        let mut database = FontDatabase::new();
+        let errors = database.scan_directory(directory.path());
+
+        assert!(errors.is_empty(), "{errors:?}");
+        let faces = database.faces();
+        assert_eq!(faces.len(), 1);
+        assert_eq!((faces[0].family.as_str(), faces[0].index), ("Roboto", 0));
+    }
+
+    #[test]
+    fn resolves_generic_families() {
+        let (mut database, _directory) = database();
+        assert_eq!(database.resolve_family("sans-serif"), Some("Roboto"));
+        assert_eq!(database.resolve_family("roboto"), Some("Roboto"));
+        assert_eq!(database.resolve_family("serif"), None);
+
+        database.set_generic_family(
+            GenericFamily::SansSerif,
+            vec!["Missing".into(), "Roboto".into()],
+        );
+        assert_eq!(database.resolve_family("Sans-Serif"), Some("Roboto"));
+        database.set_generic_family(GenericFamily::Serif, vec!["Roboto".into()]);
+        assert_eq!(database.resolve_family("serif"), Some("Roboto"));
+        database.set_generic_family(GenericFamily::SansSerif, vec!["Missing".into()]);
+        assert_eq!(database.resolve_family("sans-serif"), None);
+    }
+
+    #[test]
+    fn queries_in_order() {
+        let (mut database, _directory) = database();
+        let typeface = database.query(&["Missing", "serif", "Roboto"]).unwrap();
+        assert_eq!(typeface.variants().len(), 4);
+        assert!(database.query(&["monospace", "sans-serif"]).is_ok());
+
+        // The first family that resolves wins, even when a later one would too
+        database.set_generic_family(GenericFamily::Monospace, vec!["Missing".into()]);
+        assert!(matches!(
+            database.query(&["monospace"]),
+            Err(FontError::UnknownFamily(_))
+        ));
+        database.set_generic_family(GenericFamily::Serif, vec!["Roboto".into()]);
+        let typeface = database.query(&["monospace", "serif", "Missing"]).unwrap();
+        assert_eq!(typeface.variants()[0].weight, 400);
+    }
+
+    #[test]
+    fn skips_unreadable_files() {
+        let (database, directory) = database();
+        std::fs::remove_file(directory.path().join("truetype/roboto/Roboto-Bold.ttf")).unwrap();
+        let typeface = database.typeface("Roboto").unwrap();
+        assert_eq!(typeface.variants().len(), 3);
+
+        ROBOTO.iter().for_each(|(name, _)| {
+            std::fs::remove_file(directory.path().join("truetype/roboto").join(name)).ok();
+        });
+        assert!(matches!(
+            database.typeface("Roboto"),
+            Err(FontError::Load { .. })
+        ));
+    }
+
+    #[test]
+    fn unknown_families() {
+        let (database, _directory) = database();
+        match database.query(&["Missing", "Other"]) {
+            Err(FontError::UnknownFamily(names)) => assert_eq!(names, "Missing, Other"),
+            other => panic!("expected an unknown family, got {:?}", other.err()),
+        }
+        assert!(matches!(
+            database.typeface("Missing"),
+            Err(FontError::UnknownFamily(name)) if name == "Missing"
+        ));
+    }
+}
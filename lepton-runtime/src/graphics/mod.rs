@@ -1,4 +1,19 @@
+/// Colors, and their conversion to the frame buffer format
+pub mod color;
+
 /// Defines rich text and display options for text elements
 pub mod text;
 
 pub mod typeface;
+
+/// Discovery and lookup of the fonts installed on the system
+pub mod font_database;
+
+/// Vector paths, and how they're filled and stroked
+pub mod path;
+
+/// Affine transformations, for moving, scaling and rotating paths and paints
+pub mod transform;
+
+/// Solid colors and gradients, for filling shapes and text
+pub mod paint;
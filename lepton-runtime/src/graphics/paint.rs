@@ -0,0 +1,377 @@
+use std::f32::consts::TAU;
+
+use palette::{Clamp, FromColor, LinSrgb, Oklab, Srgb};
+
+use super::color::Color;
+use super::path::Point;
+use super::transform::Transform;
+
+/// What a shape or text is filled with: a single color, or a gradient
+#[derive(Clone, PartialEq, Debug)]
+pub enum Paint {
+    Solid(Color),
+    Gradient(Gradient),
+}
+impl Paint {
+    /// The color at `point`, in the same coordinates as the paint's gradient
+    pub fn color_at(&self, point: Point) -> Color {
+        match self {
+            Paint::Solid(color) => *color,
+            Paint::Gradient(gradient) => gradient.color_at(point),
+        }
+    }
+
+    /// The same paint with its gradient, if any, transformed along with what it's drawn on
+    pub fn transformed(&self, transform: Transform) -> Paint {
+        match self {
+            Paint::Solid(color) => Paint::Solid(*color),
+            Paint::Gradient(gradient) => Paint::Gradient(
+                gradient
+                    .clone()
+                    .with_transform(gradient.transform.then(transform)),
+            ),
+        }
+    }
+}
+impl From<Color> for Paint {
+    fn from(color: Color) -> Self {
+        Paint::Solid(color)
+    }
+}
+impl From<Gradient> for Paint {
+    fn from(gradient: Gradient) -> Self {
+        Paint::Gradient(gradient)
+    }
+}
+
+/// The shape of a gradient, which maps every point to a position along its color stops
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GradientKind {
+    /// Changes along the line from `start` to `end`, and stays the same across it
+    Linear { start: Point, end: Point },
+    /// Changes from the `center` out to the circle of `radius` around it
+    Radial { center: Point, radius: f32 },
+    /// Changes going clockwise around the `center`, starting from `angle`,
+    /// in radians clockwise from pointing right
+    Conic { center: Point, angle: f32 },
+}
+impl GradientKind {
+    /// Position of `point` along the gradient, where `0` is the first stop and `1` is the last
+    fn position(self, point: Point) -> f32 {
+        match self {
+            GradientKind::Linear { start, end } => {
+                let direction = end - start;
+                let length = direction.x * direction.x + direction.y * direction.y;
+                if length == 0.0 {
+                    return 0.0;
+                }
+                let relative = point - start;
+                (relative.x * direction.x + relative.y * direction.y) / length
+            }
+            GradientKind::Radial { center, radius } => match radius > 0.0 {
+                true => point.distance(center) / radius,
+                false => 1.0,
+            },
+            GradientKind::Conic { center, angle } => {
+                let relative = point - center;
+                ((relative.y.atan2(relative.x) - angle) / TAU).rem_euclid(1.0)
+            }
+        }
+    }
+}
+
+/// What a gradient does past its first and last stops,
+/// analogous to the SVG `spreadMethod` attribute
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Spread {
+    /// Keeps the colors of the first and last stops
+    #[default]
+    Pad,
+    /// Starts over from the first stop
+    Repeat,
+    /// Goes back and forth between the first and last stops
+    Reflect,
+}
+impl Spread {
+    fn apply(self, position: f32) -> f32 {
+        match self {
+            Spread::Pad => position.clamp(0.0, 1.0),
+            Spread::Repeat => position.rem_euclid(1.0),
+            Spread::Reflect => 1.0 - (position.rem_euclid(2.0) - 1.0).abs(),
+        }
+    }
+}
+
+/// The color space that colors between stops are mixed in
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Interpolation {
+    /// Mixes the encoded sRGB values, like most design tools and older CSS do
+    #[default]
+    Srgb,
+    /// Mixes light intensities, which keeps the brightness even but makes dark colors spread out
+    Linear,
+    /// Mixes in a perceptual color space, which avoids the muddy middles of the other two
+    Oklab,
+}
+impl Interpolation {
+    /// Mixes the colors with premultiplied alpha, so that transparent stops don't tint their neighbors
+    fn mix(self, from: Color, to: Color, t: f32) -> Color {
+        let alpha = |color: Color| color.alpha as f32 / 255.0;
+        let (from_alpha, to_alpha) = (alpha(from), alpha(to));
+        let mixed_alpha = from_alpha + (to_alpha - from_alpha) * t;
+        if mixed_alpha <= 0.0 {
+            return super::color::TRANSPARENT;
+        }
+
+        let lerp = |a: [f32; 3], b: [f32; 3]| {
+            let channel = |i: usize| {
+                let premultiplied = a[i] * from_alpha + (b[i] * to_alpha - a[i] * from_alpha) * t;
+                premultiplied / mixed_alpha
+            };
+            [channel(0), channel(1), channel(2)]
+        };
+
+        let encoded = |color: Color| color.color.into_format::<f32>();
+        let linear = |color: Color| encoded(color).into_linear::<f32>();
+        let mixed: Srgb<f32> = match self {
+            Interpolation::Srgb => {
+                let (a, b) = (encoded(from), encoded(to));
+                let [red, green, blue] = lerp([a.red, a.green, a.blue], [b.red, b.green, b.blue]);
+                Srgb::new(red, green, blue)
+            }
+            Interpolation::Linear => {
+                let (a, b) = (linear(from), linear(to));
+                let [red, green, blue] = lerp([a.red, a.green, a.blue], [b.red, b.green, b.blue]);
+                Srgb::from_linear(LinSrgb::new(red, green, blue))
+            }
+            Interpolation::Oklab => {
+                let (a, b) = (
+                    Oklab::from_color(linear(from)),
+                    Oklab::from_color(linear(to)),
+                );
+                let [l, a, b] = lerp([a.l, a.a, a.b], [b.l, b.a, b.b]);
+                Srgb::from_linear(LinSrgb::from_color(Oklab::new(l, a, b)))
+            }
+        };
+
+        let mixed: Srgb<u8> = mixed.clamp().into_format();
+        Color::new(
+            mixed.red,
+            mixed.green,
+            mixed.blue,
+            (mixed_alpha * 255.0).round() as u8,
+        )
+    }
+}
+
+/// A color at a position along a gradient, from `0` to `1`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ColorStop {
+    pub offset: f32,
+    pub color: Color,
+}
+impl ColorStop {
+    pub fn new(offset: f32, color: Color) -> Self {
+        Self { offset, color }
+    }
+}
+
+/// A smooth transition between colors, analogous to the CSS gradient functions
+#[derive(Clone, PartialEq, Debug)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    /// Always sorted by offset
+    stops: Vec<ColorStop>,
+    pub spread: Spread,
+    pub interpolation: Interpolation,
+    /// From the coordinates of `kind` to the ones the gradient is drawn in,
+    /// like the SVG `gradientTransform` attribute
+    transform: Transform,
+    /// Kept along with `transform`, to map every pixel back;
+    /// `None` when the transform squashes the gradient flat
+    inverse: Option<Transform>,
+}
+impl Gradient {
+    /// A gradient with the given stops, which don't have to be sorted
+    pub fn new(kind: GradientKind, stops: impl IntoIterator<Item = ColorStop>) -> Self {
+        let mut stops = stops.into_iter().collect::<Vec<_>>();
+        stops.sort_by(|a, b| a.offset.total_cmp(&b.offset));
+        Self {
+            kind,
+            stops,
+            spread: Spread::default(),
+            interpolation: Interpolation::default(),
+            transform: Transform::IDENTITY,
+            inverse: Some(Transform::IDENTITY),
+        }
+    }
+
+    pub fn linear(start: Point, end: Point, stops: impl IntoIterator<Item = ColorStop>) -> Self {
+        Self::new(GradientKind::Linear { start, end }, stops)
+    }
+
+    pub fn radial(center: Point, radius: f32, stops: impl IntoIterator<Item = ColorStop>) -> Self {
+        Self::new(GradientKind::Radial { center, radius }, stops)
+    }
+
+    pub fn conic(center: Point, angle: f32, stops: impl IntoIterator<Item = ColorStop>) -> Self {
+        Self::new(GradientKind::Conic { center, angle }, stops)
+    }
+
+    pub fn with_spread(mut self, spread: Spread) -> Self {
+        self.spread = spread;
+        self
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// A transform that squashes the gradient flat leaves it showing only its first stop
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self.inverse = transform.inverse();
+        self
+    }
+
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    pub fn stops(&self) -> &[ColorStop] {
+        &self.stops
+    }
+
+    pub fn color_at(&self, point: Point) -> Color {
+        let Some(inverse) = self.inverse else {
+            return self
+                .stops
+                .first()
+                .map_or(super::color::TRANSPARENT, |stop| stop.color);
+        };
+        let point = inverse.apply(point);
+        let position = self.spread.apply(self.kind.position(point));
+
+        let after = self.stops.partition_point(|stop| stop.offset <= position);
+        match (
+            after.checked_sub(1).map(|i| self.stops[i]),
+            self.stops.get(after).copied(),
+        ) {
+            (None, None) => super::color::TRANSPARENT,
+            (Some(stop), None) | (None, Some(stop)) => stop.color,
+            (Some(before), Some(after)) => {
+                let t = (position - before.offset) / (after.offset - before.offset);
+                self.interpolation.mix(before.color, after.color, t)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::color::{BLACK, WHITE};
+
+    const RED: Color = Color::new(255, 0, 0, 255);
+    const BLUE: Color = Color::new(0, 0, 255, 255);
+
+    /// A gradient from `x = 0` to `x = 100`, to read off at `x = 100 * position`
+    fn horizontal(stops: impl IntoIterator<Item = ColorStop>) -> Gradient {
+        Gradient::linear(Point::new(0.0, 0.0), Point::new(100.0, 0.0), stops)
+    }
+
+    fn at(gradient: &Gradient, position: f32) -> Color {
+        gradient.color_at(Point::new(position * 100.0, 7.0))
+    }
+
+    #[test]
+    fn interpolates_between_stops() {
+        // Stops are sorted
+        let gradient = horizontal([ColorStop::new(1.0, WHITE), ColorStop::new(0.0, BLACK)]);
+        assert_eq!(at(&gradient, 0.0), BLACK);
+        assert_eq!(at(&gradient, 0.5), Color::new(128, 128, 128, 255));
+        assert_eq!(at(&gradient, 1.0), WHITE);
+
+        // Stops at the same offset make a hard edge, with the last one showing from there on
+        let gradient = horizontal([
+            ColorStop::new(0.2, RED),
+            ColorStop::new(0.5, RED),
+            ColorStop::new(0.5, BLUE),
+            ColorStop::new(0.8, BLUE),
+        ]);
+        assert_eq!(at(&gradient, 0.0), RED);
+        assert_eq!(at(&gradient, 0.49), RED);
+        assert_eq!(at(&gradient, 0.5), BLUE);
+        assert_eq!(at(&gradient, 1.0), BLUE);
+
+        assert_eq!(
+            at(&horizontal([]), 0.5),
+            crate::graphics::color::TRANSPARENT
+        );
+    }
+
+    #[test]
+    fn spreads_past_the_ends() {
+        let cases = [
+            (Spread::Pad, [(-0.5, 0.0), (0.25, 0.25), (1.5, 1.0)]),
+            (Spread::Repeat, [(-0.25, 0.75), (1.25, 0.25), (2.0, 0.0)]),
+            (Spread::Reflect, [(-0.25, 0.25), (1.25, 0.75), (2.0, 0.0)]),
+        ];
+        cases.into_iter().for_each(|(spread, positions)| {
+            positions.into_iter().for_each(|(position, expected)| {
+                let actual = spread.apply(position);
+                assert!(
+                    (actual - expected).abs() < 1e-6,
+                    "{spread:?} of {position} is {actual}, not {expected}"
+                );
+            });
+        });
+
+        let gradient = horizontal([ColorStop::new(0.0, BLACK), ColorStop::new(1.0, WHITE)])
+            .with_spread(Spread::Reflect);
+        assert_eq!(at(&gradient, 1.5), at(&gradient, 0.5));
+        assert_eq!(at(&gradient, -0.5), at(&gradient, 0.5));
+    }
+
+    #[test]
+    fn interpolation_spaces() {
+        let middle = |interpolation: Interpolation| {
+            let gradient = horizontal([ColorStop::new(0.0, BLACK), ColorStop::new(1.0, WHITE)])
+                .with_interpolation(interpolation);
+            at(&gradient, 0.5).red
+        };
+        assert_eq!(middle(Interpolation::Srgb), 128);
+        // Half of white's light
+        assert_eq!(middle(Interpolation::Linear), 188);
+        // Half of its perceived lightness, which is an eighth of its light
+        assert_eq!(middle(Interpolation::Oklab), 99);
+
+        // A transparent stop fades the other one out, without mixing in its own color
+        let transparent = Color::new(0, 0, 255, 0);
+        [
+            Interpolation::Srgb,
+            Interpolation::Linear,
+            Interpolation::Oklab,
+        ]
+        .into_iter()
+        .for_each(|interpolation| {
+            let mixed = interpolation.mix(RED, transparent, 0.5);
+            assert_eq!(mixed, Color::new(255, 0, 0, 128), "{interpolation:?}");
+        });
+    }
+
+    #[test]
+    fn gradient_kinds() {
+        let center = Point::new(10.0, 10.0);
+        let radial = GradientKind::Radial {
+            center,
+            radius: 20.0,
+        };
+        assert_eq!(radial.position(Point::new(10.0, 20.0)), 0.5);
+        // Clockwise on screen, with y pointing down
+        let conic = GradientKind::Conic { center, angle: 0.0 };
+        assert!((conic.position(Point::new(10.0, 30.0)) - 0.25).abs() < 1e-6);
+        assert!((conic.position(Point::new(0.0, 10.0)) - 0.5).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,424 @@
+use std::ops::{Add, Mul, Sub};
+
+use super::transform::Transform;
+
+/// A point with subpixel precision, in pixels from the top left
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+impl Point {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn distance(self, other: Point) -> f32 {
+        (other - self).length()
+    }
+
+    /// Length of the point as a vector from the origin
+    pub fn length(self) -> f32 {
+        self.x.hypot(self.y)
+    }
+
+    /// Linear interpolation towards `other`; `t = 0` is this point and `t = 1` is `other`
+    pub fn lerp(self, other: Point, t: f32) -> Point {
+        self + (other - self) * t
+    }
+}
+impl Add for Point {
+    type Output = Point;
+    fn add(self, other: Point) -> Point {
+        Point::new(self.x + other.x, self.y + other.y)
+    }
+}
+impl Sub for Point {
+    type Output = Point;
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+impl Mul<f32> for Point {
+    type Output = Point;
+    fn mul(self, scale: f32) -> Point {
+        Point::new(self.x * scale, self.y * scale)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PathSegment {
+    /// Starts a new subpath
+    MoveTo(Point),
+    LineTo(Point),
+    /// Quadratic Bézier curve, with a control point
+    QuadTo(Point, Point),
+    /// Cubic Bézier curve, with two control points
+    CubicTo(Point, Point, Point),
+    /// Closes the current subpath with a line back to its start
+    Close,
+}
+
+/// Which parts of a path that crosses over itself count as inside of it,
+/// analogous to the SVG `fill-rule` property
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum FillRule {
+    /// Inside if the path winds around the point a nonzero number of times, in either direction
+    #[default]
+    NonZero,
+    /// Inside if a line from the point crosses the path an odd number of times
+    EvenOdd,
+}
+
+/// Radii of the corners of a rounded rectangle, analogous to the CSS `border-radius` property
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct CornerRadii {
+    pub top_left: f32,
+    pub top_right: f32,
+    pub bottom_right: f32,
+    pub bottom_left: f32,
+}
+impl CornerRadii {
+    pub const fn all(radius: f32) -> Self {
+        Self {
+            top_left: radius,
+            top_right: radius,
+            bottom_right: radius,
+            bottom_left: radius,
+        }
+    }
+
+    /// Scales the radii down so that the corners on each side don't overlap, like CSS does
+    pub(crate) fn fit(self, width: f32, height: f32) -> Self {
+        let ratio = |length: f32, a: f32, b: f32| match a + b {
+            sum if sum > length => length / sum,
+            _ => 1.0,
+        };
+        let scale = [
+            ratio(width, self.top_left, self.top_right),
+            ratio(width, self.bottom_left, self.bottom_right),
+            ratio(height, self.top_left, self.bottom_left),
+            ratio(height, self.top_right, self.bottom_right),
+        ]
+        .into_iter()
+        .fold(1.0, f32::min);
+
+        let radius = |radius: f32| radius.max(0.0) * scale;
+        Self {
+            top_left: radius(self.top_left),
+            top_right: radius(self.top_right),
+            bottom_right: radius(self.bottom_right),
+            bottom_left: radius(self.bottom_left),
+        }
+    }
+}
+
+/// How far a cubic Bézier's control points are from the corner of a quarter circle,
+/// relative to its radius, for the closest approximation of the arc
+const KAPPA: f32 = 0.552_284_8;
+
+/// A vector outline made of lines and Bézier curves, in one or more subpaths
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Path {
+    segments: Vec<PathSegment>,
+}
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    pub fn move_to(mut self, point: Point) -> Self {
+        self.segments.push(PathSegment::MoveTo(point));
+        self
+    }
+
+    pub fn line_to(mut self, point: Point) -> Self {
+        self.segments.push(PathSegment::LineTo(point));
+        self
+    }
+
+    pub fn quad_to(mut self, control: Point, point: Point) -> Self {
+        self.segments.push(PathSegment::QuadTo(control, point));
+        self
+    }
+
+    pub fn cubic_to(mut self, control1: Point, control2: Point, point: Point) -> Self {
+        self.segments
+            .push(PathSegment::CubicTo(control1, control2, point));
+        self
+    }
+
+    pub fn close(mut self) -> Self {
+        self.segments.push(PathSegment::Close);
+        self
+    }
+
+    pub fn push(&mut self, segment: PathSegment) {
+        self.segments.push(segment);
+    }
+
+    /// A rectangle from its top left corner
+    pub fn rect(origin: Point, width: f32, height: f32) -> Self {
+        Path::new()
+            .move_to(origin)
+            .line_to(origin + Point::new(width, 0.0))
+            .line_to(origin + Point::new(width, height))
+            .line_to(origin + Point::new(0.0, height))
+            .close()
+    }
+
+    /// A rectangle with rounded corners, from its top left corner.
+    /// Radii that don't fit in the rectangle are scaled down
+    pub fn rounded_rect(origin: Point, width: f32, height: f32, radii: CornerRadii) -> Self {
+        let radii = radii.fit(width, height);
+        let (left, top) = (origin.x, origin.y);
+        let (right, bottom) = (left + width, top + height);
+
+        // Each corner goes clockwise, from the end of the side before it
+        let corner = |path: Path, from: Point, corner: Point, to: Point| {
+            path.line_to(from)
+                .cubic_to(from.lerp(corner, KAPPA), to.lerp(corner, KAPPA), to)
+        };
+
+        let path = Path::new().move_to(Point::new(left + radii.top_left, top));
+        let path = corner(
+            path,
+            Point::new(right - radii.top_right, top),
+            Point::new(right, top),
+            Point::new(right, top + radii.top_right),
+        );
+        let path = corner(
+            path,
+            Point::new(right, bottom - radii.bottom_right),
+            Point::new(right, bottom),
+            Point::new(right - radii.bottom_right, bottom),
+        );
+        let path = corner(
+            path,
+            Point::new(left + radii.bottom_left, bottom),
+            Point::new(left, bottom),
+            Point::new(left, bottom - radii.bottom_left),
+        );
+        let path = corner(
+            path,
+            Point::new(left, top + radii.top_left),
+            Point::new(left, top),
+            Point::new(left + radii.top_left, top),
+        );
+        path.close()
+    }
+
+    /// An ellipse from its center and radii
+    pub fn ellipse(center: Point, radius_x: f32, radius_y: f32) -> Self {
+        let (x, y) = (Point::new(radius_x, 0.0), Point::new(0.0, radius_y));
+        let quarter = |path: Path, from: Point, to: Point| {
+            let corner = center + (from - center) + (to - center);
+            path.cubic_to(from.lerp(corner, KAPPA), to.lerp(corner, KAPPA), to)
+        };
+
+        let path = Path::new().move_to(center + x);
+        let path = quarter(path, center + x, center + y);
+        let path = quarter(path, center + y, center - x);
+        let path = quarter(path, center - x, center - y);
+        let path = quarter(path, center - y, center + x);
+        path.close()
+    }
+
+    pub fn circle(center: Point, radius: f32) -> Self {
+        Self::ellipse(center, radius, radius)
+    }
+
+    /// A single straight line; this only shows up when stroked
+    pub fn line(from: Point, to: Point) -> Self {
+        Path::new().move_to(from).line_to(to)
+    }
+
+    /// The same path with every point transformed; Bézier curves stay exact under affine transforms
+    pub fn transformed(&self, transform: Transform) -> Path {
+        let apply = |point| transform.apply(point);
+        let segments = self
+            .segments
+            .iter()
+            .map(|&segment| match segment {
+                PathSegment::MoveTo(point) => PathSegment::MoveTo(apply(point)),
+                PathSegment::LineTo(point) => PathSegment::LineTo(apply(point)),
+                PathSegment::QuadTo(control, point) => {
+                    PathSegment::QuadTo(apply(control), apply(point))
+                }
+                PathSegment::CubicTo(control1, control2, point) => {
+                    PathSegment::CubicTo(apply(control1), apply(control2), apply(point))
+                }
+                PathSegment::Close => PathSegment::Close,
+            })
+            .collect();
+        Path { segments }
+    }
+
+    /// Approximates the path with straight lines,
+    /// that are never further than `tolerance` pixels from the curves.
+    /// The ends of each segment are marked as [`Polyline::corners`]; the points in between aren't
+    pub fn flatten(&self, tolerance: f32) -> Vec<Polyline> {
+        let mut result = Vec::new();
+        let mut current = Polyline::default();
+        let mut start = Point::default();
+        let mut pen = Point::default();
+
+        let mut finish = |current: &mut Polyline| {
+            let polyline = std::mem::take(current);
+            if polyline.points.len() > 1 {
+                result.push(polyline);
+            }
+        };
+
+        for &segment in &self.segments {
+            match segment {
+                PathSegment::MoveTo(point) => {
+                    finish(&mut current);
+                    current.push(point, true);
+                    start = point;
+                    pen = point;
+                    continue;
+                }
+                PathSegment::Close => {
+                    current.closed = true;
+                    finish(&mut current);
+                    pen = start;
+                    continue;
+                }
+                _ => (),
+            }
+
+            // Drawing without a `MoveTo` first starts from wherever the pen is
+            if current.points.is_empty() {
+                current.push(pen, true);
+            }
+
+            match segment {
+                PathSegment::LineTo(point) => current.push(point, true),
+                PathSegment::QuadTo(control, point) => {
+                    let deviation = (pen - control * 2.0 + point).length();
+                    let count = subdivisions(deviation / 4.0, tolerance);
+                    (1..=count).for_each(|index| {
+                        let t = index as f32 / count as f32;
+                        let at = pen.lerp(control, t).lerp(control.lerp(point, t), t);
+                        current.push(at, index == count);
+                    });
+                }
+                PathSegment::CubicTo(control1, control2, point) => {
+                    let deviation = f32::max(
+                        (pen - control1 * 2.0 + control2).length(),
+                        (control1 - control2 * 2.0 + point).length(),
+                    );
+                    let count = subdivisions(deviation * 3.0 / 4.0, tolerance);
+                    (1..=count).for_each(|index| {
+                        let t = index as f32 / count as f32;
+                        let (a, b, c) = (
+                            pen.lerp(control1, t),
+                            control1.lerp(control2, t),
+                            control2.lerp(point, t),
+                        );
+                        current.push(a.lerp(b, t).lerp(b.lerp(c, t), t), index == count);
+                    });
+                }
+                PathSegment::MoveTo(_) | PathSegment::Close => unreachable!(),
+            }
+            pen = current.points.last().copied().unwrap_or(pen);
+        }
+        finish(&mut current);
+
+        result
+    }
+}
+
+/// Number of straight lines to split a curve into, so that it stays within `tolerance`;
+/// `deviation` is how far the curve's second derivative can take it from a straight line
+fn subdivisions(deviation: f32, tolerance: f32) -> usize {
+    ((deviation / tolerance.max(0.001)).sqrt().ceil() as usize).clamp(1, 256)
+}
+
+/// A subpath, approximated with straight lines
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Polyline {
+    pub points: Vec<Point>,
+    /// Whether the last point connects back to the first
+    pub closed: bool,
+    /// For each point, whether it's a vertex of the original path,
+    /// rather than somewhere along a flattened curve.
+    /// Strokes only get their [`LineJoin`] at corners; points without an entry here count as corners
+    pub corners: Vec<bool>,
+}
+impl Polyline {
+    fn push(&mut self, point: Point, corner: bool) {
+        self.points.push(point);
+        self.corners.push(corner);
+    }
+
+    pub fn is_corner(&self, index: usize) -> bool {
+        self.corners.get(index).copied().unwrap_or(true)
+    }
+}
+
+/// How the ends of open subpaths are drawn when stroking, analogous to the SVG `stroke-linecap` property
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum LineCap {
+    /// The line stops right at its end
+    #[default]
+    Butt,
+    /// A half circle past the end
+    Round,
+    /// A half square past the end
+    Square,
+}
+
+/// How corners are drawn when stroking, analogous to the SVG `stroke-linejoin` property
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum LineJoin {
+    /// The outer edges carry on until they meet, unless that's further than the [`Stroke::miter_limit`],
+    /// in which case it's a bevel
+    #[default]
+    Miter,
+    /// A circular arc around the corner
+    Round,
+    /// The outer edges are connected with a straight line
+    Bevel,
+}
+
+/// How a path is outlined
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Stroke {
+    pub width: f32,
+    pub cap: LineCap,
+    pub join: LineJoin,
+    /// How long a miter can get relative to the stroke width before it's beveled instead,
+    /// analogous to the SVG `stroke-miterlimit` property
+    pub miter_limit: f32,
+}
+impl Stroke {
+    pub fn new(width: f32) -> Self {
+        Self {
+            width,
+            cap: LineCap::default(),
+            join: LineJoin::default(),
+            miter_limit: 4.0,
+        }
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn with_miter_limit(mut self, miter_limit: f32) -> Self {
+        self.miter_limit = miter_limit;
+        self
+    }
+}
@@ -1,64 +1,361 @@
-use std::str::Chars;
+use std::collections::BTreeMap;
+use std::ops::Range;
 
-use fontdue::Font;
+use unicode_linebreak::BreakOpportunity;
+use unicode_segmentation::{GraphemeIndices, UnicodeSegmentation};
 
 use crate::render::Pixel;
 
-use super::typeface::{FontStyle, FontWeight, Typeface};
+use super::color::{self, Color};
+use super::typeface::{FontStyle, FontWeight, Typeface, Variant, Weight};
 
-/// Rich text, no idea what this will look like
+/// Lines drawn along with the text, analogous to the CSS `text-decoration-line` property
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Decoration {
+    pub underline: bool,
+    pub overline: bool,
+    pub strikethrough: bool,
+}
+impl Decoration {
+    pub const NONE: Self = Self {
+        underline: false,
+        overline: false,
+        strikethrough: false,
+    };
+    pub const UNDERLINE: Self = Self {
+        underline: true,
+        ..Self::NONE
+    };
+    pub const OVERLINE: Self = Self {
+        overline: true,
+        ..Self::NONE
+    };
+    pub const STRIKETHROUGH: Self = Self {
+        strikethrough: true,
+        ..Self::NONE
+    };
+}
+
+/// A single rich text attribute, applied to a range of a [`RichString`] with a [`Span`]
+#[derive(Clone, Copy)]
+pub enum Attribute {
+    Typeface(&'static Typeface),
+    Size(Pixel),
+    Weight(Weight),
+    Style(FontStyle),
+    Color(Color),
+    /// Extra space after each character, in pixels; can be negative
+    LetterSpacing(f32),
+    Decoration(Decoration),
+}
+impl Attribute {
+    pub fn kind(&self) -> AttributeKind {
+        match self {
+            Attribute::Typeface(_) => AttributeKind::Typeface,
+            Attribute::Size(_) => AttributeKind::Size,
+            Attribute::Weight(_) => AttributeKind::Weight,
+            Attribute::Style(_) => AttributeKind::Style,
+            Attribute::Color(_) => AttributeKind::Color,
+            Attribute::LetterSpacing(_) => AttributeKind::LetterSpacing,
+            Attribute::Decoration(_) => AttributeKind::Decoration,
+        }
+    }
+}
+
+/// The kinds of [`Attribute`], without their values; used to remove or query attributes
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AttributeKind {
+    Typeface,
+    Size,
+    Weight,
+    Style,
+    Color,
+    LetterSpacing,
+    Decoration,
+}
+
+/// An attribute applied to a range of bytes in a [`RichString`].
+///
+/// Spans of the same [`AttributeKind`] never overlap; applying an attribute replaces that kind over the range.
+#[derive(Clone)]
+pub struct Span {
+    pub range: Range<usize>,
+    pub attribute: Attribute,
+}
+
+/// Every attribute, resolved to a value for some part of the text
+#[derive(Clone, Copy)]
+pub struct Attributes {
+    pub typeface: &'static Typeface,
+    pub size: Pixel,
+    pub weight: Weight,
+    pub style: FontStyle,
+    pub color: Color,
+    pub letter_spacing: f32,
+    pub decoration: Decoration,
+}
+impl Attributes {
+    pub fn new(typeface: &'static Typeface) -> Self {
+        Self {
+            typeface,
+            size: 32,
+            weight: FontWeight::Normal.into(),
+            style: FontStyle::Regular,
+            color: color::WHITE,
+            letter_spacing: 0.0,
+            decoration: Decoration::NONE,
+        }
+    }
+
+    pub fn set(&mut self, attribute: Attribute) {
+        match attribute {
+            Attribute::Typeface(typeface) => self.typeface = typeface,
+            Attribute::Size(size) => self.size = size,
+            Attribute::Weight(weight) => self.weight = weight,
+            Attribute::Style(style) => self.style = style,
+            Attribute::Color(color) => self.color = color,
+            Attribute::LetterSpacing(spacing) => self.letter_spacing = spacing,
+            Attribute::Decoration(decoration) => self.decoration = decoration,
+        }
+    }
+}
+
+/// A line break opportunity, as determined by the Unicode line breaking algorithm (UAX #14)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LineBreak {
+    /// The line can be broken here, e.g. after a space
+    Allowed,
+    /// The line has to be broken here, e.g. after a newline
+    Mandatory,
+}
+
+/// A range of text over which the attributes don't change
+pub struct Run {
+    pub range: Range<usize>,
+    pub attributes: Attributes,
+}
+
+/// Text with [`Attribute`]s applied to byte ranges.
+/// Any text not covered by a span of some kind uses the default for that kind.
 pub struct RichString {
     contents: String,
 
-    /// The "default" typeface of the text.
+    /// Attributes for the text that's not covered by a span.
     ///
-    /// Eventually we will add attribution for rich text,
-    /// but the truncation ellipsis will be in the default typeface.
-    typeface: &'static Typeface,
+    /// The typeface here is the "default" typeface of the text;
+    /// the truncation ellipsis will be in this typeface if the text's own font doesn't have one.
+    defaults: Attributes,
+
+    spans: Vec<Span>,
 
-    /// This will probably be completely replaced by attribution
-    size: Pixel,
+    /// Typefaces to try, in order, for characters that the typeface
+    /// (and its own fallback chain) has no glyph for
+    fallbacks: Vec<&'static Typeface>,
 }
 impl RichString {
     pub fn new(contents: String, typeface: &'static Typeface) -> Self {
         Self {
             contents,
-            typeface,
-            size: 32,
+            defaults: Attributes::new(typeface),
+            spans: Vec::new(),
+            fallbacks: Vec::new(),
         }
     }
 
-    pub fn rich_iter(&self) -> RichIterator {
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    /// Attributes for the text that's not covered by a span
+    pub fn defaults(&self) -> &Attributes {
+        &self.defaults
+    }
+
+    /// Appends a typeface to the end of this string's fallback chain,
+    /// which is tried after the typeface's own fallback chain
+    pub fn with_fallback(mut self, fallback: &'static Typeface) -> Self {
+        self.fallbacks.push(fallback);
+        self
+    }
+
+    /// Sets the attribute for all text that isn't covered by a span of the same kind
+    pub fn with_default(mut self, attribute: Attribute) -> Self {
+        self.defaults.set(attribute);
+        self
+    }
+
+    /// Builder version of [`RichString::apply`]
+    pub fn with(mut self, range: Range<usize>, attribute: Attribute) -> Self {
+        self.apply(range, attribute);
+        self
+    }
+
+    /// Applies an attribute to a byte range, replacing any attribute of the same kind there.
+    ///
+    /// Panics if the range doesn't fall on `char` boundaries, like slicing a `str` would.
+    pub fn apply(&mut self, range: Range<usize>, attribute: Attribute) {
+        self.remove(range.clone(), attribute.kind());
+        if !range.is_empty() {
+            self.spans.push(Span { range, attribute });
+        }
+    }
+
+    /// Removes any attribute of the given kind from a byte range,
+    /// trimming or splitting the spans that extend outside of it.
+    ///
+    /// Panics if the range doesn't fall on `char` boundaries, like slicing a `str` would.
+    pub fn remove(&mut self, range: Range<usize>, kind: AttributeKind) {
+        assert!(
+            self.contents.get(range.clone()).is_some(),
+            "span range {range:?} is not on char boundaries"
+        );
+
+        let spans = std::mem::take(&mut self.spans);
+        for span in spans {
+            let overlaps = span.range.start < range.end && range.start < span.range.end;
+            if span.attribute.kind() != kind || !overlaps {
+                self.spans.push(span);
+                continue;
+            }
+
+            if span.range.start < range.start {
+                self.spans.push(Span {
+                    range: span.range.start..range.start,
+                    attribute: span.attribute,
+                });
+            }
+            if range.end < span.range.end {
+                self.spans.push(Span {
+                    range: range.end..span.range.end,
+                    attribute: span.attribute,
+                });
+            }
+        }
+    }
+
+    /// Every span, in no particular order
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// The spans that cover the byte at `index`
+    pub fn spans_at(&self, index: usize) -> impl Iterator<Item = &Span> {
+        self.spans
+            .iter()
+            .filter(move |span| span.range.contains(&index))
+    }
+
+    /// The resolved attributes of the byte at `index`
+    pub fn attributes_at(&self, index: usize) -> Attributes {
+        let mut attributes = self.defaults;
+        self.spans_at(index)
+            .for_each(|span| attributes.set(span.attribute));
+        attributes
+    }
+
+    /// Splits the text into ranges over which the attributes don't change, in order
+    pub fn runs(&self) -> Vec<Run> {
+        let mut boundaries = vec![0, self.contents.len()];
+        self.spans.iter().for_each(|span| {
+            boundaries.push(span.range.start);
+            boundaries.push(span.range.end);
+        });
+        boundaries.sort_unstable();
+        boundaries.dedup();
+
+        boundaries
+            .windows(2)
+            .map(|window| Run {
+                range: window[0]..window[1],
+                attributes: self.attributes_at(window[0]),
+            })
+            .collect()
+    }
+
+    /// The first font in the fallback chain that has glyphs for the whole grapheme `cluster`;
+    /// the chain is the run's typeface, then the default typeface, then the string's fallbacks.
+    /// If there isn't one, we use the run's typeface and let it render notdef.
+    fn resolve_font(&self, attributes: &Attributes, cluster: &str) -> &'static Variant {
+        let (weight, style) = (attributes.weight, attributes.style);
+
+        [attributes.typeface, self.defaults.typeface]
+            .into_iter()
+            .chain(self.fallbacks.iter().copied())
+            .find_map(|typeface| typeface.match_glyphs(weight, style, cluster))
+            .unwrap_or_else(|| attributes.typeface.match_variant(weight, style))
+    }
+
+    /// Line break opportunities according to UAX #14, by the byte index they occur **before**.
+    /// This includes the mandatory break at the very end of the text.
+    pub fn line_breaks(&self) -> BTreeMap<usize, LineBreak> {
+        unicode_linebreak::linebreaks(&self.contents)
+            .map(|(index, opportunity)| {
+                let line_break = match opportunity {
+                    BreakOpportunity::Mandatory => LineBreak::Mandatory,
+                    BreakOpportunity::Allowed => LineBreak::Allowed,
+                };
+                (index, line_break)
+            })
+            .collect()
+    }
+
+    pub fn rich_iter(&self) -> RichIterator<'_> {
         RichIterator {
-            chars: self.contents.chars(),
+            clusters: self.contents.grapheme_indices(true),
+            runs: self.runs().into_iter(),
+            run: None,
             string: self,
         }
     }
 }
 
-/// An abstract character in rich text.
-/// (Right now it's just a single `char`, but this will become a glyph cluster when we implement that)
-pub struct RichChar {
-    /// This will be replaced by a cluster when we implement segmentation
-    pub(crate) char: char,
-    pub(crate) font: &'static Font,
-    pub size: Pixel,
+/// An abstract character in rich text: a single extended grapheme cluster,
+/// which is what a user would think of as one character.
+/// Combining marks, flags, and emoji ZWJ sequences all stay together.
+pub struct RichChar<'a> {
+    pub(crate) cluster: &'a str,
+    /// Byte offset of the cluster in the string
+    pub(crate) index: usize,
+
+    /// The first font in the fallback chain that has glyphs for this cluster
+    pub(crate) font: &'static Variant,
+    /// The font from the string's default typeface, regardless of fallbacks;
+    /// used for things like the truncation ellipsis
+    pub(crate) base_font: &'static Variant,
+
+    pub attributes: Attributes,
 }
 
 // We use the iterator to deal with rich text attributes, style changes and stuff
 pub struct RichIterator<'a> {
     string: &'a RichString,
-    chars: Chars<'a>,
+    clusters: GraphemeIndices<'a>,
+    runs: std::vec::IntoIter<Run>,
+    run: Option<Run>,
 }
-impl<'a> RichIterator<'a> {
-    pub fn next(&mut self) -> Option<RichChar> {
-        self.chars.next().map(|char| RichChar {
-            char,
-            font: self
+impl<'a> Iterator for RichIterator<'a> {
+    type Item = RichChar<'a>;
+
+    fn next(&mut self) -> Option<RichChar<'a>> {
+        let (index, cluster) = self.clusters.next()?;
+
+        // The runs cover the whole string, so we'll always find one
+        while !matches!(&self.run, Some(run) if run.range.contains(&index)) {
+            self.run = Some(self.runs.next()?);
+        }
+        let attributes = self.run.as_ref()?.attributes;
+
+        // If the attributes change in the middle of a cluster, the start of the cluster wins
+        Some(RichChar {
+            cluster,
+            index,
+            font: self.string.resolve_font(&attributes, cluster),
+            base_font: self
                 .string
+                .defaults
                 .typeface
-                .match_style(FontWeight::Normal.into(), FontStyle::Regular),
-            size: self.string.size,
+                .match_variant(attributes.weight, attributes.style),
+            attributes,
         })
     }
 }
@@ -79,10 +376,8 @@ pub enum Wrapping {
     /// Only a single line.  Raises an error if truncation happens
     SingleLine,
 
-    /// Currently, somewhat naïve word boundary-based wrapping;
+    /// Wrapping at the line break opportunities from the Unicode line breaking algorithm (UAX #14);
     /// **unless** [`WORD_WRAP_LENGTH_THRESHOLD`] is reached, then it breaks by character for that line
-    ///
-    /// This is very eurocentric; we need to explore internationalization.
     Word,
 
     /// Line breaks occur between _grapheme clusters_
@@ -96,15 +391,159 @@ pub enum Direction {
     // that can be converted to/from the normal Size struct
 }
 
+/// Height of each line of text, analogous to the CSS `line-height` property.
+/// The difference to the height of the font is split evenly above and below the text.
+#[derive(Clone, Copy)]
+pub enum LineHeight {
+    /// The font's own line height: its ascent, descent and line gap
+    Normal,
+    /// A fixed line height
+    Absolute(Pixel),
+    /// A multiple of the font size
+    Multiplier(f32),
+}
+
 pub struct TextOptions {
     pub wrapping: Wrapping,
     pub direction: Direction,
+    pub line_height: LineHeight,
+
+    /// Extra space after each paragraph; that is, after each newline or U+2029 PARAGRAPH SEPARATOR
+    pub paragraph_spacing: Pixel,
 }
 impl Default for TextOptions {
     fn default() -> Self {
         Self {
             wrapping: Wrapping::Word,
             direction: Direction::Horizontal,
+            line_height: LineHeight::Normal,
+            paragraph_spacing: 0,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+
+    use super::*;
+
+    fn typeface() -> &'static Typeface {
+        static MEM: OnceLock<Typeface> = OnceLock::new();
+        MEM.get_or_init(|| Typeface::try_roboto().unwrap())
+    }
+
+    fn string(contents: &str) -> RichString {
+        RichString::new(contents.to_string(), typeface())
+    }
+
+    /// The ranges and sizes of the runs, to compare them in one go
+    fn sizes(string: &RichString) -> Vec<(Range<usize>, Pixel)> {
+        string
+            .runs()
+            .into_iter()
+            .map(|run| (run.range, run.attributes.size))
+            .collect()
+    }
+
+    #[test]
+    fn overlapping_apply_replaces() {
+        let string = string("0123456789")
+            .with(2..6, Attribute::Size(10))
+            .with(4..8, Attribute::Size(20));
+        assert_eq!(
+            sizes(&string),
+            [(0..2, 32), (2..4, 10), (4..8, 20), (8..10, 32)]
+        );
+        // Spans of one kind never overlap
+        assert_eq!(string.spans_at(5).count(), 1);
+
+        // Other kinds are left alone
+        let string = string.with(0..10, Attribute::Weight(700));
+        assert_eq!(string.attributes_at(5).size, 20);
+        assert_eq!(string.attributes_at(5).weight, 700);
+        assert_eq!(string.spans_at(5).count(), 2);
+    }
+
+    #[test]
+    fn remove_splits_spans() {
+        let mut string = string("0123456789")
+            .with(0..10, Attribute::Size(10))
+            .with(0..10, Attribute::Weight(700));
+        string.remove(3..5, AttributeKind::Size);
+        assert_eq!(sizes(&string), [(0..3, 10), (3..5, 32), (5..10, 10)]);
+        assert!((0..10).all(|index| string.attributes_at(index).weight == 700));
+
+        // Removing where there's nothing does nothing
+        string.remove(3..5, AttributeKind::Size);
+        assert_eq!(string.spans().len(), 3);
+        string.remove(0..10, AttributeKind::Size);
+        assert_eq!(sizes(&string), [(0..10, 32)]);
+    }
+
+    #[test]
+    fn span_boundaries() {
+        let string = string("0123456789").with(3..6, Attribute::Size(10));
+        // Ranges are half-open, like slices
+        assert_eq!(string.attributes_at(2).size, 32);
+        assert_eq!(string.attributes_at(3).size, 10);
+        assert_eq!(string.attributes_at(5).size, 10);
+        assert_eq!(string.attributes_at(6).size, 32);
+        assert_eq!(sizes(&string), [(0..3, 32), (3..6, 10), (6..10, 32)]);
+
+        // Spans touching the ends don't leave empty runs
+        let string = string
+            .with(0..3, Attribute::Size(20))
+            .with(6..10, Attribute::Size(20));
+        assert_eq!(sizes(&string), [(0..3, 20), (3..6, 10), (6..10, 20)]);
+        // Empty ranges don't add spans
+        let string = string.with(5..5, Attribute::Weight(700));
+        assert_eq!(string.spans().len(), 3);
+    }
+
+    /// The line break opportunities, as the text before each one
+    fn breaks(contents: &str) -> Vec<(&str, LineBreak)> {
+        string(contents)
+            .line_breaks()
+            .into_iter()
+            .map(|(index, line_break)| (&contents[..index], line_break))
+            .collect()
+    }
+
+    #[test]
+    fn line_breaks() {
+        use LineBreak::*;
+        assert_eq!(
+            breaks("don't stop"),
+            [("don't ", Allowed), ("don't stop", Mandatory)]
+        );
+        assert_eq!(
+            breaks("pi 3.14"),
+            [("pi ", Allowed), ("pi 3.14", Mandatory)]
+        );
+        // No break at a no-break space
+        assert_eq!(
+            breaks("go a\u{A0}b"),
+            [("go ", Allowed), ("go a\u{A0}b", Mandatory)]
+        );
+        assert_eq!(
+            breaks("a\nb\r\nc"),
+            [
+                ("a\n", Mandatory),
+                ("a\nb\r\n", Mandatory),
+                ("a\nb\r\nc", Mandatory)
+            ]
+        );
+        assert_eq!(
+            breaks("a\u{2028}b"),
+            [("a\u{2028}", Mandatory), ("a\u{2028}b", Mandatory)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "not on char boundaries")]
+    fn apply_inside_a_char_panics() {
+        // 'é' is two bytes long
+        string("café").apply(0..4, Attribute::Size(10));
+    }
+}
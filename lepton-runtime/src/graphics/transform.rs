@@ -0,0 +1,196 @@
+use super::path::Point;
+
+/// A 2D affine transformation, like the SVG `matrix(a, b, c, d, e, f)` transform:
+/// `x' = a * x + c * y + e` and `y' = b * x + d * y + f`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+impl Transform {
+    pub const IDENTITY: Self = Self::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+
+    pub const fn new(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Self {
+        Self { a, b, c, d, e, f }
+    }
+
+    pub const fn translate(x: f32, y: f32) -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, x, y)
+    }
+
+    pub const fn scale(x: f32, y: f32) -> Self {
+        Self::new(x, 0.0, 0.0, y, 0.0, 0.0)
+    }
+
+    /// Clockwise on screen, since the y axis points down
+    pub fn rotate(radians: f32) -> Self {
+        let (sin, cos) = radians.sin_cos();
+        Self::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    /// Slants the x axis by `x` radians and the y axis by `y` radians, like the CSS `skew()` function
+    pub fn skew(x: f32, y: f32) -> Self {
+        Self::new(1.0, y.tan(), x.tan(), 1.0, 0.0, 0.0)
+    }
+
+    /// This transform, followed by `other`
+    pub fn then(self, other: Transform) -> Self {
+        Self {
+            a: other.a * self.a + other.c * self.b,
+            b: other.b * self.a + other.d * self.b,
+            c: other.a * self.c + other.c * self.d,
+            d: other.b * self.c + other.d * self.d,
+            e: other.a * self.e + other.c * self.f + other.e,
+            f: other.b * self.e + other.d * self.f + other.f,
+        }
+    }
+
+    pub fn apply(self, point: Point) -> Point {
+        Point::new(
+            self.a * point.x + self.c * point.y + self.e,
+            self.b * point.x + self.d * point.y + self.f,
+        )
+    }
+
+    /// The transform that undoes this one, unless it squashes everything onto a line or a point
+    pub fn inverse(self) -> Option<Self> {
+        let determinant = self.determinant();
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+        let (a, b, c, d) = (
+            self.d / determinant,
+            -self.b / determinant,
+            -self.c / determinant,
+            self.a / determinant,
+        );
+        Some(Self {
+            a,
+            b,
+            c,
+            d,
+            e: -(a * self.e + c * self.f),
+            f: -(b * self.e + d * self.f),
+        })
+    }
+
+    fn determinant(self) -> f32 {
+        self.a * self.d - self.b * self.c
+    }
+
+    /// How much lengths are scaled on average; exact for transforms that scale both axes the same.
+    /// Used for things like stroke widths, that can't be stretched in one direction
+    pub fn scale_factor(self) -> f32 {
+        self.determinant().abs().sqrt()
+    }
+
+    pub fn is_identity(self) -> bool {
+        self == Self::IDENTITY
+    }
+
+    /// `true` if this only moves things, without scaling, rotating or skewing them
+    pub fn is_translation(self) -> bool {
+        (self.a, self.b, self.c, self.d) == (1.0, 0.0, 0.0, 1.0)
+    }
+
+    /// The scale, if this only scales both axes the same way and moves things,
+    /// without rotating, skewing or flipping them
+    pub fn uniform_scale(self) -> Option<f32> {
+        (self.b == 0.0 && self.c == 0.0 && self.a == self.d && self.a > 0.0).then_some(self.a)
+    }
+}
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Point, expected: Point) {
+        assert!(
+            actual.distance(expected) < 1e-4,
+            "{actual:?} isn't {expected:?}"
+        );
+    }
+
+    #[test]
+    fn then_applies_in_order() {
+        let point = Point::new(3.0, -2.0);
+        let (scale, translate) = (Transform::scale(2.0, 3.0), Transform::translate(10.0, 20.0));
+        assert_close(scale.then(translate).apply(point), Point::new(16.0, 14.0));
+        assert_close(translate.then(scale).apply(point), Point::new(26.0, 54.0));
+
+        // Clockwise on screen: right turns into down
+        let rotate = Transform::rotate(std::f32::consts::FRAC_PI_2);
+        assert_close(rotate.apply(Point::new(1.0, 0.0)), Point::new(0.0, 1.0));
+        let chained = rotate.then(translate).then(scale);
+        let expected = scale.apply(translate.apply(rotate.apply(point)));
+        assert_close(chained.apply(point), expected);
+        assert_eq!(Transform::IDENTITY.then(chained), chained);
+        assert_eq!(chained.then(Transform::IDENTITY), chained);
+    }
+
+    #[test]
+    fn inverse_undoes() {
+        let transform = Transform::rotate(0.7)
+            .then(Transform::skew(0.2, -0.1))
+            .then(Transform::scale(2.0, 0.5))
+            .then(Transform::translate(-4.0, 9.0));
+        let inverse = transform.inverse().unwrap();
+        [
+            Point::new(0.0, 0.0),
+            Point::new(3.0, -2.0),
+            Point::new(-50.0, 7.5),
+        ]
+        .into_iter()
+        .for_each(|point| {
+            assert_close(inverse.apply(transform.apply(point)), point);
+            assert_close(transform.apply(inverse.apply(point)), point);
+        });
+
+        // Squashed flat, there's no way back
+        assert_eq!(Transform::scale(0.0, 1.0).inverse(), None);
+        assert_eq!(Transform::new(1.0, 2.0, 2.0, 4.0, 0.0, 0.0).inverse(), None);
+        assert_eq!(Transform::scale(f32::INFINITY, 1.0).inverse(), None);
+    }
+
+    #[test]
+    fn skew() {
+        let angle = std::f32::consts::FRAC_PI_4;
+        // Skewing along x moves points sideways by how far down they are
+        let skew_x = Transform::skew(angle, 0.0);
+        assert_close(skew_x.apply(Point::new(0.0, 2.0)), Point::new(2.0, 2.0));
+        assert_close(skew_x.apply(Point::new(3.0, 0.0)), Point::new(3.0, 0.0));
+        let skew_y = Transform::skew(0.0, angle);
+        assert_close(skew_y.apply(Point::new(2.0, 0.0)), Point::new(2.0, 2.0));
+        assert_close(skew_y.apply(Point::new(0.0, 3.0)), Point::new(0.0, 3.0));
+
+        // Skewing keeps areas
+        assert!((skew_x.scale_factor() - 1.0).abs() < 1e-6);
+        assert!(!skew_x.is_translation());
+        assert_eq!(skew_x.uniform_scale(), None);
+    }
+
+    #[test]
+    fn classification() {
+        assert!(Transform::translate(1.5, -3.0).is_translation());
+        assert_eq!(
+            Transform::scale(2.0, 2.0)
+                .then(Transform::translate(1.0, 1.0))
+                .uniform_scale(),
+            Some(2.0)
+        );
+        assert_eq!(Transform::scale(2.0, 3.0).uniform_scale(), None);
+        // Flipped
+        assert_eq!(Transform::scale(-2.0, -2.0).uniform_scale(), None);
+        assert_eq!(Transform::rotate(0.1).uniform_scale(), None);
+        assert!((Transform::scale(2.0, 8.0).scale_factor() - 4.0).abs() < 1e-6);
+    }
+}
@@ -1,4 +1,8 @@
+use std::borrow::Cow;
 use std::fmt::Display;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use eyre::WrapErr;
 use fontdue::{Font as FontData, FontSettings};
@@ -26,51 +30,726 @@ impl From<FontWeight> for Weight {
 }
 
 /// Analogous to the CSS `font-style` property
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum FontStyle {
     Regular = 0,
     Italic = 1,
+    /// A slanted version of the regular style, rather than separately drawn italics
+    Oblique = 2,
+}
+impl FontStyle {
+    /// The style a face has, going by its OS/2 table
+    pub(crate) fn of_face(face: &ttf_parser::Face) -> Self {
+        if face.is_italic() {
+            FontStyle::Italic
+        } else if face.is_oblique() {
+            FontStyle::Oblique
+        } else {
+            FontStyle::Regular
+        }
+    }
+
+    /// The styles to try in order, starting with this one, like in the CSS font matching algorithm
+    fn fallbacks(self) -> [FontStyle; 3] {
+        match self {
+            FontStyle::Regular => [FontStyle::Regular, FontStyle::Oblique, FontStyle::Italic],
+            FontStyle::Italic => [FontStyle::Italic, FontStyle::Oblique, FontStyle::Regular],
+            FontStyle::Oblique => [FontStyle::Oblique, FontStyle::Italic, FontStyle::Regular],
+        }
+    }
+}
+
+/// The raw contents of a font file, which we hang on to for text shaping.
+/// Cheap to clone, so that the faces of a font collection can share it.
+#[derive(Clone)]
+pub enum FontBytes {
+    Static(&'static [u8]),
+    Shared(Arc<[u8]>),
+}
+impl Deref for FontBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FontBytes::Static(bytes) => bytes,
+            FontBytes::Shared(bytes) => bytes,
+        }
+    }
+}
+impl From<Vec<u8>> for FontBytes {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Shared(value.into())
+    }
+}
+impl From<Cow<'static, [u8]>> for FontBytes {
+    fn from(value: Cow<'static, [u8]>) -> Self {
+        match value {
+            Cow::Borrowed(bytes) => Self::Static(bytes),
+            Cow::Owned(bytes) => bytes.into(),
+        }
+    }
 }
 
 pub struct Variant {
     pub weight: Weight,
     pub style: FontStyle,
     pub data: FontData,
+
+    /// The font file this variant was loaded from
+    pub bytes: FontBytes,
+    /// The face index within a font collection, `0` otherwise
+    pub index: u32,
+}
+impl Variant {
+    /// Loads a single face from font data.
+    /// `index` is the face index within a font collection, and `0` otherwise.
+    ///
+    /// The weight and style are read from the font's OS/2 table.
+    pub fn from_bytes(bytes: FontBytes, index: u32) -> Result<Self, FontError> {
+        let face = ttf_parser::Face::from_slice(&bytes, index).map_err(FontError::Face)?;
+        let weight = face.weight().to_number();
+        let style = FontStyle::of_face(&face);
+
+        let data = FontData::from_bytes(
+            &*bytes,
+            FontSettings {
+                collection_index: index,
+                ..FontSettings::default()
+            },
+        )
+        .map_err(FontError::Parse)?;
+
+        Ok(Self {
+            weight,
+            style,
+            data,
+            bytes,
+            index,
+        })
+    }
+}
+
+/// Where a font came from, so that load failures can be reported meaningfully
+#[derive(Debug, Clone)]
+pub enum FontOrigin {
+    File(PathBuf),
+    /// An in-memory buffer, by the order in which it was added to the [`TypefaceBuilder`]
+    Buffer(usize),
+}
+impl Display for FontOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontOrigin::File(path) => write!(f, "{}", path.display()),
+            FontOrigin::Buffer(index) => write!(f, "buffer #{index}"),
+        }
+    }
 }
 
-/// Simple wrapper for the `&'static str` returned by `fontdue`;
-/// we need something that implements `Error` for `eyre`
 #[derive(Debug)]
-pub struct FontError(&'static str);
+pub enum FontError {
+    /// Wrapper for the `&'static str` returned by `fontdue`;
+    /// we need something that implements `Error` for `eyre`
+    Parse(&'static str),
+    /// The font tables couldn't be read, so we don't know the weight or style
+    Face(ttf_parser::FaceParsingError),
+    Io(std::io::Error),
+    /// A [`Typeface`] needs at least one variant
+    NoVariants,
+    /// No installed font matches the requested family name
+    UnknownFamily(String),
+    /// Another error, along with the font it came from
+    Load {
+        origin: FontOrigin,
+        error: Box<FontError>,
+    },
+}
+impl FontError {
+    pub(crate) fn load(origin: FontOrigin, error: FontError) -> Self {
+        Self::Load {
+            origin,
+            error: Box::new(error),
+        }
+    }
+}
 impl Display for FontError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "FontError: {}", self.0)
+        match self {
+            FontError::Parse(message) => write!(f, "FontError: {message}"),
+            FontError::Face(error) => write!(f, "FontError: reading font tables: {error}"),
+            FontError::Io(error) => write!(f, "FontError: {error}"),
+            FontError::NoVariants => write!(f, "FontError: no font variants were loaded"),
+            FontError::UnknownFamily(family) => write!(f, "FontError: no font family {family:?}"),
+            FontError::Load { origin, error } => write!(f, "loading {origin}: {error}"),
+        }
+    }
+}
+impl std::error::Error for FontError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FontError::Io(error) => Some(error),
+            FontError::Load { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
     }
 }
-impl std::error::Error for FontError {}
 
-const FONT_DATA: &[u8] = include_bytes!("../../../assets/Roboto/Roboto-Regular.ttf");
+/// The bundled Roboto family, as `(data, weight, style)`
+const ROBOTO: [(&[u8], FontWeight, FontStyle); 12] = [
+    (
+        include_bytes!("../../../assets/Roboto/Roboto-Thin.ttf"),
+        FontWeight::Thin,
+        FontStyle::Regular,
+    ),
+    (
+        include_bytes!("../../../assets/Roboto/Roboto-ThinItalic.ttf"),
+        FontWeight::Thin,
+        FontStyle::Italic,
+    ),
+    (
+        include_bytes!("../../../assets/Roboto/Roboto-Light.ttf"),
+        FontWeight::Light,
+        FontStyle::Regular,
+    ),
+    (
+        include_bytes!("../../../assets/Roboto/Roboto-LightItalic.ttf"),
+        FontWeight::Light,
+        FontStyle::Italic,
+    ),
+    (
+        include_bytes!("../../../assets/Roboto/Roboto-Regular.ttf"),
+        FontWeight::Normal,
+        FontStyle::Regular,
+    ),
+    (
+        include_bytes!("../../../assets/Roboto/Roboto-Italic.ttf"),
+        FontWeight::Normal,
+        FontStyle::Italic,
+    ),
+    (
+        include_bytes!("../../../assets/Roboto/Roboto-Medium.ttf"),
+        FontWeight::Medium,
+        FontStyle::Regular,
+    ),
+    (
+        include_bytes!("../../../assets/Roboto/Roboto-MediumItalic.ttf"),
+        FontWeight::Medium,
+        FontStyle::Italic,
+    ),
+    (
+        include_bytes!("../../../assets/Roboto/Roboto-Bold.ttf"),
+        FontWeight::Bold,
+        FontStyle::Regular,
+    ),
+    (
+        include_bytes!("../../../assets/Roboto/Roboto-BoldItalic.ttf"),
+        FontWeight::Bold,
+        FontStyle::Italic,
+    ),
+    (
+        include_bytes!("../../../assets/Roboto/Roboto-Black.ttf"),
+        FontWeight::Black,
+        FontStyle::Regular,
+    ),
+    (
+        include_bytes!("../../../assets/Roboto/Roboto-BlackItalic.ttf"),
+        FontWeight::Black,
+        FontStyle::Italic,
+    ),
+];
+
+/// Ranks how well an available weight satisfies the desired one; lower is better.
+///
+/// This follows the CSS font matching algorithm:
+/// https://www.w3.org/TR/css-fonts-4/#font-style-matching
+///  - Between 400 and 500 inclusive, we look at heavier weights up to 500 first,
+///    then lighter weights, then heavier weights above 500
+///  - Below 400, we look at lighter weights first, then heavier ones
+///  - Above 500, we look at heavier weights first, then lighter ones
+///
+/// Within each of those tiers, the closest weight wins.
+fn weight_rank(desired: Weight, available: Weight) -> (u8, Weight) {
+    let distance = desired.abs_diff(available);
+    let tier = if (400..=500).contains(&desired) {
+        if (desired..=500).contains(&available) {
+            0
+        } else if available < desired {
+            1
+        } else {
+            2
+        }
+    } else if desired < 400 {
+        match available <= desired {
+            true => 0,
+            false => 1,
+        }
+    } else {
+        match available >= desired {
+            true => 0,
+            false => 1,
+        }
+    };
+
+    (tier, distance)
+}
 
 pub struct Typeface {
     /// For now, constructor guarantees non-empty, first one is default
     variants: Vec<Variant>,
+
+    /// Typefaces to try, in order, for characters that none of our variants have a glyph for
+    fallbacks: Vec<&'static Typeface>,
 }
 impl Typeface {
+    /// The bundled Roboto family
     pub fn try_default() -> eyre::Result<Self> {
-        let data = FontData::from_bytes(FONT_DATA, FontSettings::default())
-            .map_err(FontError)
-            .wrap_err("processing default font")?;
+        Self::try_roboto()
+    }
+
+    /// Builds the full Roboto family from the bundled font files, all twelve weights and styles.
+    /// The regular weight comes first, so it's the default variant.
+    pub fn try_roboto() -> eyre::Result<Self> {
+        let variants = ROBOTO
+            .into_iter()
+            .map(|(bytes, weight, style)| {
+                let weight: Weight = weight.into();
+                let data = FontData::from_bytes(bytes, FontSettings::default())
+                    .map_err(FontError::Parse)
+                    .wrap_err_with(|| format!("processing Roboto {weight} {style:?}"))?;
+
+                Ok(Variant {
+                    weight,
+                    style,
+                    data,
+                    bytes: FontBytes::Static(bytes),
+                    index: 0,
+                })
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        Ok(Self::from_variants(variants)?)
+    }
+
+    /// Orders the variants so that the one closest to regular, normal weight comes first, as the default.
+    /// Fails if there are no variants.
+    pub fn from_variants(mut variants: Vec<Variant>) -> Result<Self, FontError> {
+        if variants.is_empty() {
+            return Err(FontError::NoVariants);
+        }
+
+        variants.sort_by_key(|variant| {
+            (
+                variant.style != FontStyle::Regular,
+                weight_rank(FontWeight::Normal.into(), variant.weight),
+            )
+        });
 
         Ok(Self {
-            variants: vec![Variant {
-                weight: FontWeight::Normal.into(),
-                style: FontStyle::Regular,
-                data,
-            }],
+            variants,
+            fallbacks: Vec::new(),
         })
     }
 
-    pub fn match_style(&self, _weight: Weight, _style: FontStyle) -> &FontData {
-        //TODO: Implement this!
-        &self.variants.first().unwrap().data
+    /// Appends a typeface to the end of the fallback chain
+    pub fn with_fallback(mut self, fallback: &'static Typeface) -> Self {
+        self.fallbacks.push(fallback);
+        self
+    }
+
+    pub fn fallbacks(&self) -> &[&'static Typeface] {
+        &self.fallbacks
+    }
+
+    pub fn builder() -> TypefaceBuilder {
+        TypefaceBuilder::default()
+    }
+
+    pub fn variants(&self) -> &[Variant] {
+        &self.variants
+    }
+
+    /// Finds the variant that best matches the given weight and style,
+    /// loosely following the CSS font matching algorithm.
+    ///
+    /// The style is matched first; if there's no variant with the desired style, we fall back to the others,
+    /// like CSS does: italic to oblique to regular, oblique to italic to regular, and regular to oblique to italic.
+    /// Then, among those, we pick the nearest weight according to [`weight_rank`].
+    pub fn match_variant(&self, weight: Weight, style: FontStyle) -> &Variant {
+        let style = style
+            .fallbacks()
+            .into_iter()
+            .find(|&style| self.variants.iter().any(|variant| variant.style == style))
+            .unwrap_or(style);
+
+        self.variants
+            .iter()
+            .filter(|variant| variant.style == style)
+            .min_by_key(|variant| weight_rank(weight, variant.weight))
+            .unwrap_or_else(|| self.variants.first().unwrap())
+    }
+
+    pub fn match_style(&self, weight: Weight, style: FontStyle) -> &FontData {
+        &self.match_variant(weight, style).data
+    }
+
+    /// Like [`Typeface::match_style`], but only returns a font that actually has glyphs
+    /// for every character in the grapheme `cluster`,
+    /// trying each typeface in the fallback chain (depth-first) until one does.
+    /// `None` if nothing in the chain has them.
+    pub fn match_glyphs(
+        &self,
+        weight: Weight,
+        style: FontStyle,
+        cluster: &str,
+    ) -> Option<&Variant> {
+        let variant = self.match_variant(weight, style);
+        if has_glyphs(&variant.data, cluster) {
+            return Some(variant);
+        }
+
+        self.fallbacks
+            .iter()
+            .find_map(|fallback| fallback.match_glyphs(weight, style, cluster))
+    }
+}
+
+/// `false` if the font would render this character as the notdef glyph
+pub(crate) fn has_glyph(font: &FontData, character: char) -> bool {
+    font.lookup_glyph_index(character) != 0
+}
+
+/// [`has_glyph`] for every character in a grapheme cluster,
+/// except for the invisible ones that fonts don't need glyphs for
+pub(crate) fn has_glyphs(font: &FontData, cluster: &str) -> bool {
+    cluster
+        .chars()
+        .filter(|&character| !is_default_ignorable(character))
+        .all(|character| has_glyph(font, character))
+}
+
+/// Invisible formatting characters that show up inside grapheme clusters:
+/// joiners, variation selectors and emoji tags.
+/// These only affect how the characters around them are displayed, and are never drawn themselves.
+pub(crate) fn is_default_ignorable(character: char) -> bool {
+    matches!(
+        character,
+        '\u{200C}'
+            | '\u{200D}'
+            | '\u{FE00}'..='\u{FE0F}'
+            | '\u{E0020}'..='\u{E007F}'
+            | '\u{E0100}'..='\u{E01EF}'
+    )
+}
+
+/// File extensions that [`TypefaceBuilder::directory`] will pick up
+const FONT_EXTENSIONS: [&str; 4] = ["ttf", "otf", "ttc", "otc"];
+
+enum FontSource {
+    Directory(PathBuf),
+    File(PathBuf),
+    Buffer(Cow<'static, [u8]>),
+}
+
+/// Assembles a [`Typeface`] from font files or in-memory font data at runtime.
+/// Every face in a font collection becomes its own variant.
+///
+/// Nothing is read until [`TypefaceBuilder::build`] or [`TypefaceBuilder::build_partial`] is called.
+#[derive(Default)]
+pub struct TypefaceBuilder {
+    sources: Vec<FontSource>,
+}
+impl TypefaceBuilder {
+    /// Adds every font file directly inside the directory (not recursively),
+    /// going by the file extension.
+    pub fn directory(mut self, path: impl Into<PathBuf>) -> Self {
+        self.sources.push(FontSource::Directory(path.into()));
+        self
+    }
+
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.sources.push(FontSource::File(path.into()));
+        self
+    }
+
+    pub fn paths<P: Into<PathBuf>>(mut self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.sources
+            .extend(paths.into_iter().map(|path| FontSource::File(path.into())));
+        self
+    }
+
+    /// Adds font data that's already in memory.
+    /// Borrowed data has to be `'static` (e.g. `include_bytes!`); otherwise pass a `Vec<u8>`,
+    /// or use [`TypefaceBuilder::bytes_copied`].
+    pub fn bytes(mut self, data: impl Into<Cow<'static, [u8]>>) -> Self {
+        self.sources.push(FontSource::Buffer(data.into()));
+        self
+    }
+
+    /// Adds a copy of font data borrowed for less than `'static`, e.g. from a buffer the caller reuses.
+    pub fn bytes_copied(self, data: &[u8]) -> Self {
+        self.bytes(data.to_vec())
+    }
+
+    /// Loads every font, failing on the first one that can't be loaded.
+    pub fn build(self) -> Result<Typeface, FontError> {
+        let (typeface, errors) = self.build_partial();
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => typeface,
+        }
+    }
+
+    /// Loads every font it can, skipping the ones that fail.
+    /// The per-font failures are returned alongside the result;
+    /// the result itself is only an error if nothing could be loaded.
+    pub fn build_partial(self) -> (Result<Typeface, FontError>, Vec<FontError>) {
+        let mut variants = Vec::new();
+        let mut errors = Vec::new();
+        let mut buffer_count = 0;
+
+        for source in self.sources {
+            match source {
+                FontSource::Directory(path) => match font_files(&path) {
+                    Ok(files) => files.into_iter().for_each(|file| {
+                        load_file(file, &mut variants, &mut errors);
+                    }),
+                    Err(error) => errors.push(FontError::load(FontOrigin::File(path), error)),
+                },
+                FontSource::File(path) => load_file(path, &mut variants, &mut errors),
+                FontSource::Buffer(data) => {
+                    let origin = FontOrigin::Buffer(buffer_count);
+                    buffer_count += 1;
+                    load_faces(origin, data.into(), &mut variants, &mut errors);
+                }
+            }
+        }
+
+        (Typeface::from_variants(variants), errors)
+    }
+}
+
+/// The font files directly inside `directory`, sorted so that loading is deterministic
+pub(crate) fn font_files(directory: &Path) -> Result<Vec<PathBuf>, FontError> {
+    let mut files = std::fs::read_dir(directory)
+        .map_err(FontError::Io)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && is_font_file(path))
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+pub(crate) fn is_font_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| {
+            FONT_EXTENSIONS
+                .iter()
+                .any(|font_extension| extension.eq_ignore_ascii_case(font_extension))
+        })
+        .unwrap_or(false)
+}
+
+fn load_file(path: PathBuf, variants: &mut Vec<Variant>, errors: &mut Vec<FontError>) {
+    match std::fs::read(&path) {
+        Ok(data) => load_faces(FontOrigin::File(path), data.into(), variants, errors),
+        Err(error) => errors.push(FontError::load(
+            FontOrigin::File(path),
+            FontError::Io(error),
+        )),
+    }
+}
+
+/// Loads every face in `data`, which might be a font collection
+fn load_faces(
+    origin: FontOrigin,
+    data: FontBytes,
+    variants: &mut Vec<Variant>,
+    errors: &mut Vec<FontError>,
+) {
+    let count = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+    for index in 0..count {
+        match Variant::from_bytes(data.clone(), index) {
+            Ok(variant) => variants.push(variant),
+            Err(error) => errors.push(FontError::load(origin.clone(), error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+
+    use super::*;
+
+    const REGULAR: &[u8] = include_bytes!("../../../assets/Roboto/Roboto-Regular.ttf");
+
+    /// A typeface with a variant for each weight and style, all drawn with Roboto Regular
+    fn typeface(variants: &[(Weight, FontStyle)]) -> Typeface {
+        let variants = variants
+            .iter()
+            .map(|&(weight, style)| Variant {
+                weight,
+                style,
+                ..Variant::from_bytes(FontBytes::Static(REGULAR), 0).unwrap()
+            })
+            .collect();
+        Typeface::from_variants(variants).unwrap()
+    }
+
+    /// Roboto Regular without glyphs for `¡` to `¬` (U+00A1 to U+00AC),
+    /// by moving the start of their segment in the character map past its end
+    fn without_latin1_symbols() -> Vec<u8> {
+        let read =
+            |data: &[u8], offset: usize| u16::from_be_bytes([data[offset], data[offset + 1]]);
+        let mut font = REGULAR.to_vec();
+
+        let tables = read(&font, 4) as usize;
+        let cmap = (0..tables)
+            .map(|table| 12 + 16 * table)
+            .find(|&record| &font[record..record + 4] == b"cmap")
+            .map(|record| u32::from_be_bytes(font[record + 8..record + 12].try_into().unwrap()))
+            .unwrap() as usize;
+        // Roboto only has format 4 subtables, which all share the same data
+        let subtable =
+            cmap + u32::from_be_bytes(font[cmap + 8..cmap + 12].try_into().unwrap()) as usize;
+        let segments = read(&font, subtable + 6) as usize / 2;
+        let ends = subtable + 14;
+        let starts = ends + 2 * segments + 2;
+
+        let segment = (0..segments)
+            .find(|&segment| read(&font, ends + 2 * segment) >= 0xA1)
+            .unwrap();
+        assert_eq!(read(&font, starts + 2 * segment), 0xA1);
+        font[starts + 2 * segment..starts + 2 * segment + 2]
+            .copy_from_slice(&0xADu16.to_be_bytes());
+        font
+    }
+
+    #[test]
+    fn weight_tiers() {
+        let cases: [(Weight, &[Weight], Weight); 9] = [
+            // Between 400 and 500, heavier up to 500, then lighter, then heavier
+            (500, &[400, 700], 400),
+            (400, &[300, 500, 700], 500),
+            (450, &[400, 500], 500),
+            (400, &[100, 900], 100),
+            // Below 400, lighter first
+            (300, &[200, 400], 200),
+            (300, &[400, 500], 400),
+            // Above 500, heavier first
+            (600, &[500, 800], 800),
+            (600, &[400, 500], 500),
+            // Closest within a tier
+            (700, &[800, 900, 100], 800),
+        ];
+        for (desired, available, expected) in cases {
+            let best = available
+                .iter()
+                .copied()
+                .min_by_key(|&weight| weight_rank(desired, weight));
+            assert_eq!(best, Some(expected), "{desired} from {available:?}");
+        }
+    }
+
+    #[test]
+    fn style_fallbacks() {
+        use FontStyle::*;
+        let cases: [(FontStyle, &[FontStyle], FontStyle); 7] = [
+            (Italic, &[Regular, Oblique, Italic], Italic),
+            (Italic, &[Regular, Oblique], Oblique),
+            (Italic, &[Regular], Regular),
+            (Oblique, &[Regular, Italic], Italic),
+            (Oblique, &[Regular], Regular),
+            (Regular, &[Italic, Oblique], Oblique),
+            (Regular, &[Italic], Italic),
+        ];
+        for (desired, available, expected) in cases {
+            let variants = available
+                .iter()
+                .map(|&style| (400, style))
+                .collect::<Vec<_>>();
+            let matched = typeface(&variants).match_variant(400, desired).style;
+            assert_eq!(matched, expected, "{desired:?} from {available:?}");
+        }
+    }
+
+    #[test]
+    fn style_before_weight() {
+        let typeface = typeface(&[
+            (400, FontStyle::Italic),
+            (700, FontStyle::Regular),
+            (900, FontStyle::Italic),
+        ]);
+        let variant = typeface.match_variant(700, FontStyle::Italic);
+        assert_eq!((variant.weight, variant.style), (900, FontStyle::Italic));
+        let variant = typeface.match_variant(400, FontStyle::Regular);
+        assert_eq!((variant.weight, variant.style), (700, FontStyle::Regular));
+    }
+
+    #[test]
+    fn default_variant_is_regular() {
+        let typeface = typeface(&[
+            (700, FontStyle::Italic),
+            (300, FontStyle::Regular),
+            (500, FontStyle::Regular),
+        ]);
+        let default = &typeface.variants()[0];
+        assert_eq!((default.weight, default.style), (500, FontStyle::Regular));
+    }
+
+    #[test]
+    fn builder_collects_errors() {
+        let (typeface, errors) = Typeface::builder()
+            .bytes(REGULAR)
+            .bytes(&b"not a font"[..])
+            .build_partial();
+        assert_eq!(typeface.unwrap().variants().len(), 1);
+        assert_eq!(errors.len(), 1);
+
+        assert!(matches!(
+            Typeface::builder().build(),
+            Err(FontError::NoVariants)
+        ));
+        assert!(Typeface::builder()
+            .bytes(&b"not a font"[..])
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn builder_copies_borrowed_bytes() {
+        let buffer = REGULAR.to_vec();
+        let typeface = Typeface::builder().bytes_copied(&buffer).build();
+        drop(buffer);
+        assert_eq!(typeface.unwrap().variants().len(), 1);
+    }
+
+    #[test]
+    fn fallback_chain() {
+        static FALLBACK: OnceLock<Typeface> = OnceLock::new();
+        let fallback = FALLBACK.get_or_init(|| typeface(&[(400, FontStyle::Regular)]));
+        let primary = Typeface::from_variants(vec![Variant::from_bytes(
+            without_latin1_symbols().into(),
+            0,
+        )
+        .unwrap()])
+        .unwrap()
+        .with_fallback(fallback);
+
+        // Found in the typeface itself, without going down the chain
+        let variant = primary.match_glyphs(400, FontStyle::Regular, "a").unwrap();
+        assert!(std::ptr::eq(variant, &primary.variants()[0]));
+        // Only the fallback has this one
+        assert!(!has_glyph(&primary.variants()[0].data, '©'));
+        let variant = primary.match_glyphs(400, FontStyle::Regular, "©").unwrap();
+        assert!(std::ptr::eq(variant, &fallback.variants()[0]));
+        // Nothing in the chain has a glyph for a private use character
+        assert!(primary
+            .match_glyphs(400, FontStyle::Regular, "\u{E000}")
+            .is_none());
+        // Invisible joiners don't need glyphs of their own
+        assert!(primary
+            .match_glyphs(400, FontStyle::Regular, "a\u{200D}")
+            .is_some());
     }
 }
@@ -0,0 +1,529 @@
+/// Reading pixels between the ones in an image, for drawing it at a different size
+pub mod sampling;
+
+/// A practical subset of SVG, for vector icons
+pub mod svg;
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::graphics::color::Color;
+use crate::graphics::path::{FillRule, Path, Point};
+use crate::layout::{Position, Rect, Size};
+use crate::render::cache::{Cache, Footprint};
+use crate::render::rasterizer;
+use crate::render::{DrawHandle, Drawable, Pixel};
+use sampling::Filter;
+use svg::SvgError;
+
+/// A decoded raster image, with 8-bit sRGB channels and premultiplied alpha.
+///
+/// Premultiplied pixels can be filtered without transparent pixels bleeding their color into their neighbors.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Image {
+    width: Pixel,
+    height: Pixel,
+    /// `[red, green, blue, alpha]`, row by row from the top left
+    pixels: Vec<[u8; 4]>,
+}
+impl Image {
+    /// Decodes a PNG, JPEG, GIF or WebP image, detecting the format from its contents.
+    /// Only the first frame of animated images is decoded
+    pub fn decode(bytes: &[u8]) -> Result<Self, ImageError> {
+        let decoded = ::image::load_from_memory(bytes).map_err(ImageError::Decode)?;
+        let decoded = decoded.into_rgba8();
+        let (width, height) = (decoded.width() as Pixel, decoded.height() as Pixel);
+        let pixels = decoded.pixels().map(|pixel| premultiply(pixel.0)).collect();
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// An image from pixels with straight (not premultiplied) alpha;
+    /// `pixels` has to have exactly `width * height` pixels
+    pub fn from_colors(colors: &[Color], width: Pixel, height: Pixel) -> Option<Self> {
+        (colors.len() == width * height).then(|| Self {
+            width,
+            height,
+            pixels: colors
+                .iter()
+                .map(|color| premultiply([color.red, color.green, color.blue, color.alpha]))
+                .collect(),
+        })
+    }
+
+    pub fn width(&self) -> Pixel {
+        self.width
+    }
+
+    pub fn height(&self) -> Pixel {
+        self.height
+    }
+
+    pub fn size(&self) -> Size {
+        Size {
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Premultiplied `[red, green, blue, alpha]` pixels, row by row from the top left
+    pub fn pixels(&self) -> &[[u8; 4]] {
+        &self.pixels
+    }
+
+    /// The pixel at `x`, `y`, with the coordinates clamped to the edges of the image
+    fn clamped(&self, x: isize, y: isize) -> [u8; 4] {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+}
+impl Footprint for Image {
+    fn bytes(&self) -> usize {
+        self.pixels.len() * 4
+    }
+}
+
+fn premultiply([red, green, blue, alpha]: [u8; 4]) -> [u8; 4] {
+    let channel = |value: u8| ((value as u32 * alpha as u32 + 127) / 255) as u8;
+    [channel(red), channel(green), channel(blue), alpha]
+}
+
+/// Turns a premultiplied pixel back into a color with straight alpha, for blending
+fn unpremultiply([red, green, blue, alpha]: [f32; 4]) -> Color {
+    if alpha <= 0.0 {
+        return crate::graphics::color::TRANSPARENT;
+    }
+    let channel = |value: f32| (value * 255.0 / alpha).clamp(0.0, 255.0).round() as u8;
+    Color::new(
+        channel(red),
+        channel(green),
+        channel(blue),
+        alpha.clamp(0.0, 255.0).round() as u8,
+    )
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(std::io::Error),
+    /// The format isn't supported, or the data is corrupt
+    Decode(::image::ImageError),
+    Svg(SvgError),
+}
+impl Display for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageError::Io(error) => write!(f, "ImageError: {error}"),
+            ImageError::Decode(error) => write!(f, "ImageError: decoding: {error}"),
+            ImageError::Svg(error) => write!(f, "ImageError: {error}"),
+        }
+    }
+}
+impl std::error::Error for ImageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImageError::Io(error) => Some(error),
+            ImageError::Decode(error) => Some(error),
+            ImageError::Svg(error) => Some(error),
+        }
+    }
+}
+
+/// Where an image's encoded data comes from; decoded images are cached by their source
+#[derive(Clone, Debug)]
+pub enum ImageSource {
+    /// An image file, read when the image is first drawn
+    File(PathBuf),
+    /// Encoded image data, like from `include_bytes!` or a download.
+    /// Create these with [`ImageSource::bytes`], which hashes the data once up front
+    Bytes { data: Arc<[u8]>, hash: u64 },
+}
+impl ImageSource {
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        ImageSource::File(path.into())
+    }
+
+    pub fn bytes(data: impl Into<Arc<[u8]>>) -> Self {
+        let data = data.into();
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        ImageSource::Bytes {
+            hash: hasher.finish(),
+            data,
+        }
+    }
+
+    fn key(&self) -> ImageKey {
+        match self {
+            ImageSource::File(path) => ImageKey::File(path.clone()),
+            ImageSource::Bytes { data, hash } => ImageKey::Bytes {
+                hash: *hash,
+                len: data.len(),
+            },
+        }
+    }
+
+    /// The encoded data, reading the file if it's from one
+    fn read(&self) -> Result<Cow<'_, [u8]>, ImageError> {
+        match self {
+            ImageSource::File(path) => Ok(Cow::Owned(std::fs::read(path).map_err(ImageError::Io)?)),
+            ImageSource::Bytes { data, .. } => Ok(Cow::Borrowed(data)),
+        }
+    }
+
+    fn decode(&self) -> Result<Image, ImageError> {
+        Image::decode(&self.read()?)
+    }
+}
+
+/// Identifies a decoded [`Image`] in the image cache
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ImageKey {
+    File(PathBuf),
+    Bytes { hash: u64, len: usize },
+}
+
+/// Default memory budget of the image cache, in bytes
+pub const IMAGE_CACHE_BUDGET: usize = 64 * 1024 * 1024;
+
+thread_local! {
+    /// Decoded images, shared by every image drawn on this thread across frames
+    static IMAGE_CACHE: RefCell<Cache<ImageKey, Image>> =
+        RefCell::new(Cache::new(IMAGE_CACHE_BUDGET));
+}
+
+/// Runs `f` with this thread's image cache,
+/// e.g. to change its memory budget or look at its statistics
+pub fn with_image_cache<R>(f: impl FnOnce(&mut Cache<ImageKey, Image>) -> R) -> R {
+    IMAGE_CACHE.with(|cache| f(&mut cache.borrow_mut()))
+}
+
+/// How an image is sized to fit its box, analogous to the CSS `object-fit` property
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Fit {
+    /// As large as fits in the box while keeping its aspect ratio, centered, leaving empty space around it
+    #[default]
+    Contain,
+    /// As small as covers the box while keeping its aspect ratio, centered, with the overflow cut off
+    Cover,
+    /// Stretched to the box
+    Fill,
+}
+impl Fit {
+    /// Where the whole image goes, relative to the top left of the box, with fractional pixels
+    fn place(self, image: Size, frame: Size) -> (f32, f32, f32, f32) {
+        let (image_width, image_height) = (image.width as f32, image.height as f32);
+        let (frame_width, frame_height) = (frame.width as f32, frame.height as f32);
+        let (scale_x, scale_y) = (frame_width / image_width, frame_height / image_height);
+
+        let scale = match self {
+            Fit::Fill => return (0.0, 0.0, frame_width, frame_height),
+            Fit::Contain => f32::min(scale_x, scale_y),
+            Fit::Cover => f32::max(scale_x, scale_y),
+        };
+        let (width, height) = (image_width * scale, image_height * scale);
+        (
+            (frame_width - width) / 2.0,
+            (frame_height - height) / 2.0,
+            width,
+            height,
+        )
+    }
+}
+
+/// An image drawn into a box of a given size
+#[derive(Clone, Debug)]
+pub struct ImageView {
+    pub source: ImageSource,
+    pub size: Size,
+    pub fit: Fit,
+    pub filter: Filter,
+}
+impl ImageView {
+    pub fn new(source: ImageSource, size: Size) -> Self {
+        Self {
+            source,
+            size,
+            fit: Fit::default(),
+            filter: Filter::default(),
+        }
+    }
+
+    pub fn with_fit(mut self, fit: Fit) -> Self {
+        self.fit = fit;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Gets the decoded image from the image cache, decoding it if it's not there.
+    /// Drawing does this too, but this is where decoding errors can be handled
+    pub fn load(&self) -> Result<Rc<Image>, ImageError> {
+        with_image_cache(|cache| {
+            cache.try_get_or_insert_with(self.source.key(), || self.source.decode())
+        })
+    }
+}
+impl Drawable for ImageView {
+    fn draw_at(&self, handle: &mut DrawHandle, position: Position) {
+        let image = match self.load() {
+            Ok(image) => image,
+            Err(error) => {
+                log::warn!("couldn't load image {:?}: {error}", self.source.key());
+                return;
+            }
+        };
+        image.draw_in(
+            handle,
+            Rect::new(position, self.size),
+            self.fit,
+            self.filter,
+        );
+    }
+}
+
+impl Image {
+    /// Draws the image into `frame`, sized to it by `fit`, and clipped to it
+    fn draw_in(&self, handle: &mut DrawHandle, frame: Rect, fit: Fit, filter: Filter) {
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+        let (left, top, width, height) = fit.place(self.size(), frame.size);
+        let (left, top) = (
+            left + frame.position.x as f32,
+            top + frame.position.y as f32,
+        );
+        let (scale_x, scale_y) = (width / self.width as f32, height / self.height as f32);
+
+        let Some(offset) = handle.pixel_offset() else {
+            return self.draw_transformed(handle, frame, (left, top, width, height), filter);
+        };
+        let frame = Rect::new(
+            Position {
+                x: frame.position.x + offset.x,
+                y: frame.position.y + offset.y,
+            },
+            frame.size,
+        );
+        let (left, top) = (left + offset.x as f32, top + offset.y as f32);
+
+        // Only the pixels that the image covers, within the box; `Cover` overflows the box
+        let (first_x, first_y) = (left.floor() as i32, top.floor() as i32);
+        let covered = Rect::new(
+            Position {
+                x: first_x,
+                y: first_y,
+            },
+            Size {
+                width: ((left + width).ceil() as i32 - first_x).max(0) as Pixel,
+                height: ((top + height).ceil() as i32 - first_y).max(0) as Pixel,
+            },
+        );
+        let area = handle.clip().intersection(frame).intersection(covered);
+
+        // How much of a pixel the image covers along one axis, for antialiased edges
+        let overlap = |pixel: i32, start: f32, length: f32| {
+            let pixel = pixel as f32;
+            (f32::min(pixel + 1.0, start + length) - f32::max(pixel, start)).clamp(0.0, 1.0)
+        };
+
+        // How many image pixels each drawn pixel covers, to average them when scaling down
+        let (footprint_x, footprint_y) = (1.0 / scale_x, 1.0 / scale_y);
+        for y in area.position.y..area.bottom() {
+            let coverage_y = overlap(y, top, height);
+            for x in area.position.x..area.right() {
+                let coverage = overlap(x, left, width) * coverage_y;
+                let coverage = (coverage * 255.0).round() as u8;
+
+                // Pixel centers in the image, in pixels
+                let image_x = (x as f32 + 0.5 - left) / scale_x;
+                let image_y = (y as f32 + 0.5 - top) / scale_y;
+                let sample = filter.sample_area(self, image_x, image_y, footprint_x, footprint_y);
+                handle.blend(Position { x, y }, unpremultiply(sample), coverage);
+            }
+        }
+    }
+
+    /// Draws the image through a transform that doesn't map pixels onto pixels, like a rotation,
+    /// by filling its outline and sampling the image at each pixel mapped back into it.
+    /// `placed` is where [`Fit::place`] put the image, as `(left, top, width, height)`
+    fn draw_transformed(
+        &self,
+        handle: &mut DrawHandle,
+        frame: Rect,
+        placed: (f32, f32, f32, f32),
+        filter: Filter,
+    ) {
+        let transform = handle.transform();
+        let Some(inverse) = transform.inverse() else {
+            return;
+        };
+        let (left, top, width, height) = placed;
+        let (scale_x, scale_y) = (width / self.width as f32, height / self.height as f32);
+
+        // The part of the image inside of the frame; `Cover` overflows it
+        let visible_left = left.max(frame.position.x as f32);
+        let visible_top = top.max(frame.position.y as f32);
+        let visible_right = (left + width).min(frame.right() as f32);
+        let visible_bottom = (top + height).min(frame.bottom() as f32);
+        if visible_right <= visible_left || visible_bottom <= visible_top {
+            return;
+        }
+        let outline = Path::rect(
+            Point::new(visible_left, visible_top),
+            visible_right - visible_left,
+            visible_bottom - visible_top,
+        )
+        .transformed(transform);
+
+        // How far a step of one pixel on the surface goes in the image, along each of its axes
+        let footprint_x = inverse.a.hypot(inverse.c) / scale_x;
+        let footprint_y = inverse.b.hypot(inverse.d) / scale_y;
+
+        let polygons = outline.flatten(rasterizer::TOLERANCE);
+        rasterizer::fill(
+            &polygons,
+            FillRule::NonZero,
+            handle.clip(),
+            |x, y, coverage| {
+                let center = inverse.apply(Point::new(x as f32 + 0.5, y as f32 + 0.5));
+                let image_x = (center.x - left) / scale_x;
+                let image_y = (center.y - top) / scale_y;
+                let sample = filter.sample_area(self, image_x, image_y, footprint_x, footprint_y);
+                handle.blend(Position { x, y }, unpremultiply(sample), coverage);
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Encodes a small image with some transparency in the given format
+    fn encode(format: ::image::ImageFormat) -> Vec<u8> {
+        let image = ::image::RgbaImage::from_fn(6, 4, |x, y| {
+            ::image::Rgba([x as u8 * 40, y as u8 * 60, 200, if x < 3 { 255 } else { 0 }])
+        });
+        let image = match format {
+            // JPEG has no alpha channel
+            ::image::ImageFormat::Jpeg => ::image::DynamicImage::from(image).to_rgb8().into(),
+            _ => ::image::DynamicImage::from(image),
+        };
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn decodes_every_format() {
+        use ::image::ImageFormat;
+        for format in [
+            ImageFormat::Png,
+            ImageFormat::Jpeg,
+            ImageFormat::Gif,
+            ImageFormat::WebP,
+        ] {
+            let image = Image::decode(&encode(format)).unwrap();
+            assert_eq!((image.width(), image.height()), (6, 4), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn premultiplies_alpha() {
+        let image = Image::decode(&encode(::image::ImageFormat::Png)).unwrap();
+        assert_eq!(image.pixels()[1], [40, 0, 200, 255]);
+        assert_eq!(image.pixels()[5], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn caches_by_source() {
+        let source = ImageSource::bytes(encode(::image::ImageFormat::Png));
+        let view = ImageView::new(
+            source.clone(),
+            Size {
+                width: 6,
+                height: 4,
+            },
+        );
+        let first = view.load().unwrap();
+        let second = ImageView::new(
+            source,
+            Size {
+                width: 12,
+                height: 8,
+            },
+        )
+        .load()
+        .unwrap();
+        assert!(Rc::ptr_eq(&first, &second));
+
+        let missing = ImageView::new(ImageSource::file("missing.png"), view.size);
+        assert!(matches!(missing.load(), Err(ImageError::Io(_))));
+    }
+
+    #[test]
+    fn averages_when_scaling_down() {
+        use crate::graphics::color;
+        use crate::graphics::transform::Transform;
+        use crate::render::surface::{Canvas, Surface};
+
+        // Single pixel stripes, which alias into solid black or white when sampled at a few points
+        let image = ::image::RgbaImage::from_fn(40, 40, |x, y| match (x + y) % 2 {
+            0 => ::image::Rgba([0, 0, 0, 255]),
+            _ => ::image::Rgba([255, 255, 255, 255]),
+        });
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ::image::ImageFormat::Png)
+            .unwrap();
+        let view = ImageView::new(
+            ImageSource::bytes(bytes),
+            Size {
+                width: 8,
+                height: 8,
+            },
+        )
+        .with_fit(Fit::Fill)
+        .with_filter(Filter::Bilinear);
+
+        // Scaled down by 5, each pixel covers 12 or 13 of 25 white pixels
+        let gray = |canvas: &Canvas, x: usize, y: usize| {
+            let pixel = color::from_buffer(canvas.pixels()[y * canvas.width() + x]);
+            [pixel.red, pixel.green, pixel.blue]
+                .into_iter()
+                .all(|channel| channel.abs_diff(128) <= 6)
+        };
+
+        let mut canvas = Canvas::new(16, 16);
+        view.draw_at(&mut DrawHandle::new(&mut canvas), Position { x: 4, y: 4 });
+        assert!((4..12).all(|y| (4..12).all(|x| gray(&canvas, x, y))));
+
+        // Rotating doesn't change how much of the image each pixel covers
+        let mut canvas = Canvas::new(16, 16);
+        let mut handle = DrawHandle::new(&mut canvas);
+        handle.push_transform(
+            Transform::translate(-8.0, -8.0)
+                .then(Transform::rotate(0.3))
+                .then(Transform::translate(8.0, 8.0)),
+        );
+        view.draw_at(&mut handle, Position { x: 4, y: 4 });
+        assert!((6..10).all(|y| (6..10).all(|x| gray(&canvas, x, y))));
+    }
+}
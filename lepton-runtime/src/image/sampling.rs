@@ -0,0 +1,176 @@
+use super::Image;
+
+/// How the pixels of a scaled image are computed from the pixels around them.
+///
+/// When scaling down, [`Filter::Bilinear`] and [`Filter::Bicubic`] average every image pixel
+/// that a drawn pixel covers instead, so that fine detail doesn't alias; see [`Filter::sample_area`]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Filter {
+    /// The closest pixel; keeps pixel art crisp
+    Nearest,
+    /// A weighted average of the 2x2 closest pixels
+    #[default]
+    Bilinear,
+    /// A Catmull-Rom spline through the 4x4 closest pixels; sharper than bilinear when scaling up
+    Bicubic,
+}
+impl Filter {
+    /// The premultiplied color at `x`, `y` in the image, in pixels from its top left corner.
+    /// Pixel centers are at half pixels, and coordinates past the edges get the color of the edge
+    pub fn sample(self, image: &Image, x: f32, y: f32) -> [f32; 4] {
+        match self {
+            Filter::Nearest => to_f32(image.clamped(x.floor() as isize, y.floor() as isize)),
+            Filter::Bilinear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (left, top) = (x.floor(), y.floor());
+                let (t_x, t_y) = (x - left, y - top);
+                let (left, top) = (left as isize, top as isize);
+
+                let weights_x = [1.0 - t_x, t_x];
+                let weights_y = [1.0 - t_y, t_y];
+                convolve(image, left, top, &weights_x, &weights_y)
+            }
+            Filter::Bicubic => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (left, top) = (x.floor(), y.floor());
+                let (t_x, t_y) = (x - left, y - top);
+                let (left, top) = (left as isize - 1, top as isize - 1);
+
+                let sample = convolve(image, left, top, &catmull_rom(t_x), &catmull_rom(t_y));
+                // The spline overshoots around sharp edges; premultiplied channels can't go past alpha
+                let alpha = sample[3].clamp(0.0, 255.0);
+                [
+                    sample[0].clamp(0.0, alpha),
+                    sample[1].clamp(0.0, alpha),
+                    sample[2].clamp(0.0, alpha),
+                    alpha,
+                ]
+            }
+        }
+    }
+
+    /// Like [`Self::sample`], for a drawn pixel that covers `footprint_x` by `footprint_y` pixels of the image.
+    ///
+    /// Past one image pixel along either axis, the sample is the average of the whole footprint,
+    /// weighted by how much of each image pixel it covers; a box filter, which costs more the smaller the image is drawn.
+    /// Nearest neighbor sampling always takes the one pixel
+    pub fn sample_area(
+        self,
+        image: &Image,
+        x: f32,
+        y: f32,
+        footprint_x: f32,
+        footprint_y: f32,
+    ) -> [f32; 4] {
+        let downscaled = footprint_x > 1.0 || footprint_y > 1.0;
+        if self == Filter::Nearest || !downscaled {
+            return self.sample(image, x, y);
+        }
+        // A box one pixel wide is the same as bilinear interpolation
+        let (left, weights_x) = box_weights(x, footprint_x.max(1.0));
+        let (top, weights_y) = box_weights(y, footprint_y.max(1.0));
+        convolve(image, left, top, &weights_x, &weights_y)
+    }
+}
+
+/// The first pixel that a box `width` pixels wide around `center` covers,
+/// and how much of the box each pixel from there on covers
+fn box_weights(center: f32, width: f32) -> (isize, Vec<f32>) {
+    let (start, end) = (center - width / 2.0, center + width / 2.0);
+    let (first, last) = (start.floor() as isize, end.ceil() as isize);
+    let weights = (first..last)
+        .map(|pixel| {
+            let pixel = pixel as f32;
+            (f32::min(pixel + 1.0, end) - f32::max(pixel, start)) / width
+        })
+        .collect();
+    (first, weights)
+}
+
+fn to_f32(pixel: [u8; 4]) -> [f32; 4] {
+    pixel.map(|channel| channel as f32)
+}
+
+/// Weighted sum of the pixels in a block starting at `left`, `top`,
+/// as wide and tall as there are weights
+fn convolve(
+    image: &Image,
+    left: isize,
+    top: isize,
+    weights_x: &[f32],
+    weights_y: &[f32],
+) -> [f32; 4] {
+    let mut sum = [0.0; 4];
+    for (row, weight_y) in weights_y.iter().enumerate() {
+        for (column, weight_x) in weights_x.iter().enumerate() {
+            let pixel = to_f32(image.clamped(left + column as isize, top + row as isize));
+            let weight = weight_x * weight_y;
+            (0..4).for_each(|channel| sum[channel] += pixel[channel] * weight);
+        }
+    }
+    sum
+}
+
+/// Weights of the 4 pixels around a point `t` of the way between the middle two
+fn catmull_rom(t: f32) -> [f32; 4] {
+    let (t2, t3) = (t * t, t * t * t);
+    [
+        (-t3 + 2.0 * t2 - t) / 2.0,
+        (3.0 * t3 - 5.0 * t2 + 2.0) / 2.0,
+        (-3.0 * t3 + 4.0 * t2 + t) / 2.0,
+        (t3 - t2) / 2.0,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::color::{BLACK, WHITE};
+
+    /// Black and white pixels, alternating in both directions
+    fn checkerboard(size: usize) -> Image {
+        let colors = (0..size * size)
+            .map(|index| match (index % size + index / size) % 2 {
+                0 => BLACK,
+                _ => WHITE,
+            })
+            .collect::<Vec<_>>();
+        Image::from_colors(&colors, size, size).unwrap()
+    }
+
+    #[test]
+    fn box_weights_cover_the_box() {
+        let (first, weights) = box_weights(5.0, 4.0);
+        assert_eq!((first, weights.as_slice()), (3, [0.25; 4].as_slice()));
+
+        let (first, weights) = box_weights(2.3, 2.5);
+        assert_eq!(first, 1);
+        assert_eq!(weights.len(), 3);
+        assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        assert!((weights[0] - 0.95 / 2.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn downscaling_averages_the_footprint() {
+        let image = checkerboard(16);
+        // Scaling down by 4, every drawn pixel covers as much black as white
+        let gray = [127.5, 127.5, 127.5, 255.0];
+        for (x, y) in [(2.0, 2.0), (6.0, 10.0), (3.0, 8.0)] {
+            for filter in [Filter::Bilinear, Filter::Bicubic] {
+                assert_eq!(filter.sample_area(&image, x, y, 4.0, 4.0), gray);
+            }
+        }
+
+        // Sampling only at the center would land on one pixel or the other
+        let point = Filter::Bilinear.sample(&image, 2.5, 2.5);
+        assert!(point[0] == 0.0 || point[0] == 255.0);
+        assert_eq!(
+            Filter::Bilinear.sample_area(&image, 2.5, 2.5, 1.0, 1.0),
+            point
+        );
+        assert_eq!(
+            Filter::Nearest.sample_area(&image, 2.5, 2.5, 4.0, 4.0),
+            point
+        );
+    }
+}
@@ -0,0 +1,878 @@
+//! Supports what icon sets actually use:
+//! `<path>`, the basic shapes, `<g>` groups, `transform`, `viewBox`,
+//! fills and strokes with solid colors and linear or radial gradients, and `opacity`.
+//!
+//! Text, filters, masks, clip paths, `<use>` and CSS stylesheets are ignored, but inline `style` attributes work.
+//! Group opacity is applied to each shape on its own, so overlapping shapes in a translucent group show through each other.
+
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use palette::LinSrgb;
+use roxmltree::{Document, Node};
+
+use super::sampling::Filter;
+use super::{premultiply, Fit, Image, ImageError, ImageKey, ImageSource};
+use crate::graphics::color::{self, Color, Gamma};
+use crate::graphics::paint::{ColorStop, Gradient, GradientKind, Paint, Spread};
+use crate::graphics::path::{
+    CornerRadii, FillRule, LineCap, LineJoin, Path, PathSegment, Point, Polyline, Stroke,
+};
+use crate::graphics::transform::Transform;
+use crate::layout::{Position, Rect, Size};
+use crate::render::cache::Cache;
+use crate::render::rasterizer;
+use crate::render::{DrawHandle, Drawable, Pixel};
+
+const XLINK: &str = "http://www.w3.org/1999/xlink";
+
+#[derive(Debug)]
+pub enum SvgError {
+    Utf8(std::str::Utf8Error),
+    Xml(roxmltree::Error),
+    /// The root element isn't `<svg>`
+    NotSvg,
+    /// There's no `viewBox`, and no `width` and `height` to make one from
+    NoSize,
+}
+impl Display for SvgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SvgError::Utf8(error) => write!(f, "SvgError: {error}"),
+            SvgError::Xml(error) => write!(f, "SvgError: {error}"),
+            SvgError::NotSvg => write!(f, "SvgError: the root element isn't <svg>"),
+            SvgError::NoSize => write!(f, "SvgError: no viewBox, width or height"),
+        }
+    }
+}
+impl std::error::Error for SvgError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SvgError::Utf8(error) => Some(error),
+            SvgError::Xml(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// A parsed SVG document, flattened to a list of shapes in its own coordinates
+#[derive(Clone, Debug)]
+pub struct Svg {
+    /// The area of the document's coordinates that gets drawn, from the `viewBox` attribute
+    view_box: (Point, f32, f32),
+    /// `false` for `preserveAspectRatio="none"`, which stretches the view box;
+    /// otherwise it's scaled to fit and centered
+    keep_aspect_ratio: bool,
+    shapes: Vec<SvgShape>,
+}
+
+/// A shape with its transforms already applied, in the order it's drawn
+#[derive(Clone, Debug)]
+struct SvgShape {
+    path: Path,
+    fill: Option<(Paint, FillRule)>,
+    stroke: Option<(Paint, Stroke)>,
+}
+
+/// The inherited properties, as they are at some element
+#[derive(Clone)]
+struct Style {
+    fill: Option<PaintValue>,
+    fill_rule: FillRule,
+    fill_opacity: f32,
+    stroke: Option<PaintValue>,
+    stroke_width: f32,
+    stroke_opacity: f32,
+    line_cap: LineCap,
+    line_join: LineJoin,
+    miter_limit: f32,
+    /// The `color` property, which `currentColor` refers to
+    color: Color,
+    /// The `opacity` of the element and all of its ancestors, multiplied together
+    opacity: f32,
+    /// From the element's coordinates to the document's
+    transform: Transform,
+}
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fill: Some(PaintValue::Color(color::BLACK)),
+            fill_rule: FillRule::NonZero,
+            fill_opacity: 1.0,
+            stroke: None,
+            stroke_width: 1.0,
+            stroke_opacity: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            miter_limit: 4.0,
+            color: color::BLACK,
+            opacity: 1.0,
+            transform: Transform::IDENTITY,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum PaintValue {
+    Color(Color),
+    CurrentColor,
+    /// A gradient, by its `id`
+    Url(String),
+}
+
+impl Svg {
+    pub fn decode(bytes: &[u8]) -> Result<Self, SvgError> {
+        Self::parse(std::str::from_utf8(bytes).map_err(SvgError::Utf8)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, SvgError> {
+        let document = Document::parse(text).map_err(SvgError::Xml)?;
+        let root = document.root_element();
+        if root.tag_name().name() != "svg" {
+            return Err(SvgError::NotSvg);
+        }
+
+        let view_box = match root.attribute("viewBox").map(svgtypes::ViewBox::from_str) {
+            Some(Ok(view_box)) => (
+                Point::new(view_box.x as f32, view_box.y as f32),
+                view_box.w as f32,
+                view_box.h as f32,
+            ),
+            _ => {
+                let length = |name| root.attribute(name).and_then(|value| length(value, 0.0));
+                let (Some(width), Some(height)) = (length("width"), length("height")) else {
+                    return Err(SvgError::NoSize);
+                };
+                (Point::default(), width, height)
+            }
+        };
+
+        let keep_aspect_ratio = root
+            .attribute("preserveAspectRatio")
+            .is_none_or(|value| value.trim() != "none");
+
+        let mut svg = Svg {
+            view_box,
+            keep_aspect_ratio,
+            shapes: Vec::new(),
+        };
+        svg.walk(root, &Style::default());
+        Ok(svg)
+    }
+
+    /// Collects the shapes in `node` and its descendants, with the style they inherit
+    fn walk(&mut self, node: Node, inherited: &Style) {
+        if property(node, "display") == Some("none") {
+            return;
+        }
+        let style = style(node, inherited);
+
+        match node.tag_name().name() {
+            "svg" | "g" | "a" => node
+                .children()
+                .filter(Node::is_element)
+                .for_each(|child| self.walk(child, &style)),
+            name => {
+                if let Some(path) = shape_path(node, name, self.view_box) {
+                    self.push_shape(node, path, &style);
+                }
+            }
+        }
+    }
+
+    fn push_shape(&mut self, node: Node, path: Path, style: &Style) {
+        let visible = property(node, "visibility").is_none_or(|value| value == "visible");
+        if !visible {
+            return;
+        }
+
+        let paint = |value: &Option<PaintValue>, opacity: f32| {
+            let paint = match value.as_ref()? {
+                PaintValue::Color(color) => Paint::Solid(*color),
+                PaintValue::CurrentColor => Paint::Solid(style.color),
+                PaintValue::Url(id) => gradient(node.document(), id, &path, self.view_box)?,
+            };
+            Some(with_opacity(
+                paint.transformed(style.transform),
+                opacity * style.opacity,
+            ))
+        };
+        let fill = paint(&style.fill, style.fill_opacity).map(|paint| (paint, style.fill_rule));
+        let stroke = paint(&style.stroke, style.stroke_opacity)
+            .filter(|_| style.stroke_width > 0.0)
+            .map(|paint| {
+                let width = style.stroke_width * style.transform.scale_factor();
+                let stroke = Stroke::new(width)
+                    .with_cap(style.line_cap)
+                    .with_join(style.line_join)
+                    .with_miter_limit(style.miter_limit);
+                (paint, stroke)
+            });
+
+        if fill.is_some() || stroke.is_some() {
+            self.shapes.push(SvgShape {
+                path: path.transformed(style.transform),
+                fill,
+                stroke,
+            });
+        }
+    }
+
+    /// From the document's coordinates to the pixels of an image of `size`
+    fn view_transform(&self, size: Size) -> Transform {
+        let (origin, width, height) = self.view_box;
+        let (scale_x, scale_y) = (size.width as f32 / width, size.height as f32 / height);
+        let (scale_x, scale_y) = match self.keep_aspect_ratio {
+            true => (scale_x.min(scale_y), scale_x.min(scale_y)),
+            false => (scale_x, scale_y),
+        };
+        let offset_x = (size.width as f32 - width * scale_x) / 2.0;
+        let offset_y = (size.height as f32 - height * scale_y) / 2.0;
+
+        Transform::translate(-origin.x, -origin.y)
+            .then(Transform::scale(scale_x, scale_y))
+            .then(Transform::translate(offset_x, offset_y))
+    }
+
+    /// Rasterizes the document to fill an image of `size`.
+    /// With a `color`, every fill and stroke is drawn in it instead, keeping its opacity;
+    /// that's how monochrome icons are tinted
+    pub fn render(&self, size: Size, color: Option<Color>) -> Image {
+        let view = self.view_transform(size);
+        let bounds = Rect::new(Position { x: 0, y: 0 }, size);
+
+        // Premultiplied, in linear light, so that shapes blend the same way they do when drawn directly
+        let mut pixels = vec![[0.0_f32; 4]; size.width * size.height];
+        let mut draw = |polygons: &[Polyline], rule: FillRule, paint: &Paint| {
+            let paint = paint.transformed(view);
+            let paint = match color {
+                Some(color) => recolor(&paint, color),
+                None => paint,
+            };
+            rasterizer::fill(polygons, rule, bounds, |x, y, coverage| {
+                let center = Point::new(x as f32 + 0.5, y as f32 + 0.5);
+                let pixel = &mut pixels[y as usize * size.width + x as usize];
+                composite(pixel, paint.color_at(center), coverage);
+            });
+        };
+
+        for shape in &self.shapes {
+            let polylines = shape.path.transformed(view).flatten(rasterizer::TOLERANCE);
+            if let Some((paint, rule)) = &shape.fill {
+                draw(&polylines, *rule, paint);
+            }
+            if let Some((paint, stroke)) = &shape.stroke {
+                let stroke = Stroke {
+                    width: stroke.width * view.scale_factor(),
+                    ..*stroke
+                };
+                draw(
+                    &rasterizer::stroke(&polylines, &stroke),
+                    FillRule::NonZero,
+                    paint,
+                );
+            }
+        }
+
+        Image {
+            width: size.width,
+            height: size.height,
+            pixels: pixels.into_iter().map(encode).collect(),
+        }
+    }
+}
+
+/// Source-over compositing of `color` onto a premultiplied linear pixel
+fn composite(pixel: &mut [f32; 4], color: Color, coverage: u8) {
+    let alpha = color.alpha as f32 / 255.0 * coverage as f32 / 255.0;
+    let linear = Gamma::Srgb.to_linear(color.color);
+    let source = [linear.red, linear.green, linear.blue, 1.0];
+    (0..4).for_each(|channel| {
+        pixel[channel] = source[channel] * alpha + pixel[channel] * (1.0 - alpha);
+    });
+}
+
+/// Turns a premultiplied linear pixel into a premultiplied sRGB one, for an [`Image`]
+fn encode([red, green, blue, alpha]: [f32; 4]) -> [u8; 4] {
+    if alpha <= 0.0 {
+        return [0; 4];
+    }
+    let encoded = Gamma::Srgb.from_linear(LinSrgb::new(red / alpha, green / alpha, blue / alpha));
+    let alpha = (alpha.min(1.0) * 255.0).round() as u8;
+    premultiply([encoded.red, encoded.green, encoded.blue, alpha])
+}
+
+fn recolor(paint: &Paint, color: Color) -> Paint {
+    let tint = |alpha: u8| {
+        let alpha = (alpha as u32 * color.alpha as u32 + 127) / 255;
+        Color::new(color.red, color.green, color.blue, alpha as u8)
+    };
+    match paint {
+        Paint::Solid(solid) => Paint::Solid(tint(solid.alpha)),
+        // A gradient's colors are lost, but its changes in opacity aren't
+        Paint::Gradient(gradient) => Paint::Gradient(map_stops(gradient, |stop| {
+            ColorStop::new(stop.offset, tint(stop.color.alpha))
+        })),
+    }
+}
+
+fn with_opacity(paint: Paint, opacity: f32) -> Paint {
+    if opacity >= 1.0 {
+        return paint;
+    }
+    let fade = |color: Color| {
+        let alpha = (color.alpha as f32 * opacity.max(0.0)).round() as u8;
+        Color::new(color.red, color.green, color.blue, alpha)
+    };
+    match paint {
+        Paint::Solid(color) => Paint::Solid(fade(color)),
+        Paint::Gradient(gradient) => Paint::Gradient(map_stops(&gradient, |stop| {
+            ColorStop::new(stop.offset, fade(stop.color))
+        })),
+    }
+}
+
+fn map_stops(gradient: &Gradient, f: impl Fn(&ColorStop) -> ColorStop) -> Gradient {
+    let mut result = Gradient::new(gradient.kind, gradient.stops().iter().map(f))
+        .with_transform(gradient.transform());
+    result.spread = gradient.spread;
+    result.interpolation = gradient.interpolation;
+    result
+}
+
+/// A property from the element's `style` attribute, or else its presentation attribute
+fn property<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    let from_style = node.attribute("style").and_then(|style| {
+        style.split(';').rev().find_map(|declaration| {
+            let (key, value) = declaration.split_once(':')?;
+            (key.trim() == name).then_some(value.trim())
+        })
+    });
+    from_style
+        .or_else(|| node.attribute(name))
+        .map(str::trim)
+        .filter(|value| *value != "inherit")
+}
+
+fn style(node: Node, inherited: &Style) -> Style {
+    let mut style = inherited.clone();
+    let number = |name| property(node, name).and_then(|value| f32::from_str(value).ok());
+
+    if let Some(value) = property(node, "color").and_then(parse_color) {
+        style.color = value;
+    }
+    if let Some(value) = property(node, "fill") {
+        style.fill = parse_paint(value);
+    }
+    if let Some(value) = property(node, "stroke") {
+        style.stroke = parse_paint(value);
+    }
+    if let Some(value) = property(node, "fill-rule") {
+        style.fill_rule = match value {
+            "evenodd" => FillRule::EvenOdd,
+            _ => FillRule::NonZero,
+        };
+    }
+    if let Some(value) = property(node, "stroke-linecap") {
+        style.line_cap = match value {
+            "round" => LineCap::Round,
+            "square" => LineCap::Square,
+            _ => LineCap::Butt,
+        };
+    }
+    if let Some(value) = property(node, "stroke-linejoin") {
+        style.line_join = match value {
+            "round" => LineJoin::Round,
+            "bevel" => LineJoin::Bevel,
+            _ => LineJoin::Miter,
+        };
+    }
+    // Limits below 1 are an error in SVG, which leaves the property as it was
+    if let Some(value) = number("stroke-miterlimit").filter(|value| *value >= 1.0) {
+        style.miter_limit = value;
+    }
+    if let Some(value) = property(node, "stroke-width").and_then(|value| length(value, 0.0)) {
+        style.stroke_width = value;
+    }
+    if let Some(value) = number("fill-opacity") {
+        style.fill_opacity = value.clamp(0.0, 1.0);
+    }
+    if let Some(value) = number("stroke-opacity") {
+        style.stroke_opacity = value.clamp(0.0, 1.0);
+    }
+    // Unlike the others, `opacity` isn't inherited, but it applies to everything inside
+    if let Some(value) = number("opacity") {
+        style.opacity *= value.clamp(0.0, 1.0);
+    }
+    if let Some(transform) = node.attribute("transform").and_then(parse_transform) {
+        style.transform = transform.then(style.transform);
+    }
+
+    style
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    let color = svgtypes::Color::from_str(value).ok()?;
+    Some(Color::new(color.red, color.green, color.blue, color.alpha))
+}
+
+/// `None` for `none`, and for values that can't be parsed
+fn parse_paint(value: &str) -> Option<PaintValue> {
+    match svgtypes::Paint::from_str(value).ok()? {
+        svgtypes::Paint::Color(color) => Some(PaintValue::Color(Color::new(
+            color.red,
+            color.green,
+            color.blue,
+            color.alpha,
+        ))),
+        svgtypes::Paint::CurrentColor => Some(PaintValue::CurrentColor),
+        svgtypes::Paint::FuncIRI(id, _) => Some(PaintValue::Url(id.to_owned())),
+        _ => None,
+    }
+}
+
+fn parse_transform(value: &str) -> Option<Transform> {
+    let transform = svgtypes::Transform::from_str(value).ok()?;
+    let [a, b, c, d, e, f] = [
+        transform.a,
+        transform.b,
+        transform.c,
+        transform.d,
+        transform.e,
+        transform.f,
+    ]
+    .map(|value| value as f32);
+    Some(Transform::new(a, b, c, d, e, f))
+}
+
+/// A length in pixels; percentages are of `reference`
+fn length(value: &str, reference: f32) -> Option<f32> {
+    use svgtypes::LengthUnit;
+    let length = svgtypes::Length::from_str(value).ok()?;
+    let number = length.number as f32;
+    Some(match length.unit {
+        LengthUnit::None | LengthUnit::Px => number,
+        LengthUnit::Percent => number / 100.0 * reference,
+        LengthUnit::In => number * 96.0,
+        LengthUnit::Cm => number * 96.0 / 2.54,
+        LengthUnit::Mm => number * 96.0 / 25.4,
+        LengthUnit::Pt => number * 96.0 / 72.0,
+        LengthUnit::Pc => number * 96.0 / 6.0,
+        // Relative to a font size we don't have; assume the usual 16 pixels
+        LengthUnit::Em => number * 16.0,
+        LengthUnit::Ex => number * 8.0,
+    })
+}
+
+/// The outline of a shape element, in its own coordinates
+fn shape_path(node: Node, name: &str, view_box: (Point, f32, f32)) -> Option<Path> {
+    let (_, view_width, view_height) = view_box;
+    let diagonal = view_width.hypot(view_height) / std::f32::consts::SQRT_2;
+    let attribute = |name: &str, reference: f32| {
+        node.attribute(name)
+            .and_then(|value| length(value, reference))
+            .unwrap_or(0.0)
+    };
+    let x = |name| attribute(name, view_width);
+    let y = |name| attribute(name, view_height);
+
+    match name {
+        "path" => Some(parse_path(node.attribute("d")?)),
+        "rect" => {
+            let (width, height) = (x("width"), y("height"));
+            if width <= 0.0 || height <= 0.0 {
+                return None;
+            }
+            // Elliptical corners are approximated with circular ones
+            let radius = match (node.attribute("rx"), node.attribute("ry")) {
+                (Some(_), _) => x("rx"),
+                (None, Some(_)) => y("ry"),
+                (None, None) => 0.0,
+            };
+            Some(Path::rounded_rect(
+                Point::new(x("x"), y("y")),
+                width,
+                height,
+                CornerRadii::all(radius),
+            ))
+        }
+        "circle" => {
+            let radius = attribute("r", diagonal);
+            (radius > 0.0).then(|| Path::circle(Point::new(x("cx"), y("cy")), radius))
+        }
+        "ellipse" => {
+            let (radius_x, radius_y) = (x("rx"), y("ry"));
+            (radius_x > 0.0 && radius_y > 0.0)
+                .then(|| Path::ellipse(Point::new(x("cx"), y("cy")), radius_x, radius_y))
+        }
+        "line" => Some(Path::line(
+            Point::new(x("x1"), y("y1")),
+            Point::new(x("x2"), y("y2")),
+        )),
+        "polyline" | "polygon" => {
+            let mut points = svgtypes::PointsParser::from(node.attribute("points")?)
+                .map(|(x, y)| Point::new(x as f32, y as f32));
+            let mut path = Path::new().move_to(points.next()?);
+            for point in points {
+                path = path.line_to(point);
+            }
+            Some(match name {
+                "polygon" => path.close(),
+                _ => path,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Path data, up to the first error, like browsers do
+fn parse_path(data: &str) -> Path {
+    use svgtypes::SimplePathSegment as Segment;
+    let point = |x: f64, y: f64| Point::new(x as f32, y as f32);
+
+    let mut path = Path::new();
+    for segment in svgtypes::SimplifyingPathParser::from(data) {
+        let Ok(segment) = segment else {
+            break;
+        };
+        path.push(match segment {
+            Segment::MoveTo { x, y } => PathSegment::MoveTo(point(x, y)),
+            Segment::LineTo { x, y } => PathSegment::LineTo(point(x, y)),
+            Segment::Quadratic { x1, y1, x, y } => PathSegment::QuadTo(point(x1, y1), point(x, y)),
+            Segment::CurveTo {
+                x1,
+                y1,
+                x2,
+                y2,
+                x,
+                y,
+            } => PathSegment::CubicTo(point(x1, y1), point(x2, y2), point(x, y)),
+            Segment::ClosePath => PathSegment::Close,
+        });
+    }
+    path
+}
+
+/// The element that `node` refers to with its `href`, if any
+fn referenced<'a, 'input>(node: Node<'a, 'input>) -> Option<Node<'a, 'input>> {
+    let href = node
+        .attribute("href")
+        .or_else(|| node.attribute((XLINK, "href")))?;
+    let id = href.strip_prefix('#')?;
+    node.document()
+        .descendants()
+        .find(|other| other.attribute("id") == Some(id))
+}
+
+/// Follows a gradient's `href` chain, which can provide any attributes or stops it doesn't have itself
+fn gradient_chain<'a, 'input>(node: Node<'a, 'input>) -> Vec<Node<'a, 'input>> {
+    let mut chain = vec![node];
+    while let Some(next) = chain.last().copied().and_then(referenced) {
+        // Cycles, or ridiculously long chains
+        if chain.len() > 8 || chain.contains(&next) {
+            break;
+        }
+        chain.push(next);
+    }
+    chain
+}
+
+/// The gradient with the given `id`, for filling or stroking `path`
+fn gradient(
+    document: &Document,
+    id: &str,
+    path: &Path,
+    view_box: (Point, f32, f32),
+) -> Option<Paint> {
+    let node = document
+        .descendants()
+        .find(|node| node.attribute("id") == Some(id))?;
+    let chain = gradient_chain(node);
+    let attribute = |name: &str| chain.iter().find_map(|node| node.attribute(name));
+
+    let stops = chain
+        .iter()
+        .map(|node| {
+            node.children()
+                .filter(|child| child.tag_name().name() == "stop")
+                .collect::<Vec<_>>()
+        })
+        .find(|stops| !stops.is_empty())
+        .unwrap_or_default();
+    // Offsets can't go backwards; a stop before the previous one is moved up to it
+    let mut previous = 0.0_f32;
+    let stops = stops
+        .into_iter()
+        .map(|stop| {
+            let offset = stop
+                .attribute("offset")
+                .and_then(|value| length(value, 1.0))
+                .unwrap_or(0.0)
+                .clamp(previous, 1.0);
+            previous = offset;
+
+            let color = property(stop, "stop-color")
+                .and_then(parse_color)
+                .unwrap_or(color::BLACK);
+            let opacity = property(stop, "stop-opacity")
+                .and_then(|value| f32::from_str(value).ok())
+                .unwrap_or(1.0)
+                .clamp(0.0, 1.0);
+            let alpha = (color.alpha as f32 * opacity).round() as u8;
+            ColorStop::new(
+                offset,
+                Color::new(color.red, color.green, color.blue, alpha),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    match stops[..] {
+        [] => return None,
+        [only] => return Some(Paint::Solid(only.color)),
+        _ => (),
+    }
+
+    // Coordinates are fractions of the shape's bounding box, unless they're in user space
+    let user_space = attribute("gradientUnits") == Some("userSpaceOnUse");
+    let (_, view_width, view_height) = view_box;
+    let (reference_x, reference_y) = match user_space {
+        true => (view_width, view_height),
+        false => (1.0, 1.0),
+    };
+    let value = |name: &str, reference: f32, default: &str| {
+        length(attribute(name).unwrap_or(default), reference).unwrap_or(0.0)
+    };
+
+    let kind = match node.tag_name().name() {
+        "linearGradient" => GradientKind::Linear {
+            start: Point::new(
+                value("x1", reference_x, "0%"),
+                value("y1", reference_y, "0%"),
+            ),
+            end: Point::new(
+                value("x2", reference_x, "100%"),
+                value("y2", reference_y, "0%"),
+            ),
+        },
+        "radialGradient" => GradientKind::Radial {
+            center: Point::new(
+                value("cx", reference_x, "50%"),
+                value("cy", reference_y, "50%"),
+            ),
+            radius: value(
+                "r",
+                reference_x.hypot(reference_y) / std::f32::consts::SQRT_2,
+                "50%",
+            ),
+        },
+        _ => return None,
+    };
+
+    let mut transform = attribute("gradientTransform")
+        .and_then(parse_transform)
+        .unwrap_or_default();
+    if !user_space {
+        // A bounding box with no area has no units to go by, so the shape isn't painted at all
+        let (min, max) = bounding_box(path).filter(|(min, max)| min.x < max.x && min.y < max.y)?;
+        transform = transform
+            .then(Transform::scale(max.x - min.x, max.y - min.y))
+            .then(Transform::translate(min.x, min.y));
+    }
+
+    let mut gradient = Gradient::new(kind, stops).with_transform(transform);
+    gradient.spread = match attribute("spreadMethod") {
+        Some("reflect") => Spread::Reflect,
+        Some("repeat") => Spread::Repeat,
+        _ => Spread::Pad,
+    };
+    Some(Paint::Gradient(gradient))
+}
+
+/// The top left and bottom right corners of the path, from its flattened outline
+fn bounding_box(path: &Path) -> Option<(Point, Point)> {
+    let polylines = path.flatten(rasterizer::TOLERANCE);
+    let mut points = polylines.iter().flat_map(|polyline| &polyline.points);
+    let first = *points.next()?;
+    Some(points.fold((first, first), |(min, max), point| {
+        (
+            Point::new(min.x.min(point.x), min.y.min(point.y)),
+            Point::new(max.x.max(point.x), max.y.max(point.y)),
+        )
+    }))
+}
+
+/// Identifies a rendered SVG in the SVG cache
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct SvgKey {
+    source: ImageKey,
+    width: Pixel,
+    height: Pixel,
+    /// The color override, as `[red, green, blue, alpha]`
+    color: Option<[u8; 4]>,
+}
+
+/// Default memory budget of the SVG cache, in bytes
+pub const SVG_CACHE_BUDGET: usize = 16 * 1024 * 1024;
+
+thread_local! {
+    /// SVG documents rendered at the sizes and colors they've been drawn with, on this thread
+    static SVG_CACHE: RefCell<Cache<SvgKey, Image>> = RefCell::new(Cache::new(SVG_CACHE_BUDGET));
+}
+
+/// Runs `f` with this thread's SVG cache,
+/// e.g. to change its memory budget or look at its statistics
+pub fn with_svg_cache<R>(f: impl FnOnce(&mut Cache<SvgKey, Image>) -> R) -> R {
+    SVG_CACHE.with(|cache| f(&mut cache.borrow_mut()))
+}
+
+/// An SVG document drawn to fill a box of a given size, keeping its aspect ratio unless it says not to
+#[derive(Clone, Debug)]
+pub struct SvgView {
+    pub source: ImageSource,
+    pub size: Size,
+    /// Draws every shape in this color, for monochrome icons; see [`Svg::render`]
+    pub color: Option<Color>,
+}
+impl SvgView {
+    pub fn new(source: ImageSource, size: Size) -> Self {
+        Self {
+            source,
+            size,
+            color: None,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    /// Gets the rendered document from the SVG cache, parsing and rendering it if it's not there.
+    /// Drawing does this too, but this is where parsing errors can be handled
+    pub fn load(&self) -> Result<Rc<Image>, ImageError> {
+        self.load_at(self.size)
+    }
+
+    /// Like [`Self::load`], but rendered at `size` instead of the view's own size
+    fn load_at(&self, size: Size) -> Result<Rc<Image>, ImageError> {
+        let key = SvgKey {
+            source: self.source.key(),
+            width: size.width,
+            height: size.height,
+            color: self
+                .color
+                .map(|color| [color.red, color.green, color.blue, color.alpha]),
+        };
+        with_svg_cache(|cache| {
+            cache.try_get_or_insert_with(key, || {
+                let svg = Svg::decode(&self.source.read()?).map_err(ImageError::Svg)?;
+                Ok(svg.render(size, self.color))
+            })
+        })
+    }
+}
+impl Drawable for SvgView {
+    fn draw_at(&self, handle: &mut DrawHandle, position: Position) {
+        // Pixels land on pixels when the transform only moves things by whole pixels,
+        // so the document is rendered at exactly the right size.
+        // Otherwise it's rendered at the scale it's drawn at, and resampled from there
+        let (size, filter) = match handle.pixel_offset() {
+            Some(_) => (self.size, Filter::Nearest),
+            None => {
+                let scale = handle.transform().scale_factor();
+                let scaled = |length: Pixel| ((length as f32 * scale).round() as Pixel).max(1);
+                let size = Size {
+                    width: scaled(self.size.width),
+                    height: scaled(self.size.height),
+                };
+                (size, Filter::Bilinear)
+            }
+        };
+        let image = match self.load_at(size) {
+            Ok(image) => image,
+            Err(error) => {
+                log::warn!("couldn't load SVG {:?}: {error}", self.source.key());
+                return;
+            }
+        };
+        image.draw_in(handle, Rect::new(position, self.size), Fit::Fill, filter);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(Svg::parse("<svg"), Err(SvgError::Xml(_))));
+        assert!(matches!(Svg::parse("<html/>"), Err(SvgError::NotSvg)));
+        assert!(matches!(Svg::parse("<svg/>"), Err(SvgError::NoSize)));
+        assert!(matches!(Svg::decode(&[b'<', 0xFF]), Err(SvgError::Utf8(_))));
+    }
+
+    #[test]
+    fn caches_each_size() {
+        let source = ImageSource::bytes(
+            &br#"<svg viewBox="0 0 2 2"><rect width="1" height="1"/></svg>"#[..],
+        );
+        let small = SvgView::new(
+            source.clone(),
+            Size {
+                width: 8,
+                height: 8,
+            },
+        );
+        let large = SvgView::new(
+            source,
+            Size {
+                width: 16,
+                height: 16,
+            },
+        );
+
+        let first = small.load().unwrap();
+        assert!(Rc::ptr_eq(&first, &small.load().unwrap()));
+        assert_eq!(large.load().unwrap().size(), large.size);
+        assert!(!Rc::ptr_eq(
+            &first,
+            &small.clone().with_color(color::WHITE).load().unwrap()
+        ));
+
+        // The top left quarter is covered, and nothing else
+        assert_eq!(first.pixels()[0], [0, 0, 0, 255]);
+        assert_eq!(first.pixels()[3 * 8 + 3], [0, 0, 0, 255]);
+        assert_eq!(first.pixels()[4 * 8 + 4], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn bounding_box_units_need_an_area() {
+        let render = |units: &str| {
+            let svg = Svg::parse(&format!(
+                r#"<svg viewBox="0 0 8 8">
+                    <linearGradient id="g" gradientUnits="{units}">
+                        <stop offset="0" stop-color="red"/>
+                        <stop offset="1" stop-color="blue"/>
+                    </linearGradient>
+                    <line x1="0" y1="4" x2="8" y2="4" stroke="url(#g)" stroke-width="2"/>
+                </svg>"#
+            ))
+            .unwrap();
+            svg.render(
+                Size {
+                    width: 8,
+                    height: 8,
+                },
+                None,
+            )
+        };
+
+        // A horizontal line is flat, so its stroke isn't drawn
+        let flat = render("objectBoundingBox");
+        assert!(flat.pixels().iter().all(|pixel| pixel[3] == 0));
+        let user_space = render("userSpaceOnUse");
+        assert_eq!(user_space.pixels()[4 * 8 + 4][3], 255);
+    }
+}
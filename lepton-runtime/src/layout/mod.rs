@@ -1,15 +1,63 @@
 use crate::render::Pixel;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Size {
     pub width: Pixel,
     pub height: Pixel,
 }
 
-/// Position from the top left of the screen
+/// Position from the top left of the screen.
+/// This can be negative, for things that are partly off-screen or scrolled out of view
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Position {
-    pub x: Pixel,
-    pub y: Pixel,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// An axis-aligned rectangle, from its top left corner
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rect {
+    pub position: Position,
+    pub size: Size,
+}
+impl Rect {
+    pub fn new(position: Position, size: Size) -> Self {
+        Self { position, size }
+    }
+
+    /// The edge just past the right of the rectangle
+    pub fn right(&self) -> i32 {
+        self.position.x + self.size.width as i32
+    }
+
+    /// The edge just past the bottom of the rectangle
+    pub fn bottom(&self) -> i32 {
+        self.position.y + self.size.height as i32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size.width == 0 || self.size.height == 0
+    }
+
+    pub fn contains(&self, position: Position) -> bool {
+        (self.position.x..self.right()).contains(&position.x)
+            && (self.position.y..self.bottom()).contains(&position.y)
+    }
+
+    /// The area covered by both rectangles; empty if they don't overlap
+    pub fn intersection(&self, other: Rect) -> Rect {
+        let x = i32::max(self.position.x, other.position.x);
+        let y = i32::max(self.position.y, other.position.y);
+        let right = i32::min(self.right(), other.right());
+        let bottom = i32::min(self.bottom(), other.bottom());
+        Rect {
+            position: Position { x, y },
+            size: Size {
+                width: (right - x).max(0) as Pixel,
+                height: (bottom - y).max(0) as Pixel,
+            },
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -48,3 +96,43 @@ impl SizeConstraint {
         self.height.map(|ch| height <= ch).unwrap_or(true)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: Pixel, height: Pixel) -> Rect {
+        Rect::new(Position { x, y }, Size { width, height })
+    }
+
+    #[test]
+    fn contains_is_half_open() {
+        let rect = rect(-2, 3, 4, 2);
+        assert!(rect.contains(Position { x: -2, y: 3 }));
+        assert!(rect.contains(Position { x: 1, y: 4 }));
+        assert!(!rect.contains(Position { x: 2, y: 4 }));
+        assert!(!rect.contains(Position { x: 1, y: 5 }));
+        assert!(!rect.contains(Position { x: -3, y: 3 }));
+        assert!(!Rect::new(
+            Position { x: 0, y: 0 },
+            Size {
+                width: 0,
+                height: 5
+            }
+        )
+        .contains(Position { x: 0, y: 0 }));
+    }
+
+    #[test]
+    fn intersection() {
+        let a = rect(-4, -4, 10, 6);
+        assert_eq!(a.intersection(rect(0, 0, 10, 10)), rect(0, 0, 6, 2));
+        assert_eq!(a.intersection(rect(-2, -3, 2, 2)), rect(-2, -3, 2, 2));
+        assert_eq!(a.intersection(a), a);
+
+        // Rectangles that don't overlap, or only touch, intersect to nothing
+        assert!(a.intersection(rect(6, 0, 5, 5)).is_empty());
+        assert!(a.intersection(rect(20, 20, 5, 5)).is_empty());
+        assert!(a.intersection(rect(-20, -20, 5, 5)).is_empty());
+    }
+}
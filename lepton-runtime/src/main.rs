@@ -9,11 +9,17 @@ pub mod render;
 
 pub mod layout;
 
+/// Decoding, caching and drawing of raster images, like icons and pictures
+pub mod image;
+
 use std::{num::NonZeroU32, sync::OnceLock};
 
-use crate::graphics::{text::RichString, typeface::Typeface};
-use crate::layout::Position;
+use crate::graphics::color::{self, Color};
+use crate::graphics::text::{Attribute, Decoration, RichString, TextOptions};
+use crate::graphics::typeface::{FontStyle, FontWeight, Typeface};
+use crate::layout::{Position, SizeConstraint};
 use crate::render::{DrawHandle, Drawable, text::TextBody};
+use crate::render::surface::{Surface, WindowSurface};
 
 use winit::{
     event::{Event, WindowEvent},
@@ -42,19 +48,19 @@ fn main() {
         // "Pack my box with five dozen liquor jugs"
         ;
 
-    let _paragraph = concat!(
-        "I'm on the highway, life in the dark - still it's your face, won't leave me alone.",
-        "I'm in that fast lane, riding from my wrongs,",
-        "and when I lose my faith, I'm hopeless but I'm yours.",
-        "Staring at Beretta Lake",
-
-        "Climbing branches up to brick red rooftops, Lilly keeps her secrets to herself.",
-        "Longing for the low end, hearing nothing much worth hearing, until she heard something else -",
-        "locked and waiting for the key...",
-        "Watch the morning into sundown, what Lilly says is there inside her stare",
-        "Chase the sun across the pavement, where the cars are warm and parked inside the lines.",
-        "Knowing nothing but exactly what he knows, and he knows what's better.",
-        "Up the arm rest, scale the branches of the olive tree",
+    let paragraph = concat!(
+        "I'm on the highway, life in the dark - still it's your face, won't leave me alone. ",
+        "I'm in that fast lane, riding from my wrongs, ",
+        "and when I lose my faith, I'm hopeless but I'm yours. ",
+        "Staring at Beretta Lake\n",
+
+        "Climbing branches up to brick red rooftops, Lilly keeps her secrets to herself. ",
+        "Longing for the low end, hearing nothing much worth hearing, until she heard something else - ",
+        "locked and waiting for the key... ",
+        "Watch the morning into sundown, what Lilly says is there inside her stare. ",
+        "Chase the sun across the pavement, where the cars are warm and parked inside the lines. ",
+        "Knowing nothing but exactly what he knows, and he knows what's better. ",
+        "Up the arm rest, scale the branches of the olive tree. ",
         "Wait until the gradient begins to form, Simon says to let him in",
     );
 
@@ -80,24 +86,36 @@ fn main() {
                         )
                         .unwrap();
 
-                    let mut buffer = surface.buffer_mut().unwrap();
-                    for index in 0..(width * height) {
-                        buffer[index as usize] = 0x0;
-                    }
+                    let buffer = surface.buffer_mut().unwrap();
+                    let mut frame = WindowSurface::new(buffer, width as usize, height as usize);
+                    frame.fill(color::BLACK);
 
-                    let mut handle = DrawHandle {
-                        buffer: &mut buffer,
-                        width: width as usize,
-                    };
+                    let mut handle = DrawHandle::new(&mut frame);
 
-                    let text = RichString::new(sentence.to_owned(), typeface());
+                    let text = RichString::new(sentence.to_owned(), typeface())
+                        .with(0..6, Attribute::Weight(FontWeight::Bold.into()))
+                        .with(10..22, Attribute::Style(FontStyle::Italic))
+                        .with(10..22, Attribute::Color(Color::new(255, 200, 80, 255)))
+                        .with(33..36, Attribute::Decoration(Decoration::UNDERLINE));
 
                     TextBody::new_label(text, graphics::text::Direction::Horizontal).draw_at(&mut handle, Position {
                         x: 15,
                         y: 15
                     });
 
-                    buffer.present().unwrap();
+                    let text = RichString::new(paragraph.to_owned(), typeface())
+                        .with_default(Attribute::Size(20));
+                    let options = TextOptions {
+                        paragraph_spacing: 12,
+                        ..Default::default()
+                    };
+                    let max_size = SizeConstraint::intrinsic_width((width as usize).saturating_sub(30));
+                    TextBody::layout(text, options, max_size).draw_at(&mut handle, Position {
+                        x: 15,
+                        y: 75
+                    });
+
+                    frame.present().unwrap();
                 }
                 _ => (),
             }
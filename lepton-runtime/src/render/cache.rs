@@ -0,0 +1,237 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::rc::Rc;
+
+/// Anything that can be kept in a [`Cache`], which needs to know how much memory it takes up
+pub trait Footprint {
+    /// Approximate size in bytes; this only has to be good enough to budget the cache with
+    fn bytes(&self) -> usize;
+}
+
+/// Counters for how a [`Cache`] has been used, since it was created or last reset
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped to stay within the budget
+    pub evictions: u64,
+}
+
+struct Entry<V> {
+    value: Rc<V>,
+    bytes: usize,
+    /// When this entry was last used; the key into `Cache::recency`
+    tick: u64,
+}
+
+/// A least-recently-used cache with a memory budget.
+///
+/// Values are shared through `Rc`, so an entry that gets evicted stays alive
+/// for as long as something is still drawing with it.
+/// A single value bigger than the whole budget is handed out, but not kept.
+pub struct Cache<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    /// Keys by when they were last used, oldest first
+    recency: BTreeMap<u64, K>,
+    tick: u64,
+
+    budget: usize,
+    used: usize,
+    stats: CacheStats,
+}
+impl<K: Hash + Eq + Clone, V: Footprint> Cache<K, V> {
+    /// `budget` is in bytes
+    pub fn new(budget: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            budget,
+            used: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Gets the value for `key`, creating it with `create` if it's not in the cache
+    pub fn get_or_insert_with(&mut self, key: K, create: impl FnOnce() -> V) -> Rc<V> {
+        match self.try_get_or_insert_with(key, || Ok::<V, std::convert::Infallible>(create())) {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Like [`Self::get_or_insert_with`], for values that can fail to be created;
+    /// failures aren't cached, so the next call tries again
+    pub fn try_get_or_insert_with<E>(
+        &mut self,
+        key: K,
+        create: impl FnOnce() -> Result<V, E>,
+    ) -> Result<Rc<V>, E> {
+        self.tick += 1;
+
+        if let Some(entry) = self.entries.get_mut(&key) {
+            self.stats.hits += 1;
+            self.recency.remove(&entry.tick);
+            self.recency.insert(self.tick, key);
+            entry.tick = self.tick;
+            return Ok(entry.value.clone());
+        }
+
+        self.stats.misses += 1;
+        let value = Rc::new(create()?);
+        let bytes = value.bytes();
+        if bytes > self.budget {
+            return Ok(value);
+        }
+
+        self.used += bytes;
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                value: value.clone(),
+                bytes,
+                tick: self.tick,
+            },
+        );
+        self.evict();
+
+        Ok(value)
+    }
+
+    /// Drops the least recently used entries until the cache fits in its budget
+    fn evict(&mut self) {
+        while self.used > self.budget {
+            let Some((_, key)) = self.recency.pop_first() else {
+                return;
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.used -= entry.bytes;
+                self.stats.evictions += 1;
+            }
+        }
+    }
+
+    /// Memory budget in bytes
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Changes the memory budget, evicting entries right away if they don't fit anymore
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict();
+    }
+
+    /// Bytes taken up by the entries in the cache
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    /// Removes every entry; this doesn't count as evicting them
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+        self.used = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Footprint for Vec<u8> {
+        fn bytes(&self) -> usize {
+            self.len()
+        }
+    }
+
+    #[test]
+    fn hits_and_misses() {
+        let mut cache = Cache::<u32, Vec<u8>>::new(100);
+        cache.get_or_insert_with(1, || vec![0; 10]);
+        cache.get_or_insert_with(1, || unreachable!());
+        cache.get_or_insert_with(2, || vec![0; 10]);
+
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 2,
+                evictions: 0
+            }
+        );
+        assert_eq!(cache.used(), 20);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = Cache::<u32, Vec<u8>>::new(30);
+        cache.get_or_insert_with(1, || vec![0; 10]);
+        cache.get_or_insert_with(2, || vec![0; 10]);
+        cache.get_or_insert_with(3, || vec![0; 10]);
+        // 1 is now the most recently used, leaving 2 to be evicted
+        cache.get_or_insert_with(1, || unreachable!());
+        cache.get_or_insert_with(4, || vec![0; 10]);
+
+        assert_eq!(cache.stats().evictions, 1);
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.used(), 30);
+
+        cache.get_or_insert_with(1, || unreachable!());
+        cache.get_or_insert_with(2, || vec![0; 10]);
+        assert_eq!(cache.stats().misses, 5);
+    }
+
+    #[test]
+    fn oversized_values_are_not_kept() {
+        let mut cache = Cache::<u32, Vec<u8>>::new(30);
+        cache.get_or_insert_with(1, || vec![0; 10]);
+        let value = cache.get_or_insert_with(2, || vec![0; 40]);
+
+        assert_eq!(value.len(), 40);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.stats().evictions, 0);
+    }
+
+    #[test]
+    fn shrinking_the_budget_evicts() {
+        let mut cache = Cache::<u32, Vec<u8>>::new(30);
+        (0..3).for_each(|key| {
+            cache.get_or_insert_with(key, || vec![0; 10]);
+        });
+        cache.set_budget(15);
+
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.used(), 10);
+        assert_eq!(cache.stats().evictions, 2);
+    }
+
+    #[test]
+    fn failures_are_not_kept() {
+        let mut cache = Cache::<u32, Vec<u8>>::new(30);
+        let result = cache.try_get_or_insert_with(1, || Err("no"));
+        assert_eq!(result, Err("no"));
+        assert!(cache.is_empty());
+
+        let value = cache.try_get_or_insert_with(1, || Ok::<_, ()>(vec![0; 10]));
+        assert_eq!(value.map(|value| value.len()), Ok(10));
+        assert_eq!(cache.stats().misses, 2);
+    }
+}
@@ -0,0 +1,514 @@
+use super::{rasterizer, DrawHandle, Drawable, Pixel};
+use crate::graphics::color::Color;
+use crate::graphics::path::{CornerRadii, FillRule, Path, Point, Polyline};
+use crate::graphics::transform::Transform;
+use crate::layout::{Position, Rect, Size};
+
+/// A shadow cast by a box, like the CSS `box-shadow` property.
+///
+/// Shadows are drawn on their own, before the box for drop shadows and after its background for inset ones.
+/// Like in CSS, a drop shadow doesn't show through a transparent box, and an inset one stays inside of it
+#[derive(Clone, Debug)]
+pub struct BoxShadow {
+    /// The box casting the shadow, with its top left at the position it's drawn at
+    pub size: Size,
+    pub radii: CornerRadii,
+
+    pub color: Color,
+    /// How far the shadow is moved from the box
+    pub offset: Point,
+    /// How far the shadow's edge fades out; twice the standard deviation of the Gaussian blur, like in CSS
+    pub blur: f32,
+    /// How much the shadow grows in every direction before it's blurred; negative shrinks it
+    pub spread: f32,
+    /// `true` to cast the shadow inside of the box, from its edges, instead of behind it
+    pub inset: bool,
+}
+impl BoxShadow {
+    /// A hard shadow right behind the box, which won't show up until it's moved, spread or blurred
+    pub fn new(size: Size, color: Color) -> Self {
+        Self {
+            size,
+            radii: CornerRadii::default(),
+            color,
+            offset: Point::default(),
+            blur: 0.0,
+            spread: 0.0,
+            inset: false,
+        }
+    }
+
+    pub fn with_radii(mut self, radii: CornerRadii) -> Self {
+        self.radii = radii;
+        self
+    }
+
+    pub fn with_offset(mut self, x: f32, y: f32) -> Self {
+        self.offset = Point::new(x, y);
+        self
+    }
+
+    pub fn with_blur(mut self, blur: f32) -> Self {
+        self.blur = blur.max(0.0);
+        self
+    }
+
+    pub fn with_spread(mut self, spread: f32) -> Self {
+        self.spread = spread;
+        self
+    }
+
+    pub fn inset(mut self) -> Self {
+        self.inset = true;
+        self
+    }
+
+    /// The shadow's rounded rect before it's blurred, with the box's top left at the origin,
+    /// as its top left corner, its bottom right corner and its corner radii
+    fn rect(&self) -> (Point, Point, CornerRadii) {
+        let (width, height) = (self.size.width as f32, self.size.height as f32);
+        let spread = match self.inset {
+            true => -self.spread,
+            false => self.spread,
+        };
+        // Rounded corners grow and shrink with the shadow, but square ones stay square
+        let radius = |radius: f32| match radius > 0.0 {
+            true => (radius + spread).max(0.0),
+            false => 0.0,
+        };
+        let radii = CornerRadii {
+            top_left: radius(self.radii.top_left),
+            top_right: radius(self.radii.top_right),
+            bottom_right: radius(self.radii.bottom_right),
+            bottom_left: radius(self.radii.bottom_left),
+        };
+        let min = self.offset - Point::new(spread, spread);
+        let (width, height) = (width + 2.0 * spread, height + 2.0 * spread);
+        let max = min + Point::new(width.max(0.0), height.max(0.0));
+        (min, max, radii.fit(max.x - min.x, max.y - min.y))
+    }
+
+    /// The shape of the shadow before it's blurred, with the box's top left at the origin
+    fn shape(&self) -> Path {
+        let (min, max, radii) = self.rect();
+        Path::rounded_rect(min, max.x - min.x, max.y - min.y, radii)
+    }
+}
+impl Drawable for BoxShadow {
+    fn draw_at(&self, handle: &mut DrawHandle, position: Position) {
+        let transform =
+            Transform::translate(position.x as f32, position.y as f32).then(handle.transform());
+        let Some(inverse) = transform.inverse() else {
+            return;
+        };
+        let (width, height) = (self.size.width as f32, self.size.height as f32);
+        let outline = Path::rounded_rect(Point::default(), width, height, self.radii)
+            .transformed(transform)
+            .flatten(rasterizer::TOLERANCE);
+        let shadow = self
+            .shape()
+            .transformed(transform)
+            .flatten(rasterizer::TOLERANCE);
+
+        let (sigma, scale) = (self.blur / 2.0, transform.scale_factor());
+        let clip = handle.clip();
+        let visible = match self.inset {
+            true => rasterizer::pixel_bounds(&outline).map(|bounds| bounds.intersection(clip)),
+            false => rasterizer::pixel_bounds(&shadow)
+                .map(|bounds| expand_within(bounds, blur_extent(sigma * scale), clip)),
+        };
+        let Some(visible) = visible.filter(|visible| !visible.is_empty()) else {
+            return;
+        };
+
+        // A blurred shadow is worked out for each pixel on its own, so it costs the same however far it blurs.
+        // Without a blur, it's just its shape
+        let hard = (sigma <= 0.0).then(|| coverage(&shadow, visible));
+        let (min, max, radii) = self.rect();
+        // Blurring by the size of a pixel too averages over the pixel, like the rasterizer does for edges
+        let sigma = (sigma * sigma + 1.0 / (12.0 * scale * scale)).sqrt();
+        let outline = coverage(&outline, visible);
+
+        let mut index = 0;
+        for y in visible.position.y..visible.bottom() {
+            for x in visible.position.x..visible.right() {
+                let shadow = match &hard {
+                    Some(hard) => hard[index][0],
+                    None => {
+                        let center = inverse.apply(Point::new(x as f32 + 0.5, y as f32 + 0.5));
+                        blurred_coverage(min, max, radii, center, sigma)
+                    }
+                };
+                // An inset shadow is cast by everything around the shadow's shape
+                let coverage = match self.inset {
+                    true => (1.0 - shadow) * outline[index][0],
+                    false => shadow * (1.0 - outline[index][0]),
+                };
+                let coverage = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+                if coverage > 0 {
+                    handle.blend(Position { x, y }, self.color, coverage);
+                }
+                index += 1;
+            }
+        }
+    }
+}
+
+/// How much a rounded rect from `min` to `max` covers `point` once it's gone through a Gaussian blur
+/// with a standard deviation of `sigma`, from `0.0` to `1.0`.
+///
+/// Each row of the rect blurs along itself exactly, with the error function.
+/// So do the straight rows between the corners across each other, since they're all the same;
+/// the rows along the corners get narrower, so those are added up a few at a time.
+/// <https://madebyevan.com/shaders/fast-rounded-rectangle-shadows/>
+fn blurred_coverage(min: Point, max: Point, radii: CornerRadii, point: Point, sigma: f32) -> f32 {
+    // How much of a line from `start` to `end` blurs onto `at`
+    let scale = 1.0 / (sigma * std::f32::consts::SQRT_2);
+    let along =
+        |start: f32, end: f32, at: f32| (erf((end - at) * scale) - erf((start - at) * scale)) / 2.0;
+    // The row of the rect at `y`, blurred onto `point`
+    let row = |y: f32| {
+        let inset = |radius: f32, distance: f32| match distance < radius {
+            true => {
+                radius
+                    - (radius * radius - (radius - distance).powi(2))
+                        .max(0.0)
+                        .sqrt()
+            }
+            false => 0.0,
+        };
+        let (from_top, from_bottom) = (y - min.y, max.y - y);
+        let left = min.x
+            + f32::max(
+                inset(radii.top_left, from_top),
+                inset(radii.bottom_left, from_bottom),
+            );
+        let right = max.x
+            - f32::max(
+                inset(radii.top_right, from_top),
+                inset(radii.bottom_right, from_bottom),
+            );
+        along(left, right, point.x)
+    };
+
+    let top = min.y + radii.top_left.max(radii.top_right);
+    let bottom = (max.y - radii.bottom_left.max(radii.bottom_right)).max(top);
+    let mut total = match bottom > top {
+        true => row((top + bottom) / 2.0) * along(top, bottom, point.y),
+        false => 0.0,
+    };
+
+    // Rows further away than this don't add up to anything visible
+    const STEPS: usize = 8;
+    let reach = sigma * 4.0;
+    for (start, end) in [(min.y, top.min(max.y)), (bottom, max.y)] {
+        let (start, end) = (start.max(point.y - reach), end.min(point.y + reach));
+        if end <= start {
+            continue;
+        }
+        let step = (end - start) / STEPS as f32;
+        total += (0..STEPS)
+            .map(|index| {
+                let from = start + step * index as f32;
+                row(from + step / 2.0) * along(from, from + step, point.y)
+            })
+            .sum::<f32>();
+    }
+    total.clamp(0.0, 1.0)
+}
+
+/// The error function, to within `1.5e-7`; Abramowitz and Stegun, formula 7.1.26
+fn erf(x: f32) -> f32 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let polynomial = t
+        * (0.254_829_6
+            + t * (-0.284_496_7 + t * (1.421_413_7 + t * (-1.453_152_1 + t * 1.061_405_4))));
+    (1.0 - polynomial * (-x * x).exp()).copysign(x)
+}
+
+/// How far a Gaussian blur with a standard deviation of `sigma` reaches, in whole pixels;
+/// past three standard deviations there's nothing left to see
+pub(crate) fn blur_extent(sigma: f32) -> i32 {
+    (sigma * 3.0).ceil().max(0.0) as i32
+}
+
+/// Grows `rect` by `amount` pixels on every side, only as far as it's inside of `within`.
+/// However far that is, it doesn't overflow
+pub(crate) fn expand_within(rect: Rect, amount: i32, within: Rect) -> Rect {
+    let amount = amount as i64;
+    let left = (rect.position.x as i64 - amount).max(within.position.x as i64);
+    let top = (rect.position.y as i64 - amount).max(within.position.y as i64);
+    let right = (rect.right() as i64 + amount).min(within.right() as i64);
+    let bottom = (rect.bottom() as i64 + amount).min(within.bottom() as i64);
+    Rect::new(
+        Position {
+            x: left as i32,
+            y: top as i32,
+        },
+        Size {
+            width: (right - left).max(0) as Pixel,
+            height: (bottom - top).max(0) as Pixel,
+        },
+    )
+}
+
+/// How much of each pixel in `region` the polygons cover, from `0.0` to `1.0`, row by row
+fn coverage(polygons: &[Polyline], region: Rect) -> Vec<[f32; 1]> {
+    let mut coverage = vec![[0.0]; region.size.width * region.size.height];
+    rasterizer::fill(polygons, FillRule::NonZero, region, |x, y, alpha| {
+        let x = (x - region.position.x) as usize;
+        let y = (y - region.position.y) as usize;
+        coverage[y * region.size.width + x] = [alpha as f32 / 255.0];
+    });
+    coverage
+}
+
+/// Approximates a Gaussian blur with a standard deviation of `sigma` pixels,
+/// with three box blurs in each direction; each one costs the same however wide it is.
+/// `pixels` is `size.width * size.height` values with `N` channels each, row by row.
+/// Pixels past the edges are the same as the edges, so the blur doesn't fade out there
+pub(crate) fn blur<const N: usize>(pixels: &mut [[f32; N]], size: Size, sigma: f32) {
+    let (width, height) = (size.width, size.height);
+    if sigma <= 0.0 || width == 0 || height == 0 {
+        return;
+    }
+
+    let mut line = Vec::new();
+    for radius in box_radii(sigma) {
+        for y in 0..height {
+            line.clear();
+            line.extend_from_slice(&pixels[y * width..(y + 1) * width]);
+            box_blur(&line, radius, |x, value| pixels[y * width + x] = value);
+        }
+        for x in 0..width {
+            line.clear();
+            line.extend((0..height).map(|y| pixels[y * width + x]));
+            box_blur(&line, radius, |y, value| pixels[y * width + x] = value);
+        }
+    }
+}
+
+/// Radii of three box blurs that add up to about a Gaussian blur with a standard deviation of `sigma`.
+/// <https://www.peterkovesi.com/papers/FastGaussianSmoothing.pdf>
+fn box_radii(sigma: f32) -> [usize; 3] {
+    const PASSES: f32 = 3.0;
+    let ideal = (12.0 * sigma * sigma / PASSES + 1.0).sqrt();
+    let mut lower = ideal.floor() as i32;
+    if lower % 2 == 0 {
+        lower -= 1;
+    }
+    let lower = lower.max(1);
+    let upper = lower + 2;
+
+    // How many of the passes use the narrower box, to get closest to the right variance
+    let lower_f = lower as f32;
+    let narrower =
+        (12.0 * sigma * sigma - PASSES * lower_f * lower_f - 4.0 * PASSES * lower_f - 3.0 * PASSES)
+            / (-4.0 * lower_f - 4.0);
+    let narrower = narrower.round() as usize;
+    [0, 1, 2].map(|pass| {
+        let width = if pass < narrower { lower } else { upper };
+        (width as usize - 1) / 2
+    })
+}
+
+/// Averages each value in `line` with the `radius` values on either side of it, with a running sum
+fn box_blur<const N: usize>(
+    line: &[[f32; N]],
+    radius: usize,
+    mut write: impl FnMut(usize, [f32; N]),
+) {
+    let last = line.len() - 1;
+    let at = |index: isize| line[index.clamp(0, last as isize) as usize];
+    let radius = radius as isize;
+    let scale = 1.0 / (2 * radius + 1) as f32;
+
+    // Past the ends, the first and last values repeat, however far the box reaches
+    let mut sum = [0.0; N];
+    let inside = radius.min(last as isize) as usize;
+    let repeats = [
+        (line[0], radius),
+        (line[last], (radius - last as isize).max(0)),
+    ];
+    for (value, count) in repeats {
+        (0..N).for_each(|channel| sum[channel] += value[channel] * count as f32);
+    }
+    for value in &line[..=inside] {
+        (0..N).for_each(|channel| sum[channel] += value[channel]);
+    }
+    for index in 0..line.len() {
+        write(index, sum.map(|channel| channel * scale));
+        let (entering, leaving) = (at(index as isize + radius + 1), at(index as isize - radius));
+        (0..N).for_each(|channel| sum[channel] += entering[channel] - leaving[channel]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: Pixel, height: Pixel) -> Rect {
+        Rect::new(Position { x, y }, Size { width, height })
+    }
+
+    /// The pixels the shadow's shape touches, and how much it covers each of them
+    fn shape(shadow: &BoxShadow) -> (Rect, Vec<[f32; 1]>) {
+        let polygons = shadow.shape().flatten(rasterizer::TOLERANCE);
+        let bounds = rasterizer::pixel_bounds(&polygons).unwrap();
+        (bounds, coverage(&polygons, bounds))
+    }
+
+    #[test]
+    fn spread_grows_the_shape() {
+        let size = Size {
+            width: 20,
+            height: 10,
+        };
+        let shadow = BoxShadow::new(size, Color::new(0, 0, 0, 255)).with_offset(3.0, 4.0);
+        assert_eq!(shape(&shadow).0, rect(3, 4, 20, 10));
+        assert_eq!(
+            shape(&shadow.clone().with_spread(2.0)).0,
+            rect(1, 2, 24, 14)
+        );
+        assert_eq!(
+            shape(&shadow.clone().with_spread(-3.0)).0,
+            rect(6, 7, 14, 4)
+        );
+
+        // Inset shadows shrink instead, since they're cast from the box's edges inwards
+        let inset = shadow.clone().inset();
+        assert_eq!(shape(&inset.clone().with_spread(2.0)).0, rect(5, 6, 16, 6));
+        assert_eq!(shape(&inset.with_spread(-2.0)).0, rect(1, 2, 24, 14));
+
+        // Shrunk past nothing, there's nothing left
+        let gone = shadow
+            .with_spread(-6.0)
+            .shape()
+            .flatten(rasterizer::TOLERANCE);
+        assert!(rasterizer::pixel_bounds(&gone).is_none_or(|bounds| bounds.is_empty()));
+    }
+
+    #[test]
+    fn spread_rounds_corners() {
+        let size = Size {
+            width: 20,
+            height: 10,
+        };
+        let radii = CornerRadii {
+            top_left: 4.0,
+            ..CornerRadii::default()
+        };
+        let shadow = BoxShadow::new(size, Color::new(0, 0, 0, 255)).with_radii(radii);
+        let corners = |shadow: &BoxShadow| {
+            let (bounds, coverage) = shape(shadow);
+            let width = bounds.size.width;
+            (coverage[0][0], coverage[width - 1][0])
+        };
+
+        // A corner with a radius of 6 barely touches its corner pixel, a square one fills it
+        let (top_left, top_right) = corners(&shadow.clone().with_spread(2.0));
+        assert!(top_left < 0.05);
+        assert_eq!(top_right, 1.0);
+
+        // Shrinking rounded corners past their radius leaves them square
+        let (top_left, top_right) = corners(&shadow.clone().inset().with_spread(4.0));
+        assert_eq!((top_left, top_right), (1.0, 1.0));
+        let (top_left, _) = corners(&shadow.with_spread(-4.0));
+        assert_eq!(top_left, 1.0);
+    }
+
+    #[test]
+    fn blur_keeps_the_total() {
+        let size = Size {
+            width: 41,
+            height: 41,
+        };
+        let mut pixels = vec![[0.0]; size.width * size.height];
+        pixels[20 * size.width + 20] = [1.0];
+        blur(&mut pixels, size, 3.0);
+
+        let total: f32 = pixels.iter().map(|pixel| pixel[0]).sum();
+        assert!((total - 1.0).abs() < 1e-4);
+        // Symmetric, and highest in the middle
+        let center = pixels[20 * size.width + 20][0];
+        assert!(pixels.iter().all(|pixel| pixel[0] <= center));
+        assert!((pixels[20 * size.width + 17][0] - pixels[20 * size.width + 23][0]).abs() < 1e-6);
+        assert!((pixels[17 * size.width + 20][0] - pixels[20 * size.width + 17][0]).abs() < 1e-6);
+    }
+
+    #[test]
+    fn box_radii_match_the_variance() {
+        [1.0, 2.5, 8.0, 20.0].into_iter().for_each(|sigma: f32| {
+            // A box blur of width `w` has a variance of `(w^2 - 1) / 12`, and variances add up
+            let variance: f32 = box_radii(sigma)
+                .into_iter()
+                .map(|radius| {
+                    let width = (2 * radius + 1) as f32;
+                    (width * width - 1.0) / 12.0
+                })
+                .sum();
+            assert!((variance.sqrt() - sigma).abs() < sigma * 0.1 + 0.5);
+        });
+    }
+
+    #[test]
+    fn blurred_coverage_of_a_rect() {
+        let (min, max) = (Point::new(0.0, 0.0), Point::new(100.0, 60.0));
+        let square = |point: Point| blurred_coverage(min, max, CornerRadii::default(), point, 4.0);
+        let close = |actual: f32, expected: f32| (actual - expected).abs() < 1e-3;
+
+        // Half of the blur of an edge is inside, and a quarter of a corner's
+        assert!(close(square(Point::new(50.0, 30.0)), 1.0));
+        assert!(close(square(Point::new(0.0, 30.0)), 0.5));
+        assert!(close(square(Point::new(100.0, 60.0)), 0.25));
+        assert!(close(square(Point::new(-20.0, 30.0)), 0.0));
+        // Symmetric
+        assert!(close(
+            square(Point::new(3.0, 2.0)),
+            square(Point::new(97.0, 58.0))
+        ));
+
+        // Rounding a corner takes some of it away, but not from the edges
+        let rounded = |point: Point| blurred_coverage(min, max, CornerRadii::all(20.0), point, 4.0);
+        assert!(rounded(Point::new(2.0, 2.0)) < square(Point::new(2.0, 2.0)) - 0.1);
+        assert!(close(rounded(Point::new(0.0, 30.0)), 0.5));
+        assert!(close(rounded(Point::new(50.0, 30.0)), 1.0));
+    }
+
+    #[test]
+    fn huge_blurs_stay_cheap() {
+        use crate::graphics::color;
+        use crate::graphics::paint::Paint;
+        use crate::render::layer::Layer;
+        use crate::render::surface::{Canvas, Surface};
+
+        let mut canvas = Canvas::new(32, 32);
+        let mut handle = DrawHandle::new(&mut canvas);
+        let size = Size {
+            width: 16,
+            height: 16,
+        };
+        let shadow = BoxShadow::new(size, Color::new(0, 0, 0, 255)).with_blur(1e6);
+        shadow.draw_at(&mut handle, Position { x: 8, y: 8 });
+        // Spread over millions of pixels, there's nothing left to see
+        assert!(canvas.pixels().iter().all(|&pixel| pixel == 0));
+
+        // An endless blur doesn't overflow, and spreads what's around an inset shadow evenly over the box
+        let inset = BoxShadow::new(size, Color::new(255, 255, 255, 255))
+            .inset()
+            .with_blur(f32::MAX);
+        inset.draw_at(&mut DrawHandle::new(&mut canvas), Position { x: 8, y: 8 });
+        let lit = |x: usize, y: usize| canvas.pixels()[y * 32 + x] != 0;
+        assert!((8..24).all(|y| (8..24).all(|x| lit(x, y))));
+        assert!(!lit(7, 7) && !lit(24, 24));
+
+        // Layer blurs only go as far as the layer and what's below it
+        let mut handle = DrawHandle::new(&mut canvas);
+        handle.push_layer(Layer::new().with_blur(1e6).with_backdrop_blur(1e6));
+        handle.fill_rect(rect(0, 0, 4, 4), &Paint::Solid(color::WHITE));
+        handle.pop_layer();
+
+        let far = expand_within(rect(i32::MAX - 1, 0, 1, 1), i32::MAX, rect(0, 0, 32, 32));
+        assert_eq!(far, rect(0, 0, 32, 32));
+    }
+}
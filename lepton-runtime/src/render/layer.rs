@@ -0,0 +1,516 @@
+use palette::{LinSrgb, Srgb};
+
+use super::effects;
+use crate::graphics::color::{Color, Gamma};
+use crate::graphics::path::Path;
+use crate::layout::{Position, Rect, Size};
+
+/// How the colors of a layer are combined with what's below it, like CSS's `mix-blend-mode`.
+///
+/// As in CSS, the blend formulas see the encoded color values, so multiplying by 50% gray halves them.
+/// Mixing the result with what's below, where the layer is see-through, happens in linear light like all other blending,
+/// which also makes [`BlendMode::Normal`] the same as drawing straight onto what's below
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum BlendMode {
+    /// The layer covers what's below it
+    #[default]
+    Normal,
+    /// Darkens, like stacking transparencies
+    Multiply,
+    /// Lightens, like projecting two images onto the same screen
+    Screen,
+    /// Multiplies the darks and screens the lights of what's below, keeping its contrast
+    Overlay,
+    Darken,
+    Lighten,
+    /// Brightens what's below to reflect the layer
+    ColorDodge,
+    /// Darkens what's below to reflect the layer
+    ColorBurn,
+    /// Like [`BlendMode::Overlay`], but with the layer and what's below it swapped
+    HardLight,
+    /// A softer [`BlendMode::HardLight`]
+    SoftLight,
+    Difference,
+    /// Like [`BlendMode::Difference`], with less contrast
+    Exclusion,
+}
+impl BlendMode {
+    /// One channel of the blended color, from the layer's `source` and the `backdrop` below it,
+    /// both without alpha; the formulas from the W3C compositing spec
+    fn blend(self, source: f32, backdrop: f32) -> f32 {
+        match self {
+            BlendMode::Normal => source,
+            BlendMode::Multiply => source * backdrop,
+            BlendMode::Screen => source + backdrop - source * backdrop,
+            BlendMode::Overlay => BlendMode::HardLight.blend(backdrop, source),
+            BlendMode::Darken => source.min(backdrop),
+            BlendMode::Lighten => source.max(backdrop),
+            BlendMode::ColorDodge => match (backdrop, source) {
+                (backdrop, _) if backdrop <= 0.0 => 0.0,
+                (_, source) if source >= 1.0 => 1.0,
+                (backdrop, source) => (backdrop / (1.0 - source)).min(1.0),
+            },
+            BlendMode::ColorBurn => match (backdrop, source) {
+                (backdrop, _) if backdrop >= 1.0 => 1.0,
+                (_, source) if source <= 0.0 => 0.0,
+                (backdrop, source) => 1.0 - ((1.0 - backdrop) / source).min(1.0),
+            },
+            BlendMode::HardLight => match source <= 0.5 {
+                true => BlendMode::Multiply.blend(2.0 * source, backdrop),
+                false => BlendMode::Screen.blend(2.0 * source - 1.0, backdrop),
+            },
+            BlendMode::SoftLight => {
+                if source <= 0.5 {
+                    backdrop - (1.0 - 2.0 * source) * backdrop * (1.0 - backdrop)
+                } else {
+                    let d = match backdrop <= 0.25 {
+                        true => ((16.0 * backdrop - 12.0) * backdrop + 4.0) * backdrop,
+                        false => backdrop.sqrt(),
+                    };
+                    backdrop + (2.0 * source - 1.0) * (d - backdrop)
+                }
+            }
+            BlendMode::Difference => (source - backdrop).abs(),
+            BlendMode::Exclusion => source + backdrop - 2.0 * source * backdrop,
+        }
+    }
+}
+
+/// A group of drawing that's composited onto what's below it as a whole,
+/// e.g. to fade a dialog in and out; see [`DrawHandle::push_layer`](super::DrawHandle::push_layer)
+#[derive(Clone, Debug)]
+pub struct Layer {
+    /// Multiplies the alpha of everything in the layer, from `0.0` to `1.0`
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
+
+    /// Nothing gets drawn into the layer outside of this, like with
+    /// [`DrawHandle::push_clip`](super::DrawHandle::push_clip)
+    pub clip: Option<Rect>,
+
+    /// Only the inside of this path shows, with antialiased edges, e.g. for rounded corners.
+    /// It's in drawing coordinates, and goes through the transform that's current when the layer is pushed
+    pub mask: Option<Path>,
+
+    /// Blurs what's drawn in the layer, like the CSS `blur()` filter;
+    /// the standard deviation of the Gaussian blur, in drawing coordinates
+    pub blur: f32,
+
+    /// Blurs what's below the layer wherever the layer covers it, like the CSS `backdrop-filter`,
+    /// e.g. for frosted glass; the layer's clip, mask and opacity shape the blurred area
+    pub backdrop_blur: f32,
+}
+impl Layer {
+    /// A layer that's drawn like it isn't there, until it's given some of its options
+    pub fn new() -> Self {
+        Self {
+            opacity: 1.0,
+            blend_mode: BlendMode::default(),
+            clip: None,
+            mask: None,
+            blur: 0.0,
+            backdrop_blur: 0.0,
+        }
+    }
+
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn with_clip(mut self, clip: Rect) -> Self {
+        self.clip = Some(clip);
+        self
+    }
+
+    pub fn with_mask(mut self, mask: Path) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    pub fn with_blur(mut self, blur: f32) -> Self {
+        self.blur = blur.max(0.0);
+        self
+    }
+
+    pub fn with_backdrop_blur(mut self, blur: f32) -> Self {
+        self.backdrop_blur = blur.max(0.0);
+        self
+    }
+}
+impl Default for Layer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A color in linear light with premultiplied alpha, as `[red, green, blue, alpha]`
+pub(crate) type Linear = [f32; 4];
+
+/// Width and height of the tiles that layers keep their pixels in
+const TILE: usize = 32;
+
+/// What's been drawn into a layer, until it's composited onto what's below it
+pub(crate) struct Offscreen {
+    pub(crate) layer: Layer,
+
+    /// The pixels the layer covers on the surface; nothing is drawn into it outside of these
+    pub(crate) bounds: Rect,
+
+    /// `TILE` by `TILE` pixel tiles over `bounds`, row by row, each one row by row too.
+    /// Tiles are only allocated once something is drawn into them, starting out transparent,
+    /// so a layer costs as much as what's drawn into it rather than the whole clip
+    tiles: Vec<Option<Box<[Linear]>>>,
+
+    /// The mask's coverage of each pixel in `bounds`, row by row, if there is one
+    pub(crate) mask: Option<Vec<u8>>,
+
+    /// The layer's blurs, scaled by the transform that was current when it was pushed
+    pub(crate) blur: f32,
+    pub(crate) backdrop_blur: f32,
+
+    /// How many clips were on the clip stack before the layer was pushed,
+    /// to go back to when it's popped, no matter what was pushed in between
+    pub(crate) clip_depth: usize,
+}
+impl Offscreen {
+    pub(crate) fn new(layer: Layer, bounds: Rect, clip_depth: usize) -> Self {
+        let (columns, rows) = Self::tile_counts(bounds.size);
+        Self {
+            blur: layer.blur,
+            backdrop_blur: layer.backdrop_blur,
+            layer,
+            bounds,
+            tiles: vec![None; columns * rows],
+            mask: None,
+            clip_depth,
+        }
+    }
+
+    fn tile_counts(size: Size) -> (usize, usize) {
+        (size.width.div_ceil(TILE), size.height.div_ceil(TILE))
+    }
+
+    /// The tile that `position` is in, and its index in the tile, if it's inside of the layer
+    fn locate(&self, position: Position) -> Option<(usize, usize)> {
+        if !self.bounds.contains(position) {
+            return None;
+        }
+        let x = (position.x - self.bounds.position.x) as usize;
+        let y = (position.y - self.bounds.position.y) as usize;
+        let (columns, _) = Self::tile_counts(self.bounds.size);
+        Some((y / TILE * columns + x / TILE, y % TILE * TILE + x % TILE))
+    }
+
+    /// The pixel at `position`, if it's inside of the layer, allocating its tile if it's the first one drawn there
+    pub(crate) fn pixel_mut(&mut self, position: Position) -> Option<&mut Linear> {
+        let (tile, index) = self.locate(position)?;
+        let tile = self.tiles[tile]
+            .get_or_insert_with(|| vec![[0.0; 4]; TILE * TILE].into_boxed_slice());
+        Some(&mut tile[index])
+    }
+
+    /// Source-over blending of `color` onto the layer, with its alpha scaled by `coverage`,
+    /// like [`color::blend`](crate::graphics::color::blend)
+    pub(crate) fn blend(&mut self, position: Position, color: Color, coverage: u8, gamma: Gamma) {
+        let alpha = coverage as u32 * color.alpha as u32;
+        if alpha == 0 {
+            return;
+        }
+        let Some(pixel) = self.pixel_mut(position) else {
+            return;
+        };
+        let alpha = alpha as f32 / 0xFE01 as f32;
+        let source = gamma.to_linear(color.color);
+        *pixel = [
+            source.red * alpha + pixel[0] * (1.0 - alpha),
+            source.green * alpha + pixel[1] * (1.0 - alpha),
+            source.blue * alpha + pixel[2] * (1.0 - alpha),
+            alpha + pixel[3] * (1.0 - alpha),
+        ];
+    }
+
+    /// The pixel at `position`, transparent where nothing's been drawn yet, if it's inside of the layer
+    pub(crate) fn pixel(&self, position: Position) -> Option<Linear> {
+        let (tile, index) = self.locate(position)?;
+        Some(self.tiles[tile].as_ref().map_or([0.0; 4], |tile| tile[index]))
+    }
+
+    /// How much of the pixel at `position` the layer shows through its opacity and mask, from `0.0` to `1.0`,
+    /// if it's inside of the layer
+    pub(crate) fn weight(&self, position: Position) -> Option<f32> {
+        if !self.bounds.contains(position) {
+            return None;
+        }
+        let mask = self.mask.as_ref().map_or(1.0, |mask| {
+            let x = (position.x - self.bounds.position.x) as usize;
+            let y = (position.y - self.bounds.position.y) as usize;
+            mask[y * self.bounds.size.width + x] as f32 / 255.0
+        });
+        Some(self.layer.opacity * mask)
+    }
+
+    /// The smallest rectangle around the tiles that have been drawn into
+    pub(crate) fn drawn_area(&self) -> Option<Rect> {
+        let (columns, _) = Self::tile_counts(self.bounds.size);
+        let drawn = self.tiles.iter().enumerate().filter(|(_, tile)| tile.is_some());
+        let (first, last) = drawn.fold(None, |area, (index, _)| {
+            let (column, row) = (index % columns, index / columns);
+            Some(match area {
+                None => ((column, row), (column, row)),
+                Some(((left, top), (right, bottom))) => (
+                    (usize::min(left, column), usize::min(top, row)),
+                    (usize::max(right, column), usize::max(bottom, row)),
+                ),
+            })
+        })?;
+        let (left, top) = (first.0 * TILE, first.1 * TILE);
+        let area = Rect::new(
+            Position {
+                x: self.bounds.position.x + left as i32,
+                y: self.bounds.position.y + top as i32,
+            },
+            Size {
+                width: (last.0 + 1) * TILE - left,
+                height: (last.1 + 1) * TILE - top,
+            },
+        );
+        Some(area.intersection(self.bounds))
+    }
+
+    /// Blurs what's been drawn into the layer by the layer's blur, which spreads it out around where it was drawn
+    pub(crate) fn apply_blur(&mut self) {
+        let Some(drawn) = self.drawn_area().filter(|_| self.blur > 0.0) else {
+            return;
+        };
+        let region = effects::expand_within(drawn, effects::blur_extent(self.blur), self.bounds);
+        let positions = || {
+            (region.position.y..region.bottom())
+                .flat_map(|y| (region.position.x..region.right()).map(move |x| Position { x, y }))
+        };
+        let mut pixels: Vec<_> = positions()
+            .map(|position| self.pixel(position).unwrap_or_default())
+            .collect();
+        effects::blur(&mut pixels, region.size, self.blur);
+        positions().zip(pixels).for_each(|(position, blurred)| {
+            if let Some(pixel) = self.pixel_mut(position) {
+                *pixel = blurred;
+            }
+        });
+    }
+
+    /// Every pixel in the tiles that have been drawn into, with the layer's opacity and mask applied,
+    /// ready to be composited
+    pub(crate) fn drawn(&self) -> impl Iterator<Item = (Position, Linear)> + '_ {
+        let (columns, _) = Self::tile_counts(self.bounds.size);
+        let Size { width, height } = self.bounds.size;
+        self.tiles
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, tile)| {
+                Some((
+                    index % columns * TILE,
+                    index / columns * TILE,
+                    tile.as_ref()?,
+                ))
+            })
+            .flat_map(move |(left, top, tile)| {
+                let rows = top..(top + TILE).min(height);
+                rows.flat_map(move |y| {
+                    (left..(left + TILE).min(width)).map(move |x| {
+                        let position = Position {
+                            x: self.bounds.position.x + x as i32,
+                            y: self.bounds.position.y + y as i32,
+                        };
+                        let weight = self.weight(position).unwrap_or_default();
+                        let pixel = tile[(y - top) * TILE + x - left].map(|channel| channel * weight);
+                        (position, pixel)
+                    })
+                })
+            })
+    }
+}
+
+/// Composites `source` onto `backdrop` with `mode`, both premultiplied.
+/// `gamma` encodes the colors for the blend formulas
+pub(crate) fn composite(source: Linear, backdrop: Linear, mode: BlendMode, gamma: Gamma) -> Linear {
+    let (source_alpha, backdrop_alpha) = (source[3], backdrop[3]);
+    if source_alpha <= 0.0 {
+        return backdrop;
+    }
+    let unpremultiply = |color: Linear| {
+        let alpha = color[3];
+        let channel = |channel: f32| match alpha > 0.0 {
+            true => (channel / alpha).clamp(0.0, 1.0),
+            false => 0.0,
+        };
+        LinSrgb::new(channel(color[0]), channel(color[1]), channel(color[2]))
+    };
+
+    let blended = match mode {
+        BlendMode::Normal => unpremultiply(source),
+        mode => {
+            let source = gamma.encode(unpremultiply(source));
+            let backdrop = gamma.encode(unpremultiply(backdrop));
+            gamma.decode(Srgb::new(
+                mode.blend(source.red, backdrop.red),
+                mode.blend(source.green, backdrop.green),
+                mode.blend(source.blue, backdrop.blue),
+            ))
+        }
+    };
+    let blended = [blended.red, blended.green, blended.blue];
+
+    let mut result = [0.0; 4];
+    for channel in 0..3 {
+        result[channel] = source[channel] * (1.0 - backdrop_alpha)
+            + backdrop[channel] * (1.0 - source_alpha)
+            + source_alpha * backdrop_alpha * blended[channel];
+    }
+    result[3] = source_alpha + backdrop_alpha * (1.0 - source_alpha);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-5,
+            "{actual} isn't {expected}"
+        );
+    }
+
+    #[test]
+    fn blend_modes() {
+        use BlendMode::*;
+        // (mode, source, backdrop, result), worked out from the W3C compositing spec
+        let cases = [
+            (Normal, 0.3, 0.8, 0.3),
+            (Multiply, 0.5, 0.4, 0.2),
+            (Screen, 0.5, 0.4, 0.7),
+            // Multiplies a dark backdrop, screens a light one
+            (Overlay, 0.5, 0.4, 0.4),
+            (Overlay, 0.2, 0.8, 0.68),
+            (Darken, 0.3, 0.8, 0.3),
+            (Lighten, 0.3, 0.8, 0.8),
+            (ColorDodge, 0.5, 0.0, 0.0),
+            (ColorDodge, 1.0, 0.2, 1.0),
+            (ColorDodge, 0.5, 0.3, 0.6),
+            (ColorDodge, 0.5, 0.6, 1.0),
+            (ColorBurn, 0.0, 0.5, 0.0),
+            (ColorBurn, 0.2, 1.0, 1.0),
+            (ColorBurn, 0.8, 0.6, 0.5),
+            (ColorBurn, 0.2, 0.6, 0.0),
+            (HardLight, 0.3, 0.5, 0.3),
+            (HardLight, 0.8, 0.5, 0.8),
+            (SoftLight, 0.25, 0.5, 0.375),
+            (SoftLight, 0.75, 0.16, 0.279168),
+            (SoftLight, 0.75, 0.64, 0.72),
+            (Difference, 0.3, 0.8, 0.5),
+            (Exclusion, 0.3, 0.8, 0.62),
+        ];
+        cases
+            .into_iter()
+            .for_each(|(mode, source, backdrop, expected)| {
+                let actual = mode.blend(source, backdrop);
+                assert!(
+                    (actual - expected).abs() < 1e-5,
+                    "{mode:?} of {source} over {backdrop} is {actual}, not {expected}"
+                );
+            });
+    }
+
+    #[test]
+    fn composites_premultiplied() {
+        // So that the blend formulas see the same values that are composited
+        const LINEAR: Gamma = Gamma::Power(1.0);
+        let opaque_blue = [0.0, 0.0, 1.0, 1.0];
+        let half_red = [0.5, 0.0, 0.0, 0.5];
+        assert_eq!(
+            composite([0.0; 4], opaque_blue, BlendMode::Multiply, LINEAR),
+            opaque_blue
+        );
+        assert_eq!(
+            composite(half_red, opaque_blue, BlendMode::Normal, LINEAR),
+            [0.5, 0.0, 0.5, 1.0]
+        );
+
+        // Where there's nothing below, the blend mode doesn't matter
+        assert_eq!(
+            composite(half_red, [0.0; 4], BlendMode::Multiply, LINEAR),
+            half_red
+        );
+        assert_eq!(
+            composite(half_red, [0.0; 4], BlendMode::Difference, LINEAR),
+            half_red
+        );
+
+        // cs * (1 - ab) + cb * (1 - as) + as * ab * B(Cb, Cs), with a 50% gray half covering 40% gray
+        let result = composite(
+            [0.25, 0.25, 0.25, 0.5],
+            [0.4, 0.4, 0.4, 1.0],
+            BlendMode::Multiply,
+            LINEAR,
+        );
+        assert_close(result[0], 0.4 * 0.5 + 0.5 * 0.2);
+        assert_close(result[3], 1.0);
+
+        // Alpha is always source-over
+        let result = composite(half_red, [0.0, 0.0, 0.5, 0.5], BlendMode::Screen, LINEAR);
+        assert_close(result[3], 0.75);
+        assert_close(result[0], 0.5 * 0.5 + 0.25 * 1.0);
+        assert_close(result[2], 0.5 * 0.5 + 0.25 * 1.0);
+    }
+
+    #[test]
+    fn blends_encoded_values() {
+        // 50% gray, multiplied by itself, is 25% gray like in CSS, rather than 25% of the light
+        let gray = Gamma::Srgb.decode(Srgb::new(0.5, 0.5, 0.5)).red;
+        let [red, ..] = composite(
+            [gray, gray, gray, 1.0],
+            [gray, gray, gray, 1.0],
+            BlendMode::Multiply,
+            Gamma::Srgb,
+        );
+        let encoded = Gamma::Srgb.encode(LinSrgb::new(red, red, red)).red;
+        assert_close(encoded, 0.25);
+
+        // Normal compositing stays in linear light, so it matches drawing without a layer
+        let half = [gray / 2.0, gray / 2.0, gray / 2.0, 0.5];
+        let [red, ..] = composite(half, [0.0, 0.0, 0.0, 1.0], BlendMode::Normal, Gamma::Srgb);
+        assert_close(red, gray / 2.0);
+    }
+
+    #[test]
+    fn allocates_only_what_is_drawn() {
+        let bounds = Rect::new(
+            Position { x: -500, y: -500 },
+            Size {
+                width: 1000,
+                height: 1000,
+            },
+        );
+        let mut offscreen = Offscreen::new(Layer::new().with_opacity(0.5), bounds, 0);
+        assert_eq!(offscreen.drawn().count(), 0);
+
+        let white = Color::new(255, 255, 255, 255);
+        offscreen.blend(Position { x: 3, y: 4 }, white, 255, Gamma::Srgb);
+        offscreen.blend(Position { x: 4, y: 4 }, white, 255, Gamma::Srgb);
+        // Just past the right edge
+        offscreen.blend(Position { x: 500, y: 0 }, white, 255, Gamma::Srgb);
+        assert_eq!(offscreen.tiles.iter().flatten().count(), 1);
+        assert_eq!(offscreen.drawn().count(), TILE * TILE);
+        assert!(offscreen.drawn().all(|(position, pixel)| {
+            let drawn = position.y == 4 && (3..5).contains(&position.x);
+            pixel == [drawn as u8 as f32 * 0.5; 4]
+        }));
+    }
+}
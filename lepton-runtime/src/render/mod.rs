@@ -2,25 +2,391 @@
 /// This module also handles intrinsic sizing, which takes care of a lot of layout stuff as well.
 pub mod text;
 
-use softbuffer::Buffer;
+/// Memory-budgeted caches for things that are expensive to rasterize, like glyphs
+pub mod cache;
 
-use crate::layout::Position;
+/// Things to draw to: window frame buffers, and in-memory canvases that can be saved as PNG
+pub mod surface;
 
-/// Simple structure that encapsulates the frame buffer and relevant metadata.
+/// Filled and stroked vector shapes, like rectangles, circles and arbitrary paths
+pub mod shape;
+
+/// Box shadows, and the Gaussian blur behind them and behind blurred layers
+pub mod effects;
+
+/// Offscreen layers, composited onto what's below them with an opacity, a blend mode and a mask
+pub mod layer;
+
+#[cfg(test)]
+mod snapshot;
+
+/// Turns rich text into positioned glyphs, with kerning, ligatures and complex script support
+mod shaping;
+
+/// Antialiased scanline filling of polygons, and turning strokes into polygons
+pub(crate) mod rasterizer;
+
+use palette::LinSrgb;
+
+use crate::graphics::color::{self, Color, Gamma};
+use crate::graphics::paint::Paint;
+use crate::graphics::path::{FillRule, Path, Point, Stroke};
+use crate::graphics::transform::Transform;
+use crate::layout::{Position, Rect, Size};
+use layer::{Layer, Linear, Offscreen};
+use surface::Surface;
+
+/// Simple structure that encapsulates the surface being drawn to and relevant metadata.
 /// Render methods are implemented to take this structure, to keep them separate from the event loop.
-pub struct DrawHandle<'a, 'b> {
-    pub buffer: &'a mut Buffer<'b>,
-    pub width: usize,
+pub struct DrawHandle<'a> {
+    pub surface: &'a mut dyn Surface,
+
+    /// How colors are blended with what's already in the buffer
+    pub gamma: Gamma,
+
+    /// Nothing gets drawn outside of the last of these;
+    /// each one is already intersected with the ones before it
+    clips: Vec<Rect>,
+
+    /// Maps what's drawn onto the surface, along with its inverse, which is `None` when everything
+    /// gets squashed flat; each one is already combined with the ones before it
+    transforms: Vec<(Transform, Option<Transform>)>,
+
+    /// Layers that are being drawn into; everything goes into the last one,
+    /// instead of straight onto the surface
+    layers: Vec<Offscreen>,
 }
-impl<'a, 'b> DrawHandle<'a, 'b> {
+impl<'a> DrawHandle<'a> {
+    pub fn new(surface: &'a mut dyn Surface) -> Self {
+        Self {
+            surface,
+            gamma: Gamma::default(),
+            clips: Vec::new(),
+            transforms: Vec::new(),
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn with_gamma(mut self, gamma: Gamma) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    /// The whole surface
+    pub fn bounds(&self) -> Rect {
+        Rect::new(
+            Position { x: 0, y: 0 },
+            Size {
+                width: self.surface.width(),
+                height: self.surface.height(),
+            },
+        )
+    }
+
+    /// The area that can currently be drawn to
+    pub fn clip(&self) -> Rect {
+        self.clips.last().copied().unwrap_or_else(|| self.bounds())
+    }
+
+    /// Restricts drawing to `rect`, within the current clip, until the matching [`Self::pop_clip`].
+    ///
+    /// `rect` goes through the current transform like everything else,
+    /// but clips stay axis-aligned, so a rotated or skewed rect clips to the pixels around it
+    pub fn push_clip(&mut self, rect: Rect) {
+        let clip = self.clip().intersection(self.surface_rect(rect));
+        self.clips.push(clip);
+    }
+
+    /// Undoes the last [`Self::push_clip`], returning the clip it set
+    pub fn pop_clip(&mut self) -> Option<Rect> {
+        self.clips.pop()
+    }
+
+    /// Maps drawing coordinates onto the surface
+    pub fn transform(&self) -> Transform {
+        self.transforms
+            .last()
+            .map_or(Transform::IDENTITY, |(transform, _)| *transform)
+    }
+
+    /// Applies `transform` to everything drawn, before the current transform,
+    /// until the matching [`Self::pop_transform`].
+    /// Pushing a translation to a child's position lets it draw as if it were at the origin
+    pub fn push_transform(&mut self, transform: Transform) {
+        let transform = transform.then(self.transform());
+        self.transforms.push((transform, transform.inverse()));
+    }
+
+    /// Undoes the last [`Self::push_transform`], returning the transform it set
+    pub fn pop_transform(&mut self) -> Option<Transform> {
+        self.transforms.pop().map(|(transform, _)| transform)
+    }
+
+    /// Draws everything into an offscreen layer until the matching [`Self::pop_layer`],
+    /// which composites it onto what's below it as a whole, with the layer's options.
+    /// Layers can be nested, and only cover the current clip, so clipping them keeps them cheap.
+    ///
+    /// Layers that are never popped don't show up at all
+    pub fn push_layer(&mut self, layer: Layer) {
+        let clip_depth = self.clips.len();
+        if let Some(clip) = layer.clip {
+            self.push_clip(clip);
+        }
+        let mut bounds = self.clip();
+
+        // Nothing outside of the mask shows, so the layer only has to cover the mask
+        let mask = layer.mask.as_ref().map(|mask| {
+            let polygons = mask
+                .transformed(self.transform())
+                .flatten(rasterizer::TOLERANCE);
+            bounds = match rasterizer::pixel_bounds(&polygons) {
+                Some(mask) => bounds.intersection(mask),
+                None => Rect::new(
+                    bounds.position,
+                    Size {
+                        width: 0,
+                        height: 0,
+                    },
+                ),
+            };
+
+            let mut coverage = vec![0; bounds.size.width * bounds.size.height];
+            rasterizer::fill(&polygons, FillRule::NonZero, bounds, |x, y, alpha| {
+                let (x, y) = (
+                    (x - bounds.position.x) as usize,
+                    (y - bounds.position.y) as usize,
+                );
+                coverage[y * bounds.size.width + x] = alpha;
+            });
+            coverage
+        });
+
+        let mut offscreen = Offscreen::new(layer, bounds, clip_depth);
+        offscreen.mask = mask;
+        let scale = self.transform().scale_factor();
+        offscreen.blur *= scale;
+        offscreen.backdrop_blur *= scale;
+        self.layers.push(offscreen);
+    }
+
+    /// Composites the last layer from [`Self::push_layer`] onto what's below it,
+    /// whether that's another layer or the surface, returning the layer's options.
+    /// The clip stack goes back to how it was when the layer was pushed
+    pub fn pop_layer(&mut self) -> Option<Layer> {
+        let mut offscreen = self.layers.pop()?;
+        self.clips.truncate(offscreen.clip_depth);
+        offscreen.apply_blur();
+        if offscreen.backdrop_blur > 0.0 {
+            self.blur_backdrop(&offscreen);
+        }
+
+        let (mode, gamma) = (offscreen.layer.blend_mode, self.gamma);
+        for (position, source) in offscreen.drawn() {
+            // Keeps what's below exactly as it was, rather than round-tripping it through linear light
+            if source[3] <= 0.0 {
+                continue;
+            }
+            if let Some(backdrop) = self.backdrop(position) {
+                self.set_backdrop(position, layer::composite(source, backdrop, mode, gamma));
+            }
+        }
+        Some(offscreen.layer)
+    }
+
+    /// Blurs what's below `offscreen` where it shows, before it's composited
+    fn blur_backdrop(&mut self, offscreen: &Offscreen) {
+        let bounds = offscreen.bounds;
+        // Pixels just outside of the layer blur into it too
+        let below = self
+            .layers
+            .last()
+            .map_or(self.bounds(), |parent| parent.bounds);
+        let extent = effects::blur_extent(offscreen.backdrop_blur);
+        let region = effects::expand_within(bounds, extent, below);
+
+        let positions = (region.position.y..region.bottom())
+            .flat_map(|y| (region.position.x..region.right()).map(move |x| Position { x, y }));
+        let mut blurred: Vec<_> = positions
+            .map(|position| self.backdrop(position).unwrap_or_default())
+            .collect();
+        effects::blur(&mut blurred, region.size, offscreen.backdrop_blur);
+
+        for y in bounds.position.y..bounds.bottom() {
+            for x in bounds.position.x..bounds.right() {
+                let position = Position { x, y };
+                let Some(weight) = offscreen.weight(position) else {
+                    continue;
+                };
+                let Some(backdrop) = self.backdrop(position).filter(|_| weight > 0.0) else {
+                    continue;
+                };
+                let (x, y) = (x - region.position.x, y - region.position.y);
+                let blurred = blurred[y as usize * region.size.width + x as usize];
+                let mut mixed = backdrop;
+                (0..4).for_each(|channel| {
+                    mixed[channel] += (blurred[channel] - backdrop[channel]) * weight;
+                });
+                self.set_backdrop(position, mixed);
+            }
+        }
+    }
+
+    /// The pixel below the current layer, which is either in the layer below it or on the surface,
+    /// in linear light with premultiplied alpha
+    fn backdrop(&self, position: Position) -> Option<Linear> {
+        if let Some(parent) = self.layers.last() {
+            return parent.pixel(position);
+        }
+        if !self.bounds().contains(position) {
+            return None;
+        }
+        let index = position.y as usize * self.surface.width() + position.x as usize;
+        let color = color::from_buffer(self.surface.pixels()[index]);
+        let color = self.gamma.to_linear(color.color);
+        Some([color.red, color.green, color.blue, 1.0])
+    }
+
+    /// Replaces the pixel at `position` that [`Self::backdrop`] returned
+    fn set_backdrop(&mut self, position: Position, pixel: Linear) {
+        if let Some(parent) = self.layers.last_mut() {
+            if let Some(below) = parent.pixel_mut(position) {
+                *below = pixel;
+            }
+            return;
+        }
+        if !self.bounds().contains(position) {
+            return;
+        }
+        let index = position.y as usize * self.surface.width() + position.x as usize;
+        let [red, green, blue, _] = pixel;
+        let color = self.gamma.from_linear(LinSrgb::new(red, green, blue));
+        self.surface.pixels_mut()[index] =
+            color::to_buffer(Color::new(color.red, color.green, color.blue, 255));
+    }
+
+    /// How far the current transform moves things, if that's all it does, by whole pixels.
+    /// Drawing can skip antialiasing and resampling then, since pixels land on pixels
+    pub(crate) fn pixel_offset(&self) -> Option<Position> {
+        let transform = self.transform();
+        let whole = transform.e.fract() == 0.0 && transform.f.fract() == 0.0;
+        (transform.is_translation() && whole).then_some(Position {
+            x: transform.e as i32,
+            y: transform.f as i32,
+        })
+    }
+
+    /// Maps surface coordinates back to drawing coordinates,
+    /// unless the current transform squashes everything flat
+    fn inverse_transform(&self) -> Option<Transform> {
+        self.transforms
+            .last()
+            .map_or(Some(Transform::IDENTITY), |(_, inverse)| *inverse)
+    }
+
+    /// The pixels covered by `rect` once it's gone through the current transform
+    fn surface_rect(&self, rect: Rect) -> Rect {
+        let transform = self.transform();
+        let (left, top) = (rect.position.x as f32, rect.position.y as f32);
+        let (right, bottom) = (rect.right() as f32, rect.bottom() as f32);
+        let corners = [(left, top), (right, top), (right, bottom), (left, bottom)]
+            .map(|(x, y)| transform.apply(Point::new(x, y)));
+        let (mut min, mut max) = (corners[0], corners[0]);
+        for corner in corners {
+            min = Point::new(min.x.min(corner.x), min.y.min(corner.y));
+            max = Point::new(max.x.max(corner.x), max.y.max(corner.y));
+        }
+        let (left, top) = (min.x.floor() as i32, min.y.floor() as i32);
+        let (right, bottom) = (max.x.ceil() as i32, max.y.ceil() as i32);
+        Rect::new(
+            Position { x: left, y: top },
+            Size {
+                width: (right - left).max(0) as Pixel,
+                height: (bottom - top).max(0) as Pixel,
+            },
+        )
+    }
+
+    /// Index into the surface's pixels, if the position is inside of the clip
     #[inline]
-    fn index(&self, position: Position) -> usize {
-        position.y * self.width + position.x
+    fn index(&self, position: Position) -> Option<usize> {
+        self.clip()
+            .contains(position)
+            .then(|| position.y as usize * self.surface.width() + position.x as usize)
     }
 
-    fn set(&mut self, position: Position, value: u32) {
-        let index = self.index(position);
-        self.buffer[index] = value;
+    /// Blends `color` over what's already in the buffer, or in the current layer,
+    /// with its alpha scaled by `coverage`
+    pub(crate) fn blend(&mut self, position: Position, color: Color, coverage: u8) {
+        let Some(index) = self.index(position) else {
+            return;
+        };
+        if let Some(layer) = self.layers.last_mut() {
+            return layer.blend(position, color, coverage, self.gamma);
+        }
+        let Some(pixel) = self.surface.pixels_mut().get_mut(index) else {
+            return;
+        };
+        let background = color::from_buffer(*pixel);
+        let blended = color::blend(background, color, coverage, self.gamma);
+        *pixel = color::to_buffer(blended);
+    }
+
+    /// Blends the color of `paint` at the center of the pixel, like [`Self::blend`].
+    /// `position` is on the surface, but paints are in drawing coordinates, like the shapes they fill
+    fn paint(&mut self, position: Position, paint: &Paint, coverage: u8) {
+        let color = match paint {
+            Paint::Solid(color) => *color,
+            Paint::Gradient(_) => {
+                let Some(inverse) = self.inverse_transform() else {
+                    return;
+                };
+                let center = Point::new(position.x as f32 + 0.5, position.y as f32 + 0.5);
+                paint.color_at(inverse.apply(center))
+            }
+        };
+        self.blend(position, color, coverage);
+    }
+
+    fn fill_rect(&mut self, rect: Rect, paint: &Paint) {
+        if self.pixel_offset().is_none() {
+            let (width, height) = (rect.size.width as f32, rect.size.height as f32);
+            let origin = Point::new(rect.position.x as f32, rect.position.y as f32);
+            return self.fill_path(&Path::rect(origin, width, height), FillRule::NonZero, paint);
+        }
+
+        let rect = self.clip().intersection(self.surface_rect(rect));
+        for y in rect.position.y..rect.bottom() {
+            for x in rect.position.x..rect.right() {
+                self.paint(Position { x, y }, paint, u8::MAX);
+            }
+        }
+    }
+
+    /// Fills the inside of `path` with antialiasing, through the current transform
+    pub fn fill_path(&mut self, path: &Path, rule: FillRule, paint: &Paint) {
+        let path = path.transformed(self.transform());
+        let polygons = path.flatten(rasterizer::TOLERANCE);
+        rasterizer::fill(&polygons, rule, self.clip(), |x, y, coverage| {
+            self.paint(Position { x, y }, paint, coverage)
+        });
+    }
+
+    /// Outlines `path` with antialiasing, through the current transform.
+    /// The stroke is widened by the transform's [`Transform::scale_factor`], but not stretched
+    pub fn stroke_path(&mut self, path: &Path, stroke: &Stroke, paint: &Paint) {
+        let transform = self.transform();
+        let path = path.transformed(transform);
+        let stroke = Stroke {
+            width: stroke.width * transform.scale_factor(),
+            ..*stroke
+        };
+        let polygons = rasterizer::stroke(&path.flatten(rasterizer::TOLERANCE), &stroke);
+        rasterizer::fill(
+            &polygons,
+            FillRule::NonZero,
+            self.clip(),
+            |x, y, coverage| self.paint(Position { x, y }, paint, coverage),
+        );
     }
 }
 
@@ -30,3 +396,117 @@ pub type Pixel = usize;
 pub trait Drawable {
     fn draw_at(&self, handle: &mut DrawHandle, position: Position);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::surface::Canvas;
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: Pixel, height: Pixel) -> Rect {
+        Rect::new(Position { x, y }, Size { width, height })
+    }
+
+    /// Which pixels of the canvas aren't black anymore, row by row
+    fn drawn(canvas: &Canvas) -> Vec<bool> {
+        canvas.pixels().iter().map(|&pixel| pixel != 0).collect()
+    }
+
+    #[test]
+    fn draws_nothing_off_the_surface() {
+        let mut canvas = Canvas::new(4, 3);
+        let mut handle = DrawHandle::new(&mut canvas);
+        [
+            (-1, 0),
+            (0, -1),
+            (-1, -1),
+            (4, 0),
+            (0, 3),
+            (4, 2),
+            (i32::MIN, i32::MAX),
+        ]
+        .into_iter()
+        .for_each(|(x, y)| handle.blend(Position { x, y }, color::WHITE, 255));
+        // Past the right edge doesn't wrap around to the next row
+        assert!(drawn(&canvas).iter().all(|&drawn| !drawn));
+    }
+
+    #[test]
+    fn fills_partly_off_the_surface() {
+        let mut canvas = Canvas::new(4, 3);
+        let mut handle = DrawHandle::new(&mut canvas);
+        handle.fill_rect(rect(-2, -2, 3, 3), &Paint::Solid(color::WHITE));
+        handle.fill_rect(rect(3, 2, 5, 5), &Paint::Solid(color::WHITE));
+        #[rustfmt::skip]
+        let expected = [
+            true, false, false, false,
+            false, false, false, false,
+            false, false, false, true,
+        ];
+        assert_eq!(drawn(&canvas), expected);
+    }
+
+    #[test]
+    fn nested_clips_intersect() {
+        let mut canvas = Canvas::new(4, 3);
+        let mut handle = DrawHandle::new(&mut canvas);
+        handle.push_clip(rect(1, 0, 3, 3));
+        handle.push_clip(rect(-5, 1, 7, 1));
+        assert_eq!(handle.clip(), rect(1, 1, 1, 1));
+
+        // Clips that don't overlap leave nothing to draw to
+        handle.push_clip(rect(3, 0, 1, 3));
+        assert!(handle.clip().is_empty());
+        handle.fill_rect(handle.bounds(), &Paint::Solid(color::WHITE));
+        assert_eq!(handle.pop_clip(), Some(rect(3, 1, 0, 1)));
+
+        handle.fill_rect(handle.bounds(), &Paint::Solid(color::WHITE));
+        assert_eq!(handle.pop_clip(), Some(rect(1, 1, 1, 1)));
+        assert_eq!(handle.clip(), rect(1, 0, 3, 3));
+        assert_eq!(handle.pop_clip(), Some(rect(1, 0, 3, 3)));
+        assert_eq!(handle.clip(), handle.bounds());
+        assert_eq!(handle.pop_clip(), None);
+
+        let mut expected = [false; 12];
+        expected[4 + 1] = true;
+        assert_eq!(drawn(&canvas), expected);
+    }
+
+    #[test]
+    fn layers_restore_the_clip_stack() {
+        let mut canvas = Canvas::new(4, 3);
+        let mut handle = DrawHandle::new(&mut canvas);
+        handle.push_clip(rect(0, 0, 3, 3));
+
+        // Clips left over inside of a layer go with it, along with the layer's own
+        handle.push_layer(Layer::new().with_clip(rect(1, 0, 3, 3)));
+        handle.push_clip(rect(1, 1, 1, 1));
+        handle.push_clip(rect(1, 1, 1, 1));
+        handle.pop_layer();
+        assert_eq!(handle.clip(), rect(0, 0, 3, 3));
+
+        // Even if the layer's own clip was popped, the clip from before it stays
+        handle.push_layer(Layer::new().with_clip(rect(1, 0, 3, 3)));
+        handle.pop_clip();
+        handle.pop_layer();
+        assert_eq!(handle.clip(), rect(0, 0, 3, 3));
+        assert_eq!(handle.pop_clip(), Some(rect(0, 0, 3, 3)));
+        assert_eq!(handle.pop_clip(), None);
+    }
+
+    #[test]
+    fn masked_layers_only_cover_the_mask() {
+        use crate::graphics::path::{Path, Point};
+
+        let mut canvas = Canvas::new(4, 3);
+        let mut handle = DrawHandle::new(&mut canvas);
+        handle.push_layer(Layer::new().with_mask(Path::rect(Point::new(1.0, 1.0), 2.0, 1.0)));
+        assert_eq!(handle.layers[0].bounds, rect(1, 1, 2, 1));
+        handle.fill_rect(handle.bounds(), &Paint::Solid(color::WHITE));
+        handle.pop_layer();
+
+        let mut expected = [false; 12];
+        expected[4 + 1] = true;
+        expected[4 + 2] = true;
+        assert_eq!(drawn(&canvas), expected);
+    }
+}
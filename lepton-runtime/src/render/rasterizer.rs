@@ -0,0 +1,565 @@
+use std::f32::consts::TAU;
+
+use crate::graphics::path::{FillRule, LineCap, LineJoin, Point, Polyline, Stroke};
+use crate::layout::{Position, Rect, Size};
+use crate::render::Pixel;
+
+/// How far flattened curves can be from the real thing, in pixels
+pub(crate) const TOLERANCE: f32 = 0.2;
+
+/// Number of sample rows per pixel row.
+/// Coverage along each sample row is exact, so this only limits antialiasing of near-horizontal edges
+const SUBSAMPLES: usize = 16;
+
+/// A polygon edge, always pointing down
+struct Edge {
+    top: Point,
+    bottom: Point,
+    /// `1` if the edge originally pointed down, `-1` if it pointed up
+    winding: i32,
+}
+impl Edge {
+    fn new(from: Point, to: Point) -> Option<Self> {
+        match from.y.partial_cmp(&to.y)? {
+            std::cmp::Ordering::Less => Some(Self {
+                top: from,
+                bottom: to,
+                winding: 1,
+            }),
+            std::cmp::Ordering::Greater => Some(Self {
+                top: to,
+                bottom: from,
+                winding: -1,
+            }),
+            // Horizontal edges never cross a sample row
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    fn x_at(&self, y: f32) -> f32 {
+        let t = (y - self.top.y) / (self.bottom.y - self.top.y);
+        self.top.x + (self.bottom.x - self.top.x) * t
+    }
+}
+
+/// Fills polygons with antialiasing, calling `plot` with the coverage of every pixel inside of `clip`
+/// that's at least partly covered.
+///
+/// Each pixel row is sampled along several sample rows; along each of those,
+/// the spans inside of the polygons are accumulated with their exact fractional ends.
+/// Open polylines are treated as closed.
+pub(crate) fn fill(
+    polygons: &[Polyline],
+    rule: FillRule,
+    clip: Rect,
+    mut plot: impl FnMut(i32, i32, u8),
+) {
+    let edges = polygons
+        .iter()
+        .flat_map(|polygon| {
+            let points = &polygon.points;
+            let closing = points.last().zip(points.first());
+            points
+                .windows(2)
+                .map(|pair| (pair[0], pair[1]))
+                .chain(closing.map(|(&last, &first)| (last, first)))
+        })
+        .filter_map(|(from, to)| Edge::new(from, to))
+        .filter(|edge| edge.top.x.is_finite() && edge.bottom.x.is_finite())
+        .collect::<Vec<_>>();
+
+    let Some(top) = edges.iter().map(|edge| edge.top.y).reduce(f32::min) else {
+        return;
+    };
+    let bottom = edges.iter().map(|edge| edge.bottom.y).fold(top, f32::max);
+
+    let first_row = i32::max(clip.position.y, top.floor() as i32);
+    let last_row = i32::min(clip.bottom(), bottom.ceil() as i32);
+    let (left, right) = (clip.position.x as f32, clip.right() as f32);
+    let width = clip.size.width;
+
+    let mut coverage = vec![0.0_f32; width + 1];
+    let mut crossings = Vec::<(f32, i32)>::new();
+    let weight = 1.0 / SUBSAMPLES as f32;
+
+    for row in first_row..last_row {
+        let (row_top, row_bottom) = (row as f32, (row + 1) as f32);
+        let row_edges = edges
+            .iter()
+            .filter(|edge| edge.top.y < row_bottom && edge.bottom.y > row_top)
+            .collect::<Vec<_>>();
+        if row_edges.is_empty() {
+            continue;
+        }
+
+        coverage.fill(0.0);
+        for sample in 0..SUBSAMPLES {
+            let y = row_top + (sample as f32 + 0.5) * weight;
+
+            crossings.clear();
+            crossings.extend(
+                row_edges
+                    .iter()
+                    .filter(|edge| edge.top.y <= y && y < edge.bottom.y)
+                    .map(|edge| (edge.x_at(y), edge.winding)),
+            );
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            crossings.windows(2).for_each(|pair| {
+                winding += pair[0].1;
+                let inside = match rule {
+                    FillRule::NonZero => winding != 0,
+                    FillRule::EvenOdd => winding % 2 != 0,
+                };
+                if inside {
+                    let start = pair[0].0.clamp(left, right) - left;
+                    let end = pair[1].0.clamp(left, right) - left;
+                    accumulate(&mut coverage, start, end, weight);
+                }
+            });
+        }
+
+        coverage[..width]
+            .iter()
+            .enumerate()
+            .filter(|(_, &value)| value > 0.0)
+            .for_each(|(x, &value)| {
+                let value = (value.min(1.0) * 255.0).round() as u8;
+                if value > 0 {
+                    plot(clip.position.x + x as i32, row, value);
+                }
+            });
+    }
+}
+
+/// The whole pixels that the polygons touch
+pub(crate) fn pixel_bounds(polygons: &[Polyline]) -> Option<Rect> {
+    let mut points = polygons.iter().flat_map(|polygon| &polygon.points);
+    let first = *points.next()?;
+    let (min, max) = points.fold((first, first), |(min, max), point| {
+        (
+            Point::new(min.x.min(point.x), min.y.min(point.y)),
+            Point::new(max.x.max(point.x), max.y.max(point.y)),
+        )
+    });
+    let (left, top) = (min.x.floor() as i32, min.y.floor() as i32);
+    Some(Rect::new(
+        Position { x: left, y: top },
+        Size {
+            width: (max.x.ceil() as i32 - left).max(0) as Pixel,
+            height: (max.y.ceil() as i32 - top).max(0) as Pixel,
+        },
+    ))
+}
+
+/// Adds a span of a sample row to the coverage of the pixels it covers,
+/// with the pixels at its ends only partly covered
+fn accumulate(coverage: &mut [f32], start: f32, end: f32, weight: f32) {
+    if end <= start {
+        return;
+    }
+    let (first, last) = (start.floor() as usize, end.floor() as usize);
+    if first == last {
+        coverage[first] += (end - start) * weight;
+        return;
+    }
+
+    coverage[first] += (first as f32 + 1.0 - start) * weight;
+    coverage[(first + 1)..last]
+        .iter_mut()
+        .for_each(|value| *value += weight);
+    if let Some(value) = coverage.get_mut(last) {
+        *value += (end - last as f32) * weight;
+    }
+}
+
+/// Turns the outline of polylines into polygons to fill with [`FillRule::NonZero`].
+///
+/// Every segment becomes a quad, offset `width / 2` to both sides of it,
+/// and the outer sides of consecutive quads are connected with the stroke's [`LineJoin`].
+/// Between the pieces of a flattened curve, which barely turn, that's always a bevel.
+/// All of the polygons go around the same way, so that where they overlap, they add up instead of cancelling out.
+pub(crate) fn stroke(polylines: &[Polyline], stroke: &Stroke) -> Vec<Polyline> {
+    let radius = stroke.width / 2.0;
+    if radius <= 0.0 {
+        return Vec::new();
+    }
+
+    let mut result = Vec::new();
+    for polyline in polylines {
+        let mut points = polyline
+            .points
+            .iter()
+            .enumerate()
+            .map(|(index, &point)| (point, polyline.is_corner(index)))
+            .collect::<Vec<_>>();
+        // Repeated points keep the corner of either one
+        points.dedup_by(|(point, corner), (kept, kept_corner)| {
+            *kept_corner |= *corner;
+            point.distance(*kept) < f32::EPSILON
+        });
+        if polyline.closed
+            && points.len() > 2
+            && points.first().map(|first| first.0) == points.last().map(|last| last.0)
+        {
+            points.pop();
+        }
+
+        // A single point only shows up with caps that go past it
+        let [(first, _), ..] = points[..] else {
+            continue;
+        };
+        if points.len() == 1 {
+            match stroke.cap {
+                LineCap::Butt => (),
+                LineCap::Round => result.push(circle(first, radius)),
+                LineCap::Square => result.push(quad(
+                    first - Point::new(radius, 0.0),
+                    first + Point::new(radius, 0.0),
+                    radius,
+                )),
+            }
+            continue;
+        }
+
+        let closed = polyline.closed && points.len() > 2;
+        let mut segments = points
+            .windows(2)
+            .map(|pair| (pair[0].0, pair[1].0))
+            .collect::<Vec<_>>();
+        if closed {
+            segments.push((points[points.len() - 1].0, first));
+        }
+
+        // Joins go where one segment meets the next, which for closed polylines includes the first point
+        let joins = match closed {
+            true => (0..segments.len())
+                .map(|index| (index, (index + 1) % segments.len()))
+                .collect::<Vec<_>>(),
+            false => (1..segments.len())
+                .map(|index| (index - 1, index))
+                .collect::<Vec<_>>(),
+        };
+        result.extend(joins.into_iter().filter_map(|(before, after)| {
+            let (from, corner) = segments[before];
+            let to = segments[after].1;
+            let join = match points[(before + 1) % points.len()].1 {
+                true => stroke.join,
+                false => LineJoin::Bevel,
+            };
+            join_polygon(from, corner, to, radius, join, stroke.miter_limit)
+        }));
+
+        // Square caps are just the end segments reaching further out
+        if !closed && stroke.cap == LineCap::Square {
+            let extend = |from: Point, to: Point| {
+                let direction = to - from;
+                to + direction * (radius / direction.length())
+            };
+            let last = segments.len() - 1;
+            segments[0].0 = extend(segments[0].1, segments[0].0);
+            segments[last].1 = extend(segments[last].0, segments[last].1);
+        }
+
+        result.extend(segments.iter().map(|&(from, to)| quad(from, to, radius)));
+
+        if !closed && stroke.cap == LineCap::Round {
+            let last = points[points.len() - 1].0;
+            result.extend([circle(first, radius), circle(last, radius)]);
+        }
+    }
+
+    result
+}
+
+/// The piece that fills the gap on the outside of a `corner`,
+/// between the quads of the segment coming `from` a point and the one going `to` the next.
+/// `None` if the segments carry on in a straight line
+fn join_polygon(
+    from: Point,
+    corner: Point,
+    to: Point,
+    radius: f32,
+    join: LineJoin,
+    miter_limit: f32,
+) -> Option<Polyline> {
+    let (before, after) = (corner - from, to - corner);
+    let normal =
+        |direction: Point| Point::new(-direction.y, direction.x) * (radius / direction.length());
+    let (normal_before, normal_after) = (normal(before), normal(after));
+
+    // With the y axis pointing down, a positive cross product turns clockwise on screen,
+    // which puts the outside of the corner on the left
+    let turn = before.x * after.y - before.y * after.x;
+    if turn == 0.0 && before.x * after.x + before.y * after.y >= 0.0 {
+        return None;
+    }
+    let side = match turn > 0.0 {
+        true => -1.0,
+        false => 1.0,
+    };
+    let (outer_before, outer_after) = (normal_before * side, normal_after * side);
+
+    let mut points = vec![corner, corner + outer_before];
+    match join {
+        LineJoin::Bevel => (),
+        LineJoin::Miter => {
+            // The miter reaches `1 / cos(angle / 2)` of the radius out,
+            // where `angle` is how far the stroke turns
+            let cosine = (outer_before.x * outer_after.x + outer_before.y * outer_after.y)
+                / (radius * radius);
+            let half_cosine = ((1.0 + cosine) / 2.0).sqrt();
+            if half_cosine > 0.0 && 1.0 / half_cosine <= miter_limit {
+                points.push(corner + (outer_before + outer_after) * (1.0 / (1.0 + cosine)));
+            }
+        }
+        LineJoin::Round => {
+            let start = outer_before.y.atan2(outer_before.x);
+            let mut sweep = outer_after.y.atan2(outer_after.x) - start;
+            // Around the outside of the corner, which goes the same way as the stroke turns
+            if sweep * side > 0.0 {
+                sweep -= TAU * sweep.signum();
+            }
+            let count = (sweep.abs() / arc_step(radius)).ceil().max(1.0) as usize;
+            points.extend((1..count).map(|index| {
+                let angle = start + sweep * index as f32 / count as f32;
+                corner + Point::new(angle.cos(), angle.sin()) * radius
+            }));
+        }
+    }
+    points.push(corner + outer_after);
+
+    if is_counterclockwise(&points) {
+        points.reverse();
+    }
+    Some(Polyline {
+        points,
+        closed: true,
+        corners: Vec::new(),
+    })
+}
+
+/// A rectangle around a line segment, `radius` to both sides of it, going clockwise on screen
+fn quad(from: Point, to: Point, radius: f32) -> Polyline {
+    let direction = to - from;
+    let length = direction.length();
+    // Points to the right of the direction of travel, with the y axis pointing down
+    let normal = Point::new(-direction.y, direction.x) * (radius / length);
+
+    let mut points = vec![from - normal, to - normal, to + normal, from + normal];
+    if is_counterclockwise(&points) {
+        points.reverse();
+    }
+    Polyline {
+        points,
+        closed: true,
+        corners: Vec::new(),
+    }
+}
+
+/// The angle between the points of an arc, so that the flat sides between them stay within the tolerance
+fn arc_step(radius: f32) -> f32 {
+    2.0 * (1.0 - TOLERANCE / radius).clamp(-1.0, 1.0).acos()
+}
+
+/// A circle, going clockwise on screen
+fn circle(center: Point, radius: f32) -> Polyline {
+    let count = (TAU / arc_step(radius)).ceil().clamp(8.0, 256.0) as usize;
+    let points = (0..count)
+        .map(|index| {
+            let angle = index as f32 / count as f32 * TAU;
+            center + Point::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect();
+    Polyline {
+        points,
+        closed: true,
+        corners: Vec::new(),
+    }
+}
+
+/// With the y axis pointing down, from the sign of the shoelace formula
+fn is_counterclockwise(points: &[Point]) -> bool {
+    let area: f32 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum();
+    area < 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::path::Path;
+
+    /// The coverage of every pixel in a `width` by `height` clip at the origin, row by row
+    fn coverage(polygons: &[Polyline], rule: FillRule, width: usize, height: usize) -> Vec<u8> {
+        let clip = Rect::new(Position { x: 0, y: 0 }, Size { width, height });
+        let mut coverage = vec![0; width * height];
+        fill(polygons, rule, clip, |x, y, alpha| {
+            coverage[y as usize * width + x as usize] = alpha
+        });
+        coverage
+    }
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Vec<Polyline> {
+        Path::rect(Point::new(x, y), width, height).flatten(TOLERANCE)
+    }
+
+    #[test]
+    fn fractional_edges() {
+        // Vertical edges are exact
+        let pixels = coverage(&rect(0.5, 0.0, 2.0, 1.0), FillRule::NonZero, 4, 1);
+        assert_eq!(pixels, [128, 255, 128, 0]);
+        // Horizontal ones land on sample rows
+        let pixels = coverage(&rect(0.0, 0.25, 1.0, 1.5), FillRule::NonZero, 1, 2);
+        assert_eq!(pixels, [191, 191]);
+
+        // Half of a pixel, cut diagonally
+        let triangle = Polyline {
+            points: vec![
+                Point::new(0.0, 0.0),
+                Point::new(1.0, 0.0),
+                Point::new(0.0, 1.0),
+            ],
+            closed: true,
+            corners: Vec::new(),
+        };
+        let pixels = coverage(&[triangle], FillRule::NonZero, 1, 1);
+        assert!(pixels[0].abs_diff(128) <= 1);
+    }
+
+    #[test]
+    fn coverage_adds_up_to_the_area() {
+        let circle = Path::circle(Point::new(8.0, 8.0), 6.3).flatten(TOLERANCE);
+        let total: f32 = coverage(&circle, FillRule::NonZero, 16, 16)
+            .into_iter()
+            .map(|alpha| alpha as f32 / 255.0)
+            .sum();
+        // The flattened circle's own area, by the shoelace formula
+        let points = &circle[0].points;
+        let area: f32 = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(a, b)| a.x * b.y - b.x * a.y)
+            .sum::<f32>()
+            .abs()
+            / 2.0;
+        assert!((total - area).abs() < 0.5);
+    }
+
+    #[test]
+    fn fill_rules() {
+        let outer = rect(0.0, 0.0, 3.0, 1.0).remove(0);
+        let inner = rect(1.0, 0.0, 1.0, 1.0).remove(0);
+        let reversed = Polyline {
+            points: inner.points.iter().rev().copied().collect(),
+            closed: true,
+            corners: Vec::new(),
+        };
+
+        // The same way around, the inside is wound twice
+        let same = [outer.clone(), inner];
+        assert_eq!(coverage(&same, FillRule::NonZero, 3, 1), [255, 255, 255]);
+        assert_eq!(coverage(&same, FillRule::EvenOdd, 3, 1), [255, 0, 255]);
+
+        // The other way around, it isn't wound at all
+        let opposite = [outer, reversed];
+        assert_eq!(coverage(&opposite, FillRule::NonZero, 3, 1), [255, 0, 255]);
+        assert_eq!(coverage(&opposite, FillRule::EvenOdd, 3, 1), [255, 0, 255]);
+    }
+
+    #[test]
+    fn stays_inside_the_clip() {
+        let clip = Rect::new(
+            Position { x: 2, y: 1 },
+            Size {
+                width: 2,
+                height: 2,
+            },
+        );
+        fill(
+            &rect(-10.0, -10.0, 30.0, 30.0),
+            FillRule::NonZero,
+            clip,
+            |x, y, alpha| {
+                assert!(clip.contains(Position { x, y }));
+                assert_eq!(alpha, 255);
+            },
+        );
+    }
+
+    #[test]
+    fn pixel_bounds_round_outwards() {
+        assert_eq!(
+            pixel_bounds(&rect(-0.5, 1.2, 2.0, 0.3)),
+            Some(Rect::new(
+                Position { x: -1, y: 1 },
+                Size {
+                    width: 3,
+                    height: 1
+                }
+            ))
+        );
+        assert_eq!(pixel_bounds(&[]), None);
+    }
+
+    #[test]
+    fn butt_strokes_cover_their_length() {
+        let line = Path::line(Point::new(1.0, 2.0), Point::new(9.0, 2.0)).flatten(TOLERANCE);
+        let polygons = stroke(&line, &Stroke::new(2.0));
+        let pixels = coverage(&polygons, FillRule::NonZero, 10, 4);
+        // Rows 1 and 2, from 1 to 9
+        let expected: Vec<u8> = (0..40)
+            .map(|index| {
+                match (
+                    (1..3).contains(&(index / 10)),
+                    (1..9).contains(&(index % 10)),
+                ) {
+                    (true, true) => 255,
+                    _ => 0,
+                }
+            })
+            .collect();
+        assert_eq!(pixels, expected);
+    }
+
+    #[test]
+    fn line_joins() {
+        // A right turn, whose outer corner is at (12, 12)
+        let corner = Path::new()
+            .move_to(Point::new(2.0, 10.0))
+            .line_to(Point::new(10.0, 10.0))
+            .line_to(Point::new(10.0, 2.0))
+            .flatten(TOLERANCE);
+        let outer = |join: LineJoin, miter_limit: f32| {
+            let style = Stroke::new(4.0)
+                .with_join(join)
+                .with_miter_limit(miter_limit);
+            coverage(&stroke(&corner, &style), FillRule::NonZero, 14, 14)[11 * 14 + 11]
+        };
+
+        assert_eq!(outer(LineJoin::Miter, 4.0), 255);
+        assert_eq!(outer(LineJoin::Bevel, 4.0), 0);
+        let round = outer(LineJoin::Round, 4.0);
+        assert!(round > 0 && round < 255);
+        // A square corner's miter is √2 times the half width, past this limit
+        assert_eq!(outer(LineJoin::Miter, 1.2), 0);
+    }
+
+    #[test]
+    fn joins_only_at_corners() {
+        let round = Stroke::new(8.0).with_join(LineJoin::Round);
+        let is_round = |polygon: &Polyline| polygon.points.len() > 4;
+
+        // The points of a flattened curve only get a bevel
+        let circle = Path::circle(Point::new(8.0, 8.0), 6.0).flatten(TOLERANCE);
+        assert!(!stroke(&circle, &round).iter().any(is_round));
+
+        let square = rect(2.0, 2.0, 10.0, 10.0);
+        let joins = stroke(&square, &round).into_iter().filter(is_round);
+        assert_eq!(joins.count(), 4);
+    }
+}
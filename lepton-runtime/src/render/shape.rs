@@ -0,0 +1,86 @@
+use crate::graphics::paint::Paint;
+use crate::graphics::path::{CornerRadii, FillRule, Path, Point, Stroke};
+use crate::graphics::transform::Transform;
+use crate::layout::{Position, Size};
+use crate::render::{DrawHandle, Drawable};
+
+/// A vector shape that can be filled, stroked, or both.
+///
+/// The path and any gradients are relative to the position the shape is drawn at.
+/// Strokes are centered on the path, like in SVG, so they reach half their width outside of it.
+#[derive(Clone, Debug)]
+pub struct Shape {
+    path: Path,
+    fill: Option<Paint>,
+    fill_rule: FillRule,
+    stroke: Option<(Stroke, Paint)>,
+}
+impl Shape {
+    /// A shape that doesn't draw anything until it's given a fill or a stroke
+    pub fn path(path: Path) -> Self {
+        Self {
+            path,
+            fill: None,
+            fill_rule: FillRule::default(),
+            stroke: None,
+        }
+    }
+
+    pub fn rect(size: Size) -> Self {
+        let (width, height) = size_f32(size);
+        Self::path(Path::rect(Point::default(), width, height))
+    }
+
+    pub fn rounded_rect(size: Size, radii: CornerRadii) -> Self {
+        let (width, height) = size_f32(size);
+        Self::path(Path::rounded_rect(Point::default(), width, height, radii))
+    }
+
+    /// An ellipse that fits in `size`
+    pub fn ellipse(size: Size) -> Self {
+        let (width, height) = size_f32(size);
+        let center = Point::new(width / 2.0, height / 2.0);
+        Self::path(Path::ellipse(center, width / 2.0, height / 2.0))
+    }
+
+    /// A circle with its top left at the position it's drawn at
+    pub fn circle(radius: f32) -> Self {
+        Self::path(Path::circle(Point::new(radius, radius), radius))
+    }
+
+    /// A straight line, which needs a stroke to show up
+    pub fn line(from: Point, to: Point) -> Self {
+        Self::path(Path::line(from, to))
+    }
+
+    pub fn fill(mut self, paint: impl Into<Paint>) -> Self {
+        self.fill = Some(paint.into());
+        self
+    }
+
+    pub fn fill_rule(mut self, rule: FillRule) -> Self {
+        self.fill_rule = rule;
+        self
+    }
+
+    pub fn stroke(mut self, stroke: Stroke, paint: impl Into<Paint>) -> Self {
+        self.stroke = Some((stroke, paint.into()));
+        self
+    }
+}
+impl Drawable for Shape {
+    fn draw_at(&self, handle: &mut DrawHandle, position: Position) {
+        let offset = Transform::translate(position.x as f32, position.y as f32);
+        let path = self.path.transformed(offset);
+        if let Some(paint) = &self.fill {
+            handle.fill_path(&path, self.fill_rule, &paint.transformed(offset));
+        }
+        if let Some((stroke, paint)) = &self.stroke {
+            handle.stroke_path(&path, stroke, &paint.transformed(offset));
+        }
+    }
+}
+
+fn size_f32(size: Size) -> (f32, f32) {
+    (size.width as f32, size.height as f32)
+}
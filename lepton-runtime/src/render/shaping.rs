@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use rustybuzz::UnicodeBuffer;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::Pixel;
+use crate::graphics::text::{Attributes, RichChar, RichString};
+use crate::graphics::typeface::{is_default_ignorable, Variant};
+
+/// A glyph as positioned by the shaper, in pixels.
+#[derive(Clone)]
+pub(crate) struct ShapedGlyph {
+    /// Glyph ID in the font; `fontdue` uses the same indices
+    pub id: u16,
+    /// Horizontal position of the glyph's origin, relative to the start of its cluster.
+    /// This includes the shaper's offset (e.g. for combining marks), not just the advances before it
+    pub x: f32,
+    /// Vertical offset from the baseline; positive is **up**, like in the font
+    pub y: f32,
+}
+
+/// The glyphs for one or more grapheme clusters that have to stay together,
+/// because the shaper combined them (e.g. a ligature).
+pub(crate) struct ShapedCluster {
+    /// Byte range in the shaped text
+    pub range: Range<usize>,
+    pub glyphs: Vec<ShapedGlyph>,
+    pub advance: f32,
+}
+
+/// A [`ShapedCluster`] from a [`RichString`],
+/// along with the rich text information of the first grapheme cluster in it.
+pub(crate) struct ShapedText<'a> {
+    pub text: &'a str,
+    pub shaped: ShapedCluster,
+    pub font: &'static Variant,
+    pub base_font: &'static Variant,
+    pub attributes: Attributes,
+}
+
+/// Shapes a whole [`RichString`].
+///
+/// The text is split into runs of consecutive clusters that use the same font and size,
+/// each run is shaped on its own, and the resulting clusters come out in visual order.
+///
+/// There's no bidirectional reordering yet; right-to-left runs come out right-to-left,
+/// but the runs themselves stay in logical order.
+pub(crate) fn shape(string: &RichString) -> Vec<ShapedText<'_>> {
+    let contents = string.contents();
+    let mut result = Vec::new();
+
+    let mut run: Vec<RichChar> = Vec::new();
+    let mut flush = |run: &mut Vec<RichChar>| {
+        let (Some(first), Some(last)) = (run.first(), run.last()) else {
+            return;
+        };
+        let start = first.index;
+        let end = last.index + last.cluster.len();
+
+        // Clusters always start on a grapheme boundary, so they always line up with one of our `RichChar`s
+        for shaped in shape_run(&contents[start..end], first.font, first.attributes.size) {
+            let range = (start + shaped.range.start)..(start + shaped.range.end);
+            let Ok(found) = run.binary_search_by_key(&range.start, |rich_char| rich_char.index)
+            else {
+                log::warn!("shaped cluster doesn't start on a grapheme cluster");
+                continue;
+            };
+            let rich_char = &run[found];
+
+            result.push(ShapedText {
+                text: &contents[range.clone()],
+                shaped: ShapedCluster { range, ..shaped },
+                font: rich_char.font,
+                base_font: rich_char.base_font,
+                attributes: rich_char.attributes,
+            });
+        }
+        run.clear();
+    };
+
+    for rich_char in string.rich_iter() {
+        let same_run = run.last().map(|last| {
+            std::ptr::eq(last.font, rich_char.font)
+                && last.attributes.size == rich_char.attributes.size
+        });
+        if same_run == Some(false) {
+            flush(&mut run);
+        }
+        run.push(rich_char);
+    }
+    flush(&mut run);
+
+    result
+}
+
+/// Shapes text in a single font and size, grouping the glyphs into clusters.
+/// The clusters never split a grapheme cluster, and come out in visual order.
+pub(crate) fn shape_run(text: &str, font: &Variant, size: Pixel) -> Vec<ShapedCluster> {
+    let Some(face) = rustybuzz::Face::from_slice(&font.bytes, font.index) else {
+        log::warn!("couldn't load font for shaping; falling back to unshaped text");
+        return unshaped_run(text, font, size);
+    };
+
+    let scale = size as f32 / face.units_per_em() as f32;
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    let output = rustybuzz::shape(&face, &[], buffer);
+
+    // Cluster start, to the glyphs in it along with their pen positions and advances.
+    // The glyph buffer is in visual order, so we keep track of that for each cluster too
+    let mut clusters = BTreeMap::<usize, Vec<(usize, f32, ShapedGlyph, f32)>>::new();
+    let mut pen = 0.0;
+    output
+        .glyph_infos()
+        .iter()
+        .zip(output.glyph_positions())
+        .enumerate()
+        .for_each(|(order, (info, position))| {
+            let advance = position.x_advance as f32 * scale;
+            clusters.entry(info.cluster as usize).or_default().push((
+                order,
+                pen,
+                ShapedGlyph {
+                    id: info.glyph_id as u16,
+                    x: position.x_offset as f32 * scale,
+                    y: position.y_offset as f32 * scale,
+                },
+                advance,
+            ));
+            pen += advance;
+        });
+
+    // The shaper has its own idea of what a grapheme is;
+    // anything that would split one of ours gets merged into the cluster before it
+    let boundaries = text
+        .grapheme_indices(true)
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    let mut merged = Vec::<(usize, Vec<_>)>::new();
+    for (start, glyphs) in clusters {
+        match merged.last_mut() {
+            Some((_, previous)) if boundaries.binary_search(&start).is_err() => {
+                previous.extend(glyphs)
+            }
+            _ => merged.push((start, glyphs)),
+        }
+    }
+
+    let ends = merged
+        .iter()
+        .skip(1)
+        .map(|(start, _)| *start)
+        .chain(std::iter::once(text.len()))
+        .collect::<Vec<_>>();
+
+    let mut result = merged
+        .into_iter()
+        .zip(ends)
+        .map(|((start, mut glyphs), end)| {
+            glyphs.sort_by_key(|(order, ..)| *order);
+            let origin = glyphs
+                .iter()
+                .map(|(_, pen, ..)| *pen)
+                .fold(f32::INFINITY, f32::min);
+
+            let order = glyphs.first().map(|(order, ..)| *order).unwrap_or(0);
+            let advance = glyphs.iter().map(|(.., advance)| advance).sum();
+            let glyphs = glyphs
+                .into_iter()
+                .map(|(_, pen, glyph, _)| ShapedGlyph {
+                    x: pen - origin + glyph.x,
+                    ..glyph
+                })
+                .collect();
+
+            (
+                order,
+                ShapedCluster {
+                    range: start..end,
+                    glyphs,
+                    advance,
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+
+    result.sort_by_key(|(order, _)| *order);
+    result.into_iter().map(|(_, cluster)| cluster).collect()
+}
+
+/// One glyph per character, positioned purely by their advances.
+/// Only used for fonts that `rustybuzz` can't load.
+fn unshaped_run(text: &str, font: &Variant, size: Pixel) -> Vec<ShapedCluster> {
+    text.grapheme_indices(true)
+        .map(|(start, cluster)| {
+            let mut advance = 0.0;
+            let glyphs = cluster
+                .chars()
+                .filter(|&character| !is_default_ignorable(character))
+                .map(|character| {
+                    let id = font.data.lookup_glyph_index(character);
+                    let glyph = ShapedGlyph {
+                        id,
+                        x: advance,
+                        y: 0.0,
+                    };
+                    advance += font.data.metrics_indexed(id, size as f32).advance_width;
+                    glyph
+                })
+                .collect();
+
+            ShapedCluster {
+                range: start..(start + cluster.len()),
+                glyphs,
+                advance,
+            }
+        })
+        .collect()
+}
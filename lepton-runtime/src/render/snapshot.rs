@@ -0,0 +1,883 @@
+//! Golden image tests for the renderer.
+//!
+//! Each test draws into a [`Canvas`] and compares it against a reference PNG in `tests/snapshots`.
+//! When a snapshot doesn't match, the actual image and a diff image are written next to the reference,
+//! with the mismatched pixels in red over a faded copy of the reference.
+//!
+//! To update the references after an intentional change, run the tests with `LEPTON_BLESS=1`.
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use super::effects::BoxShadow;
+use super::layer::{BlendMode, Layer};
+use super::shape::Shape;
+use super::surface::{load_png, save_png, Canvas, Surface};
+use super::text::{with_glyph_cache, TextBody};
+use super::{DrawHandle, Drawable};
+use crate::graphics::color::{self, Color};
+use crate::graphics::paint::{ColorStop, Gradient, Interpolation, Spread};
+use crate::graphics::path::{CornerRadii, FillRule, LineCap, Path, Point, Stroke};
+use crate::graphics::text::{
+    Attribute, Decoration, Direction, LineHeight, RichString, TextOptions, Wrapping,
+};
+use crate::graphics::transform::Transform;
+use crate::graphics::typeface::{FontStyle, FontWeight, Typeface};
+use crate::image::sampling::Filter;
+use crate::image::svg::SvgView;
+use crate::image::{Fit, ImageSource, ImageView};
+use crate::layout::{Position, Rect, Size, SizeConstraint};
+
+/// Largest difference in any channel for two pixels to still count as the same;
+/// this absorbs floating point differences between platforms
+const TOLERANCE: u8 = 2;
+
+const BACKGROUND: Color = Color::new(24, 28, 36, 255);
+
+fn typeface() -> &'static Typeface {
+    static MEM: OnceLock<Typeface> = OnceLock::new();
+    MEM.get_or_init(|| Typeface::try_default().unwrap())
+}
+
+fn snapshot_path(name: &str, suffix: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}{suffix}.png"))
+}
+
+fn is_blessing() -> bool {
+    std::env::var_os("LEPTON_BLESS").is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Compares `actual` to the reference image `name`, or replaces the reference in bless mode
+fn assert_snapshot(name: &str, actual: &Canvas) {
+    let reference_path = snapshot_path(name, "");
+    let actual_path = snapshot_path(name, ".actual");
+    let diff_path = snapshot_path(name, ".diff");
+
+    if is_blessing() {
+        save_png(actual, &reference_path).unwrap();
+        let _ = std::fs::remove_file(actual_path);
+        let _ = std::fs::remove_file(diff_path);
+        return;
+    }
+
+    let reference = load_png(&reference_path).unwrap_or_else(|error| {
+        panic!(
+            "couldn't load snapshot {}: {error}; run with LEPTON_BLESS=1 to create it",
+            reference_path.display()
+        )
+    });
+    // There's no diff to make between images of different sizes,
+    // but the actual image still helps to see what changed
+    let (reference_size, actual_size) = (
+        (reference.width(), reference.height()),
+        (actual.width(), actual.height()),
+    );
+    if reference_size != actual_size {
+        save_png(actual, &actual_path).unwrap();
+        let _ = std::fs::remove_file(diff_path);
+        panic!(
+            "snapshot {name} changed size from {reference_size:?} to {actual_size:?}; see {}",
+            actual_path.display()
+        );
+    }
+
+    let mut diff = Canvas::new(actual.width(), actual.height());
+    let mut mismatched = 0;
+    actual
+        .pixels()
+        .iter()
+        .zip(reference.pixels())
+        .zip(diff.pixels_mut())
+        .for_each(|((&actual, &reference), diff)| {
+            let (a, r) = (color::from_buffer(actual), color::from_buffer(reference));
+            let matches = [(a.red, r.red), (a.green, r.green), (a.blue, r.blue)]
+                .into_iter()
+                .all(|(a, r)| a.abs_diff(r) <= TOLERANCE);
+
+            *diff = match matches {
+                true => {
+                    let faded = |value: u8| value / 4;
+                    color::to_buffer(Color::new(faded(r.red), faded(r.green), faded(r.blue), 255))
+                }
+                false => {
+                    mismatched += 1;
+                    color::to_buffer(Color::new(255, 0, 0, 255))
+                }
+            };
+        });
+
+    if mismatched > 0 {
+        save_png(actual, &actual_path).unwrap();
+        save_png(&diff, &diff_path).unwrap();
+        panic!(
+            "snapshot {name} has {mismatched} mismatched pixels; see {} and {}",
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+
+    let _ = std::fs::remove_file(actual_path);
+    let _ = std::fs::remove_file(diff_path);
+}
+
+/// Draws `drawable` at a small margin on a blank canvas
+fn render(width: usize, height: usize, drawable: &dyn Drawable) -> Canvas {
+    let mut canvas = Canvas::new(width, height);
+    canvas.fill(BACKGROUND);
+    let mut handle = DrawHandle::new(&mut canvas);
+    drawable.draw_at(&mut handle, Position { x: 8, y: 8 });
+    canvas
+}
+
+const PARAGRAPH: &str = concat!(
+    "Climbing branches up to brick red rooftops, Lilly keeps her secrets to herself. ",
+    "Longing for the low end, hearing nothing much worth hearing, until she heard something else - ",
+    "locked and waiting for the key..."
+);
+
+#[test]
+fn label() {
+    let text = RichString::new("Sphinx of black quartz, judge my vow".into(), typeface())
+        .with(0..6, Attribute::Weight(FontWeight::Bold.into()))
+        .with(10..22, Attribute::Style(FontStyle::Italic))
+        .with(10..22, Attribute::Color(Color::new(255, 200, 80, 255)))
+        .with(33..36, Attribute::Decoration(Decoration::UNDERLINE));
+    let body = TextBody::new_label(text, Direction::Horizontal);
+    assert_snapshot("label", &render(560, 56, &body));
+}
+
+#[test]
+fn wrapped_paragraph() {
+    let text = RichString::new(PARAGRAPH.into(), typeface()).with_default(Attribute::Size(18));
+    let body = TextBody::layout(
+        text,
+        TextOptions::default(),
+        SizeConstraint::intrinsic_width(300),
+    );
+    assert_snapshot("wrapped_paragraph", &render(316, 180, &body));
+}
+
+#[test]
+fn character_wrapping() {
+    let text = RichString::new(PARAGRAPH.into(), typeface()).with_default(Attribute::Size(18));
+    let options = TextOptions {
+        wrapping: Wrapping::Character,
+        ..Default::default()
+    };
+    let body = TextBody::layout(text, options, SizeConstraint::intrinsic_width(300));
+    assert_snapshot("character_wrapping", &render(316, 180, &body));
+}
+
+#[test]
+fn hard_breaks_and_paragraphs() {
+    let text = "First paragraph,\u{2028}with a line separator\n\nThird paragraph\r\nFourth";
+    let text = RichString::new(text.into(), typeface()).with_default(Attribute::Size(18));
+    let options = TextOptions {
+        paragraph_spacing: 10,
+        line_height: LineHeight::Multiplier(1.4),
+        ..Default::default()
+    };
+    let body = TextBody::layout(text, options, SizeConstraint::intrinsic_width(300));
+    assert_snapshot("hard_breaks_and_paragraphs", &render(316, 200, &body));
+}
+
+#[test]
+fn truncated_label() {
+    let text = RichString::new(PARAGRAPH.into(), typeface()).with_default(Attribute::Size(18));
+    let options = TextOptions {
+        wrapping: Wrapping::SingleLineTruncate,
+        ..Default::default()
+    };
+    let body = TextBody::layout(text, options, SizeConstraint::intrinsic_width(240));
+    assert_snapshot("truncated_label", &render(256, 40, &body));
+}
+
+#[test]
+fn truncated_paragraph() {
+    let text = RichString::new(PARAGRAPH.into(), typeface()).with_default(Attribute::Size(18));
+    let max_size = SizeConstraint {
+        width: Some(300),
+        height: Some(70),
+    };
+    let body = TextBody::layout(text, TextOptions::default(), max_size);
+    assert_snapshot("truncated_paragraph", &render(316, 90, &body));
+}
+
+/// Glyphs that reach past their origin or advance, which have to be placed by their bearings.
+/// Their placement is checked to the pixel in `render::text`; this covers how they're drawn
+#[test]
+fn overhanging_glyphs() {
+    let text = RichString::new("jjj fjord ƒ /// jiffy".into(), typeface())
+        .with(17..22, Attribute::Style(FontStyle::Italic))
+        .with_default(Attribute::Size(40));
+    let body = TextBody::new_label(text, Direction::Horizontal);
+    assert_snapshot("overhanging_glyphs", &render(400, 64, &body));
+}
+
+#[test]
+fn clipped_and_off_screen() {
+    let text = RichString::new("Partly off-screen".into(), typeface());
+    let body = TextBody::new_label(text, Direction::Horizontal);
+
+    let mut canvas = Canvas::new(200, 80);
+    canvas.fill(BACKGROUND);
+    let mut handle = DrawHandle::new(&mut canvas);
+    body.draw_at(&mut handle, Position { x: -40, y: -10 });
+
+    handle.push_clip(Rect::new(
+        Position { x: 20, y: 40 },
+        Size {
+            width: 100,
+            height: 20,
+        },
+    ));
+    body.draw_at(&mut handle, Position { x: 10, y: 36 });
+    handle.pop_clip();
+
+    assert_snapshot("clipped_and_off_screen", &canvas);
+}
+
+#[test]
+fn glyph_cache_hits_on_redraw() {
+    let text = || RichString::new("Redrawn every frame".into(), typeface());
+
+    with_glyph_cache(|cache| {
+        cache.clear();
+        cache.reset_stats();
+    });
+    let first = render(300, 60, &TextBody::new_label(text(), Direction::Horizontal));
+    let misses = with_glyph_cache(|cache| cache.stats().misses);
+    assert!(misses > 0);
+
+    with_glyph_cache(|cache| cache.reset_stats());
+    let second = render(300, 60, &TextBody::new_label(text(), Direction::Horizontal));
+    let stats = with_glyph_cache(|cache| cache.stats());
+    assert_eq!(stats.misses, 0);
+    assert!(stats.hits > 0);
+
+    assert_eq!(first, second);
+}
+
+#[test]
+fn shapes() {
+    let size = |width, height| Size { width, height };
+    let accent = Color::new(255, 200, 80, 255);
+    let translucent = Color::new(90, 160, 255, 160);
+
+    let mut canvas = Canvas::new(360, 200);
+    canvas.fill(BACKGROUND);
+    let mut handle = DrawHandle::new(&mut canvas);
+
+    Shape::rect(size(60, 40))
+        .fill(color::WHITE)
+        .draw_at(&mut handle, Position { x: 10, y: 10 });
+    Shape::rounded_rect(
+        size(80, 50),
+        CornerRadii {
+            top_left: 20.0,
+            top_right: 4.0,
+            bottom_right: 30.0,
+            bottom_left: 0.0,
+        },
+    )
+    .fill(translucent)
+    .stroke(Stroke::new(3.0), accent)
+    .draw_at(&mut handle, Position { x: 90, y: 10 });
+    Shape::circle(25.0)
+        .fill(accent)
+        .draw_at(&mut handle, Position { x: 190, y: 10 });
+    Shape::ellipse(size(90, 40))
+        .stroke(Stroke::new(1.5), color::WHITE)
+        .draw_at(&mut handle, Position { x: 255, y: 15 });
+
+    [LineCap::Butt, LineCap::Round, LineCap::Square]
+        .into_iter()
+        .enumerate()
+        .for_each(|(index, cap)| {
+            Shape::line(Point::new(0.0, 0.0), Point::new(80.0, 20.0))
+                .stroke(Stroke::new(8.0).with_cap(cap), accent)
+                .draw_at(
+                    &mut handle,
+                    Position {
+                        x: 20,
+                        y: 80 + 35 * index as i32,
+                    },
+                );
+        });
+
+    // A star crosses over itself, leaving a hole in the middle only with the even-odd rule
+    let star = (0..5).fold(Path::new(), |path, index| {
+        let angle = (index * 2) as f32 * std::f32::consts::TAU / 5.0 - std::f32::consts::FRAC_PI_2;
+        let point = Point::new(40.0 + 38.0 * angle.cos(), 40.0 + 38.0 * angle.sin());
+        match index {
+            0 => path.move_to(point),
+            _ => path.line_to(point),
+        }
+    });
+    let star = star.close();
+    Shape::path(star.clone())
+        .fill(translucent)
+        .draw_at(&mut handle, Position { x: 130, y: 80 });
+    Shape::path(star)
+        .fill(translucent)
+        .fill_rule(FillRule::EvenOdd)
+        .stroke(Stroke::new(1.0), color::WHITE)
+        .draw_at(&mut handle, Position { x: 220, y: 80 });
+
+    // Curves, clipped to the bottom right corner
+    handle.push_clip(Rect::new(Position { x: 300, y: 130 }, size(60, 70)));
+    let curve = Path::new()
+        .move_to(Point::new(0.0, 60.0))
+        .quad_to(Point::new(30.0, -20.0), Point::new(60.0, 60.0))
+        .cubic_to(
+            Point::new(80.0, 0.0),
+            Point::new(100.0, 120.0),
+            Point::new(120.0, 30.0),
+        );
+    Shape::path(curve)
+        .stroke(Stroke::new(4.0).with_cap(LineCap::Round), color::WHITE)
+        .draw_at(&mut handle, Position { x: 270, y: 120 });
+    handle.pop_clip();
+
+    assert_snapshot("shapes", &canvas);
+}
+
+#[test]
+fn gradients() {
+    let size = |width, height| Size { width, height };
+    let stops = || {
+        [
+            ColorStop::new(0.0, Color::new(255, 40, 80, 255)),
+            ColorStop::new(0.5, Color::new(40, 220, 120, 255)),
+            ColorStop::new(1.0, Color::new(60, 80, 255, 255)),
+        ]
+    };
+
+    let mut canvas = Canvas::new(360, 240);
+    canvas.fill(BACKGROUND);
+    let mut handle = DrawHandle::new(&mut canvas);
+
+    // The same stops mixed in each color space
+    [
+        Interpolation::Srgb,
+        Interpolation::Linear,
+        Interpolation::Oklab,
+    ]
+    .into_iter()
+    .enumerate()
+    .for_each(|(index, interpolation)| {
+        let gradient = Gradient::linear(Point::new(0.0, 0.0), Point::new(200.0, 0.0), stops())
+            .with_interpolation(interpolation);
+        Shape::rect(size(200, 20)).fill(gradient).draw_at(
+            &mut handle,
+            Position {
+                x: 10,
+                y: 10 + 25 * index as i32,
+            },
+        );
+    });
+
+    // Fading out to transparent, over the background
+    let fade = Gradient::linear(
+        Point::new(0.0, 0.0),
+        Point::new(0.0, 70.0),
+        [
+            ColorStop::new(0.0, color::WHITE),
+            ColorStop::new(1.0, Color::new(255, 255, 255, 0)),
+        ],
+    );
+    Shape::rounded_rect(size(120, 70), CornerRadii::all(12.0))
+        .fill(fade)
+        .draw_at(&mut handle, Position { x: 225, y: 10 });
+
+    let spreads = [Spread::Pad, Spread::Repeat, Spread::Reflect];
+    spreads.into_iter().enumerate().for_each(|(index, spread)| {
+        let gradient = Gradient::radial(Point::new(30.0, 30.0), 12.0, stops()).with_spread(spread);
+        Shape::circle(30.0).fill(gradient).draw_at(
+            &mut handle,
+            Position {
+                x: 10 + 70 * index as i32,
+                y: 95,
+            },
+        );
+    });
+
+    let conic = Gradient::conic(
+        Point::new(30.0, 30.0),
+        0.0,
+        [
+            ColorStop::new(0.0, Color::new(255, 40, 80, 255)),
+            ColorStop::new(0.5, Color::new(60, 80, 255, 255)),
+            ColorStop::new(1.0, Color::new(255, 40, 80, 255)),
+        ],
+    )
+    .with_interpolation(Interpolation::Oklab);
+    Shape::circle(30.0)
+        .fill(conic)
+        .stroke(Stroke::new(2.0), color::WHITE)
+        .draw_at(&mut handle, Position { x: 220, y: 95 });
+
+    let text = RichString::new("Gradient text".into(), typeface())
+        .with(9..13, Attribute::Decoration(Decoration::UNDERLINE))
+        .with_default(Attribute::Size(36));
+    let gradient = Gradient::linear(Point::new(0.0, 0.0), Point::new(220.0, 0.0), stops());
+    TextBody::new_label(text, Direction::Horizontal)
+        .with_paint(gradient)
+        .draw_at(&mut handle, Position { x: 10, y: 170 });
+
+    assert_snapshot("gradients", &canvas);
+}
+
+/// A small image with hard edges, transparent corners and a soft shadow, encoded as a PNG
+fn test_image() -> ImageSource {
+    let image = ::image::RgbaImage::from_fn(8, 6, |x, y| {
+        let corner = (x == 0 || x == 7) && (y == 0 || y == 5);
+        match (corner, (x + y) % 2 == 0) {
+            (true, _) => ::image::Rgba([0, 0, 0, 0]),
+            (false, true) => ::image::Rgba([255, 200, 80, 255]),
+            (false, false) => ::image::Rgba([60, 80, 255, 160]),
+        }
+    });
+    let mut bytes = Vec::new();
+    ::image::DynamicImage::from(image)
+        .write_to(
+            &mut std::io::Cursor::new(&mut bytes),
+            ::image::ImageFormat::Png,
+        )
+        .unwrap();
+    ImageSource::bytes(bytes)
+}
+
+#[test]
+fn images() {
+    let source = test_image();
+    let mut canvas = Canvas::new(360, 170);
+    canvas.fill(BACKGROUND);
+    let mut handle = DrawHandle::new(&mut canvas);
+
+    [Filter::Nearest, Filter::Bilinear, Filter::Bicubic]
+        .into_iter()
+        .enumerate()
+        .for_each(|(index, filter)| {
+            ImageView::new(
+                source.clone(),
+                Size {
+                    width: 96,
+                    height: 72,
+                },
+            )
+            .with_filter(filter)
+            .draw_at(
+                &mut handle,
+                Position {
+                    x: 10 + 115 * index as i32,
+                    y: 10,
+                },
+            );
+        });
+
+    // Each fit in the same box, with the box outlined
+    [Fit::Contain, Fit::Cover, Fit::Fill]
+        .into_iter()
+        .enumerate()
+        .for_each(|(index, fit)| {
+            let size = Size {
+                width: 100,
+                height: 50,
+            };
+            let position = Position {
+                x: 10 + 115 * index as i32,
+                y: 100,
+            };
+            Shape::rect(size)
+                .stroke(Stroke::new(1.0), color::WHITE)
+                .draw_at(&mut handle, position);
+            ImageView::new(source.clone(), size)
+                .with_fit(fit)
+                .with_filter(Filter::Nearest)
+                .draw_at(&mut handle, position);
+        });
+
+    assert_snapshot("images", &canvas);
+}
+
+/// A monochrome icon, drawn with `currentColor` like most icon sets
+const HEART: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none"
+    stroke="currentColor" stroke-width="2" stroke-linecap="round">
+  <path d="M20.8 4.6a5.5 5.5 0 0 0-7.8 0L12 5.7l-1-1.1a5.5 5.5 0 0 0-7.8 7.8l1 1L12 21l7.8-7.6 1-1a5.5 5.5 0 0 0 0-7.8z"/>
+  <line x1="12" y1="9" x2="12" y2="15"/>
+  <line x1="9" y1="12" x2="15" y2="12"/>
+</svg>"#;
+
+/// Gradients through `href`, in both unit systems, with nested transforms and opacity
+const BADGE: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"
+    width="64" height="64">
+  <defs>
+    <linearGradient id="stops">
+      <stop offset="0" stop-color="#ff2850"/>
+      <stop offset="100%" style="stop-color: #3c50ff"/>
+    </linearGradient>
+    <linearGradient id="diagonal" xlink:href="#stops" x2="1" y2="1"/>
+    <radialGradient id="glow" href="#stops" gradientUnits="userSpaceOnUse" cx="0" cy="0" r="8"
+        spreadMethod="reflect"/>
+  </defs>
+  <rect x="4" y="4" width="56" height="56" rx="12" fill="url(#diagonal)"/>
+  <g transform="translate(32 32) rotate(45)" opacity="0.8">
+    <rect x="-14" y="-14" width="28" height="28" fill="url(#glow)" stroke="white" stroke-width="2"/>
+  </g>
+  <circle cx="32" cy="32" r="4" style="fill: rgb(255, 255, 255); fill-opacity: 0.5"/>
+  <polygon points="4,60 14,60 4,50" fill="#ffc850" fill-rule="evenodd"/>
+</svg>"##;
+
+#[test]
+fn svg_icons() {
+    let mut canvas = Canvas::new(330, 110);
+    canvas.fill(BACKGROUND);
+    let mut handle = DrawHandle::new(&mut canvas);
+
+    let heart = ImageSource::bytes(HEART.as_bytes());
+    let sizes = [16, 24, 48];
+    sizes.into_iter().fold(10, |x, size| {
+        SvgView::new(
+            heart.clone(),
+            Size {
+                width: size,
+                height: size,
+            },
+        )
+        .with_color(Color::new(255, 200, 80, 255))
+        .draw_at(&mut handle, Position { x, y: 10 });
+        x + size as i32 + 10
+    });
+    // Without a color override, `currentColor` is black
+    SvgView::new(
+        heart,
+        Size {
+            width: 48,
+            height: 48,
+        },
+    )
+    .draw_at(&mut handle, Position { x: 130, y: 10 });
+
+    let badge = ImageSource::bytes(BADGE.as_bytes());
+    SvgView::new(
+        badge.clone(),
+        Size {
+            width: 96,
+            height: 96,
+        },
+    )
+    .draw_at(&mut handle, Position { x: 190, y: 7 });
+    // Keeps its aspect ratio, centered in a wider box
+    SvgView::new(
+        badge,
+        Size {
+            width: 40,
+            height: 24,
+        },
+    )
+    .with_color(color::WHITE)
+    .draw_at(&mut handle, Position { x: 10, y: 76 });
+
+    assert_snapshot("svg_icons", &canvas);
+}
+
+#[test]
+fn transforms() {
+    let mut canvas = Canvas::new(420, 200);
+    canvas.fill(BACKGROUND);
+    let mut handle = DrawHandle::new(&mut canvas);
+    let square = Size {
+        width: 40,
+        height: 40,
+    };
+    let gradient = Gradient::linear(
+        Point::new(0.0, 0.0),
+        Point::new(40.0, 40.0),
+        vec![
+            ColorStop::new(0.0, Color::new(255, 200, 80, 255)),
+            ColorStop::new(1.0, Color::new(80, 120, 255, 255)),
+        ],
+    );
+
+    // Nested translations add up, and the gradient moves along with the shape
+    handle.push_transform(Transform::translate(10.0, 10.0));
+    handle.push_transform(Transform::translate(0.0, 50.0));
+    Shape::rect(square)
+        .fill(gradient.clone())
+        .draw_at(&mut handle, Position { x: 0, y: 0 });
+    handle.pop_transform();
+    Shape::rect(square)
+        .fill(gradient.clone())
+        .draw_at(&mut handle, Position { x: 0, y: 0 });
+    handle.pop_transform();
+
+    // Rotated about its center, with the stroke rotated too
+    handle.push_transform(
+        Transform::translate(-20.0, -20.0)
+            .then(Transform::rotate(std::f32::consts::FRAC_PI_6))
+            .then(Transform::translate(100.0, 40.0)),
+    );
+    Shape::rounded_rect(square, CornerRadii::all(8.0))
+        .fill(gradient.clone())
+        .stroke(Stroke::new(2.0), color::WHITE)
+        .draw_at(&mut handle, Position { x: 0, y: 0 });
+    handle.pop_transform();
+
+    // Scaled strokes get wider
+    handle.push_transform(Transform::scale(2.0, 2.0).then(Transform::translate(140.0, 10.0)));
+    Shape::circle(12.0)
+        .stroke(Stroke::new(1.5), Color::new(120, 220, 140, 255))
+        .draw_at(&mut handle, Position { x: 0, y: 0 });
+    handle.pop_transform();
+
+    // Skewed and rotated images are resampled
+    handle.push_transform(Transform::skew(-0.3, 0.0).then(Transform::translate(220.0, 10.0)));
+    ImageView::new(
+        test_image(),
+        Size {
+            width: 72,
+            height: 54,
+        },
+    )
+    .draw_at(&mut handle, Position { x: 0, y: 0 });
+    handle.pop_transform();
+
+    handle.push_transform(
+        Transform::rotate(-0.4)
+            .then(Transform::scale(2.0, 2.0))
+            .then(Transform::translate(330.0, 40.0)),
+    );
+    SvgView::new(
+        ImageSource::bytes(HEART.as_bytes()),
+        Size {
+            width: 24,
+            height: 24,
+        },
+    )
+    .with_color(Color::new(255, 120, 140, 255))
+    .draw_at(&mut handle, Position { x: 0, y: 0 });
+    handle.pop_transform();
+
+    // Scaled text is rasterized at the larger size, rotated text from its outlines
+    let label = |text: &str| {
+        let text = RichString::new(text.into(), typeface())
+            .with(0..text.len(), Attribute::Color(color::WHITE));
+        TextBody::new_label(text, Direction::Horizontal)
+    };
+    handle.push_transform(Transform::scale(1.5, 1.5).then(Transform::translate(10.0, 110.0)));
+    label("Scaled text").draw_at(&mut handle, Position { x: 0, y: 0 });
+    handle.pop_transform();
+    handle.push_transform(Transform::rotate(-0.2).then(Transform::translate(200.0, 166.0)));
+    label("Rotated text")
+        .with_paint(gradient)
+        .draw_at(&mut handle, Position { x: 0, y: 0 });
+    handle.pop_transform();
+
+    assert_snapshot("transforms", &canvas);
+}
+
+#[test]
+fn layers() {
+    let mut canvas = Canvas::new(420, 200);
+    canvas.fill(BACKGROUND);
+    let mut handle = DrawHandle::new(&mut canvas);
+    // Stripes to show what's below each layer
+    (0..21).for_each(|index| {
+        let color = match index % 2 {
+            0 => Color::new(230, 230, 230, 255),
+            _ => Color::new(60, 90, 160, 255),
+        };
+        Shape::rect(Size {
+            width: 20,
+            height: 200,
+        })
+        .fill(color)
+        .draw_at(
+            &mut handle,
+            Position {
+                x: index * 20,
+                y: 0,
+            },
+        );
+    });
+
+    // Overlapping shapes fade as a group, so the overlap isn't any darker
+    handle.push_layer(Layer::new().with_opacity(0.5));
+    Shape::circle(25.0)
+        .fill(Color::new(255, 80, 80, 255))
+        .draw_at(&mut handle, Position { x: 10, y: 10 });
+    Shape::circle(25.0)
+        .fill(Color::new(255, 80, 80, 255))
+        .draw_at(&mut handle, Position { x: 40, y: 10 });
+    handle.pop_layer();
+
+    // Nested layers multiply their opacities, and clip what's drawn in them
+    handle.push_layer(Layer::new().with_opacity(0.8));
+    handle.push_layer(Layer::new().with_opacity(0.5).with_clip(Rect::new(
+        Position { x: 120, y: 10 },
+        Size {
+            width: 60,
+            height: 30,
+        },
+    )));
+    Shape::rect(Size {
+        width: 60,
+        height: 50,
+    })
+    .fill(Color::new(80, 200, 120, 255))
+    .draw_at(&mut handle, Position { x: 120, y: 10 });
+    handle.pop_layer();
+    handle.pop_layer();
+
+    // A mask with rounded corners, and the image inside it
+    handle.push_layer(Layer::new().with_mask(Path::rounded_rect(
+        Point::new(200.0, 10.0),
+        96.0,
+        72.0,
+        CornerRadii::all(18.0),
+    )));
+    ImageView::new(
+        test_image(),
+        Size {
+            width: 96,
+            height: 72,
+        },
+    )
+    .draw_at(&mut handle, Position { x: 200, y: 10 });
+    handle.pop_layer();
+
+    // Every blend mode, with the same gradient over the stripes
+    let gradient = Gradient::linear(
+        Point::new(0.0, 0.0),
+        Point::new(0.0, 40.0),
+        vec![
+            ColorStop::new(0.0, Color::new(255, 200, 80, 255)),
+            ColorStop::new(1.0, Color::new(120, 40, 200, 255)),
+        ],
+    );
+    [
+        BlendMode::Normal,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+        BlendMode::Overlay,
+        BlendMode::Darken,
+        BlendMode::Lighten,
+        BlendMode::ColorDodge,
+        BlendMode::ColorBurn,
+        BlendMode::HardLight,
+        BlendMode::SoftLight,
+        BlendMode::Difference,
+        BlendMode::Exclusion,
+    ]
+    .into_iter()
+    .enumerate()
+    .for_each(|(index, mode)| {
+        let position = Position {
+            x: 10 + 66 * (index % 6) as i32,
+            y: 100 + 50 * (index / 6) as i32,
+        };
+        handle.push_layer(Layer::new().with_blend_mode(mode));
+        Shape::rect(Size {
+            width: 56,
+            height: 40,
+        })
+        .fill(gradient.clone())
+        .draw_at(&mut handle, position);
+        handle.pop_layer();
+    });
+    assert_snapshot("layers", &canvas);
+}
+
+#[test]
+fn effects() {
+    let mut canvas = Canvas::new(420, 200);
+    canvas.fill(Color::new(236, 238, 242, 255));
+    let mut handle = DrawHandle::new(&mut canvas);
+    let card = Size {
+        width: 80,
+        height: 60,
+    };
+    let shadow = Color::new(0, 0, 0, 110);
+    let radii = CornerRadii::all(10.0);
+
+    // Drop shadows: hard, blurred, spread, and under a rounded card
+    let shadows = [
+        BoxShadow::new(card, shadow).with_offset(4.0, 4.0),
+        BoxShadow::new(card, shadow)
+            .with_offset(0.0, 6.0)
+            .with_blur(16.0),
+        BoxShadow::new(card, Color::new(80, 120, 255, 160))
+            .with_blur(8.0)
+            .with_spread(4.0),
+        BoxShadow::new(card, shadow)
+            .with_radii(radii)
+            .with_offset(0.0, 8.0)
+            .with_blur(20.0)
+            .with_spread(-4.0),
+    ];
+    shadows.into_iter().enumerate().for_each(|(index, shadow)| {
+        let position = Position {
+            x: 20 + 100 * index as i32,
+            y: 15,
+        };
+        shadow.draw_at(&mut handle, position);
+        let fill = Shape::rounded_rect(card, shadow.radii).fill(color::WHITE);
+        fill.draw_at(&mut handle, position);
+    });
+
+    // Inset shadows, drawn over the box
+    let inset = BoxShadow::new(card, shadow)
+        .with_radii(radii)
+        .with_offset(0.0, 3.0)
+        .with_blur(10.0)
+        .inset();
+    Shape::rounded_rect(card, radii)
+        .fill(color::WHITE)
+        .draw_at(&mut handle, Position { x: 20, y: 115 });
+    inset.draw_at(&mut handle, Position { x: 20, y: 115 });
+
+    // A blurred layer, and frosted glass over some stripes
+    handle.push_layer(Layer::new().with_blur(3.0));
+    label_on(&mut handle, "Blurred", Position { x: 125, y: 130 });
+    handle.pop_layer();
+
+    (0..10).for_each(|index| {
+        Shape::rect(Size {
+            width: 8,
+            height: 80,
+        })
+        .fill(Color::new(40, 60, 160, 255))
+        .draw_at(
+            &mut handle,
+            Position {
+                x: 230 + 16 * index,
+                y: 110,
+            },
+        );
+    });
+    let glass = Path::rounded_rect(Point::new(250.0, 125.0), 120.0, 50.0, radii);
+    handle.push_layer(
+        Layer::new()
+            .with_mask(glass.clone())
+            .with_backdrop_blur(4.0),
+    );
+    handle.fill_path(
+        &glass,
+        FillRule::NonZero,
+        &Color::new(255, 255, 255, 60).into(),
+    );
+    handle.pop_layer();
+
+    assert_snapshot("effects", &canvas);
+}
+
+/// Draws a single line of large black text
+fn label_on(handle: &mut DrawHandle, text: &str, position: Position) {
+    let text = RichString::new(text.into(), typeface())
+        .with(0..text.len(), Attribute::Color(color::BLACK))
+        .with(0..text.len(), Attribute::Size(28));
+    TextBody::new_label(text, Direction::Horizontal).draw_at(handle, position);
+}
@@ -0,0 +1,173 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use softbuffer::Buffer;
+
+use crate::graphics::color::{self, Color};
+use crate::render::Pixel;
+
+/// Pixels that can be drawn to with a [`DrawHandle`](super::DrawHandle):
+/// `width * height` pixels in the `0x00RRGGBB` format, row by row from the top left
+pub trait Surface {
+    fn width(&self) -> Pixel;
+    fn height(&self) -> Pixel;
+    fn pixels(&self) -> &[u32];
+    fn pixels_mut(&mut self) -> &mut [u32];
+
+    fn pixel(&self, x: Pixel, y: Pixel) -> Option<Color> {
+        if x >= self.width() {
+            return None;
+        }
+        self.pixels()
+            .get(y * self.width() + x)
+            .copied()
+            .map(color::from_buffer)
+    }
+
+    fn fill(&mut self, color: Color) {
+        let value = color::to_buffer(color);
+        self.pixels_mut().fill(value);
+    }
+}
+
+/// A window's frame buffer, from `softbuffer`
+pub struct WindowSurface<'a> {
+    buffer: Buffer<'a>,
+    width: Pixel,
+    height: Pixel,
+}
+impl<'a> WindowSurface<'a> {
+    /// `width` and `height` have to be what the `softbuffer` surface was last resized to
+    pub fn new(buffer: Buffer<'a>, width: Pixel, height: Pixel) -> Self {
+        Self {
+            buffer,
+            width,
+            height,
+        }
+    }
+
+    /// Shows the frame in the window
+    pub fn present(self) -> Result<(), softbuffer::SoftBufferError> {
+        self.buffer.present()
+    }
+}
+impl Surface for WindowSurface<'_> {
+    fn width(&self) -> Pixel {
+        self.width
+    }
+    fn height(&self) -> Pixel {
+        self.height
+    }
+    fn pixels(&self) -> &[u32] {
+        &self.buffer
+    }
+    fn pixels_mut(&mut self) -> &mut [u32] {
+        &mut self.buffer
+    }
+}
+
+/// A surface in memory, for rendering without a window,
+/// like in tests or to take screenshots
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Canvas {
+    pixels: Vec<u32>,
+    width: Pixel,
+    height: Pixel,
+}
+impl Canvas {
+    /// A new canvas, filled with black
+    pub fn new(width: Pixel, height: Pixel) -> Self {
+        Self {
+            pixels: vec![0; width * height],
+            width,
+            height,
+        }
+    }
+
+    /// `pixels` has to have exactly `width * height` pixels
+    pub fn from_pixels(pixels: Vec<u32>, width: Pixel, height: Pixel) -> Option<Self> {
+        (pixels.len() == width * height).then_some(Self {
+            pixels,
+            width,
+            height,
+        })
+    }
+
+    pub fn into_pixels(self) -> Vec<u32> {
+        self.pixels
+    }
+}
+impl Surface for Canvas {
+    fn width(&self) -> Pixel {
+        self.width
+    }
+    fn height(&self) -> Pixel {
+        self.height
+    }
+    fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+    fn pixels_mut(&mut self) -> &mut [u32] {
+        &mut self.pixels
+    }
+}
+
+/// Encodes any surface as an 8-bit RGB PNG
+pub fn encode_png(surface: &dyn Surface, writer: impl Write) -> Result<(), png::EncodingError> {
+    let mut encoder = png::Encoder::new(writer, surface.width() as u32, surface.height() as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let data = surface
+        .pixels()
+        .iter()
+        .flat_map(|&value| {
+            let color = color::from_buffer(value);
+            [color.red, color.green, color.blue]
+        })
+        .collect::<Vec<u8>>();
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    writer.finish()
+}
+
+/// Writes any surface to a PNG file
+pub fn save_png(surface: &dyn Surface, path: impl AsRef<Path>) -> Result<(), png::EncodingError> {
+    let file = File::create(path)?;
+    encode_png(surface, BufWriter::new(file))
+}
+
+/// Decodes a PNG into a canvas; any alpha channel is ignored
+pub fn decode_png(reader: impl Read) -> Result<Canvas, png::DecodingError> {
+    let mut decoder = png::Decoder::new(reader);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data)?;
+    let data = &data[..info.buffer_size()];
+
+    let pixel = |channels: &[u8]| match *channels {
+        [gray] | [gray, _] => color::to_buffer(Color::new(gray, gray, gray, 255)),
+        [red, green, blue] | [red, green, blue, _] => {
+            color::to_buffer(Color::new(red, green, blue, 255))
+        }
+        _ => 0,
+    };
+    let pixels = data.chunks(info.color_type.samples()).map(pixel).collect();
+
+    let (width, height) = (info.width as Pixel, info.height as Pixel);
+    Ok(Canvas {
+        pixels,
+        width,
+        height,
+    })
+}
+
+/// Reads a PNG file into a canvas
+pub fn load_png(path: impl AsRef<Path>) -> Result<Canvas, png::DecodingError> {
+    let file = File::open(path)?;
+    decode_png(BufReader::new(file))
+}
@@ -1,144 +1,564 @@
+use std::cell::RefCell;
 use std::collections::VecDeque;
+use std::rc::Rc;
 
+use super::cache::{Cache, Footprint};
+use super::shaping::{self, ShapedCluster, ShapedGlyph, ShapedText};
 use super::{DrawHandle, Drawable, Pixel};
+use crate::graphics::color::Color;
+use crate::graphics::paint::Paint;
+use crate::graphics::path::{FillRule, Path, PathSegment, Point};
 use crate::graphics::text::{
-    Direction, RichChar, RichString, TextOptions, Wrapping, WORD_WRAP_LENGTH_THRESHOLD,
+    Decoration, Direction, LineBreak, LineHeight, RichString, TextOptions, Wrapping,
+    WORD_WRAP_LENGTH_THRESHOLD,
 };
-use crate::layout::{Position, Size, SizeConstraint};
+use crate::graphics::transform::Transform;
+use crate::graphics::typeface::{has_glyph, Variant};
+use crate::layout::{Position, Rect, Size, SizeConstraint};
 
+use ab_glyph_rasterizer::{point, Rasterizer};
 use fontdue::Font;
 
+/// Line breaking behavior after a unit, from the UAX #14 line break opportunities
 #[derive(PartialEq, Eq)]
 enum BreakKind {
+    /// No line break opportunity;
+    /// we can only break here when falling back to breaking by character
     Character,
 
-    /// Non-word character;
     /// **After** this unit, we can perform a line break by word
     WordBoundary,
+
+    /// This unit is a hard line break (U+2028 LINE SEPARATOR);
+    /// the next unit goes on a new line, within the same paragraph
+    Line,
+
+    /// This unit is a hard paragraph break (a newline, or U+2029 PARAGRAPH SEPARATOR);
+    /// the next unit goes on a new line, after the paragraph spacing
+    Paragraph,
+}
+impl BreakKind {
+    /// `line_break` is the line break opportunity right after the `text` of the unit
+    fn new(text: &str, line_break: Option<LineBreak>) -> Self {
+        match line_break {
+            None => BreakKind::Character,
+            Some(LineBreak::Allowed) => BreakKind::WordBoundary,
+            // The end of the text is a mandatory break too, but there's nothing after it to break from
+            Some(LineBreak::Mandatory) if !is_line_terminator(text) => BreakKind::WordBoundary,
+            Some(LineBreak::Mandatory) if text == "\u{2028}" => BreakKind::Line,
+            Some(LineBreak::Mandatory) => BreakKind::Paragraph,
+        }
+    }
+
+    fn is_hard_break(&self) -> bool {
+        matches!(self, BreakKind::Line | BreakKind::Paragraph)
+    }
+}
+
+/// `true` for the grapheme clusters that force a line break;
+/// `\r\n` is a single grapheme cluster
+fn is_line_terminator(cluster: &str) -> bool {
+    matches!(
+        cluster,
+        "\n" | "\r\n" | "\r" | "\u{0B}" | "\u{0C}" | "\u{85}" | "\u{2028}" | "\u{2029}"
+    )
 }
 
-/// A "piece" of text - either whitespace, or a rasterized text segment.
+/// A "piece" of text - either whitespace, a hard line break, or a rasterized text segment.
 /// If it's a raster, `raster` is `Some`.
+/// Hard line breaks are kept at the end of their line, with no width and no raster.
 ///
-/// The `width` and `height` described here is the entire block size;
-/// `Unit`s are assumed to be placed right next to each other.
+/// `Unit`s are assumed to be placed right next to each other, sharing the line's baseline.
+/// The `width` is fractional; units are only snapped to whole pixels when they're drawn.
 struct Unit {
-    width: Pixel,
-    height: Pixel,
+    width: f32,
+    /// The font size
+    size: Pixel,
+    metrics: LineMetrics,
     raster: Option<Raster>,
 
     /// Information used during the line breaking process;
     /// describes what kind of break can happen **after** this unit
     break_kind: BreakKind,
 
-    font: &'static Font,
+    /// The font this unit was rasterized with, after resolving fallbacks
+    font: &'static Variant,
+    /// The string's own font for this unit, used for the truncation ellipsis
+    base_font: &'static Variant,
+
+    color: Color,
+    decoration: Decoration,
 }
 
 /* TODO:
-   non-breaking spaces in wrap algo
-   0 width joiners?
-   NEWLINES lmao
    change Raster to represent only bitmap; even spaces will be "rasterized" with `Unit::rasterize`
 
 
    //TODODOODODOOOD pushing characters when doesn't fit but it's also a word boundary
    // todo we are assuming we know the max width but what about like several labels side by side
    // also "label" is a good nomenclature for single-line truncate text
-
-    I think I have the wrong idea about the baseline shit
 */
 impl Unit {
-    fn rasterize(rich_char: RichChar) -> Self {
-        let raster = Raster::rasterize(rich_char.font, rich_char.size, rich_char.char);
+    /// `line_break` is the line break opportunity right after the unit
+    fn rasterize(shaped: ShapedText, line_break: Option<LineBreak>) -> Self {
+        let attributes = shaped.attributes;
+        let raster = Raster::new(&shaped.shaped);
+        let break_kind = BreakKind::new(shaped.text, line_break);
         Self {
-            width: raster.advance,
-            height: rich_char.size,
-            raster: Some(raster),
-            break_kind: match rich_char.char.is_alphabetic() {
-                true => BreakKind::Character,
-                false => BreakKind::WordBoundary,
+            width: match break_kind.is_hard_break() {
+                true => 0.0,
+                false => (raster.advance + attributes.letter_spacing).max(0.0),
+            },
+            size: attributes.size,
+            metrics: LineMetrics::new(&shaped.font.data, attributes.size),
+            // Whitespace still has an advance, but there's nothing to draw
+            raster: match shaped.text.chars().all(char::is_whitespace) {
+                true => None,
+                false => Some(raster),
             },
-            font: rich_char.font,
+            break_kind,
+            font: shaped.font,
+            base_font: shaped.base_font,
+            color: attributes.color,
+            decoration: attributes.decoration,
         }
     }
 
+    /// `true` for spaces and the like; hard line breaks don't count
     fn is_whitespace(&self) -> bool {
-        self.raster.is_none()
+        self.raster.is_none() && !self.break_kind.is_hard_break()
+    }
+
+    /// Draws this unit with its pen position at `x`, on the `baseline`,
+    /// with `paint` instead of its own color if there is one
+    fn draw_at(&self, handle: &mut DrawHandle, x: f32, baseline: i32, paint: Option<&Paint>) {
+        let own_color = Paint::Solid(self.color);
+        let paint = paint.unwrap_or(&own_color);
+        if let Some(ref raster) = self.raster {
+            raster.draw_at(handle, self.font, self.size, x, baseline, paint);
+        }
+        self.draw_decorations(handle, x, baseline, paint);
+    }
+
+    /// Draws this unit's decoration lines across its whole width.
+    /// The lines are snapped to whole pixels at both ends,
+    /// so that the decorations of consecutive units line up.
+    ///
+    /// `fontdue` doesn't give us the font's own underline and strikeout metrics,
+    /// so these are approximated from the font size and ascent.
+    fn draw_decorations(&self, handle: &mut DrawHandle, x: f32, baseline: i32, paint: &Paint) {
+        if self.decoration == Decoration::NONE {
+            return;
+        }
+
+        let size = self.size as f32;
+        let left = x.round() as i32;
+        let right = (x + self.width).round() as i32;
+        let thickness = Pixel::max(1, (size / 16.0).round() as Pixel);
+
+        let mut lines = Vec::new();
+        if self.decoration.underline {
+            lines.push(baseline + (size / 12.0) as i32);
+        }
+        if self.decoration.strikethrough {
+            lines.push(baseline - (size * 0.3) as i32);
+        }
+        if self.decoration.overline {
+            lines.push(baseline - self.metrics.ascent.round() as i32);
+        }
+
+        lines.into_iter().for_each(|y| {
+            let rect = Rect::new(
+                Position { x: left, y },
+                Size {
+                    width: (right - left).max(0) as Pixel,
+                    height: thickness,
+                },
+            );
+            handle.fill_rect(rect, paint)
+        });
     }
 }
 
-/// Output of rasterization, for a given font and  size.
-/// This currently only represents a single glyph, but eventually we will account for grapheme clusters.
-///
-/// All of the offsets contained here are relative to the top of the font line.
-///
-/// -----
-///
-/// Ultimately this stuff comes from `fontdue`,
-/// but we're keeping some computed intermediate data here.
-/// Particularly noteworthy is the conversions from subpixel floats to integer pixels.
+/// Vertical metrics of a font at some size, in pixels, from `fontdue`'s horizontal line metrics.
+/// Both `ascent` and `descent` are positive, going up and down from the baseline respectively.
+#[derive(Clone, Copy)]
+struct LineMetrics {
+    ascent: f32,
+    descent: f32,
+    line_gap: f32,
+    size: Pixel,
+}
+impl LineMetrics {
+    fn new(font: &Font, size: Pixel) -> Self {
+        match font.horizontal_line_metrics(size as f32) {
+            Some(metrics) => Self {
+                ascent: metrics.ascent,
+                descent: -metrics.descent,
+                line_gap: metrics.line_gap,
+                size,
+            },
+            // Fonts without horizontal metrics are vanishingly rare;
+            // this just treats the whole em box as being above the baseline
+            None => Self {
+                ascent: size as f32,
+                descent: 0.0,
+                line_gap: 0.0,
+                size,
+            },
+        }
+    }
+
+    /// How far this font's part of the line extends above and below the baseline, respectively.
+    ///
+    /// Like in CSS, the difference between the line height and the font's height is the _leading_,
+    /// which is split in half between the top and the bottom.
+    fn extents(&self, line_height: LineHeight) -> (f32, f32) {
+        let content = self.ascent + self.descent;
+        let height = match line_height {
+            LineHeight::Normal => content + self.line_gap,
+            LineHeight::Absolute(height) => height as f32,
+            LineHeight::Multiplier(multiplier) => multiplier * self.size as f32,
+        };
+        let half_leading = (height - content) / 2.0;
+        (self.ascent + half_leading, self.descent + half_leading)
+    }
+}
+
+/// Number of horizontal positions within a pixel that glyphs are rasterized at.
+/// Glyphs are positioned to the nearest of these, rather than the nearest whole pixel,
+/// so that rounding errors don't add up over a line.
+const SUBPIXEL_BINS: u8 = 4;
+
+/// Splits a fractional horizontal position into a whole pixel,
+/// and the subpixel bin to the right of it; see [`SUBPIXEL_BINS`]
+fn subpixel(x: f32) -> (i32, u8) {
+    let bins = SUBPIXEL_BINS as i32;
+    let position = (x * bins as f32).round() as i32;
+    (position.div_euclid(bins), position.rem_euclid(bins) as u8)
+}
+
+/// The glyphs of a whole grapheme cluster, for a given font and size.
+/// Most clusters are a single glyph, but combining marks and the like are separate glyphs
+/// positioned relative to the first one.
 ///
-/// I'm probably doing some of this wrong, so having the intermediate struct
-/// will help when I inevitably have to fix things.  I have no idea what I'm doing.
-/// Also, this might provide an opportunity to implement other layouts and future optimizations
+/// The glyphs themselves are rasterized when drawing,
+/// since that's when we know which subpixel position they end up at.
 #[derive(Clone)]
 struct Raster {
-    /// We should probably `Rc` this, or maybe implement proper caching
+    glyphs: Vec<ShapedGlyph>,
+    advance: f32,
+}
+
+/// A single glyph, rasterized at one of the subpixel positions.
+///
+/// All of the offsets contained here are relative to the glyph's origin on the baseline.
+pub struct GlyphRaster {
     bitmap: Vec<u8>,
     bitmap_width: usize,
-    bitmap_height: usize,
 
-    baseline: Pixel,
-    bottom: Pixel,
-    top: Pixel,
+    /// Vertical position of the top of the bitmap; negative is above the baseline
+    top: i32,
 
-    advance: Pixel,
+    /// Signed left side bearing: the distance from the glyph's origin to the left edge of the bitmap.
+    /// Negative for glyphs that overhang to the left, like a `j` or a lot of italics
+    bearing: i32,
 }
 impl Raster {
-    fn rasterize(font: &Font, font_size: Pixel, character: char) -> Self {
-        let (metrics, bitmap) = font.rasterize(character, font_size as f32);
+    fn new(cluster: &ShapedCluster) -> Self {
+        Self {
+            glyphs: cluster.glyphs.clone(),
+            advance: cluster.advance,
+        }
+    }
+
+    /// Draws the cluster with the given paint, blended over the buffer by the glyphs' coverage,
+    /// with its pen position at `x`, on the `baseline`.
+    ///
+    /// When the handle's transform only moves and scales, the glyphs are rasterized at the scaled size,
+    /// so that they stay as sharp as unscaled text. Otherwise their outlines are filled like any path
+    fn draw_at(
+        &self,
+        handle: &mut DrawHandle,
+        font: &Variant,
+        font_size: Pixel,
+        x: f32,
+        baseline: i32,
+        paint: &Paint,
+    ) {
+        let transform = handle.transform();
+        match transform.uniform_scale() {
+            Some(scale) => self.glyphs.iter().for_each(|glyph| {
+                let origin = Point::new(x + glyph.x, baseline as f32 - glyph.y);
+                let origin = transform.apply(origin);
+                let (x, bin) = subpixel(origin.x);
+                let y = origin.y.round() as i32;
+                let size = font_size as f32 * scale;
+                GlyphRaster::cached(font, glyph.id, size, bin).draw_at(handle, x, y, paint);
+            }),
+            None => self.glyphs.iter().for_each(|glyph| {
+                let Some(outline) = glyph_outline(font, glyph.id) else {
+                    return;
+                };
+                let scale = font_size as f32 / outline.units_per_em;
+                let origin = Point::new(x + glyph.x, baseline as f32 - glyph.y);
+                // Font units point up
+                let placement =
+                    Transform::scale(scale, -scale).then(Transform::translate(origin.x, origin.y));
+                let path = outline.path.transformed(placement);
+                handle.fill_path(&path, FillRule::NonZero, paint);
+            }),
+        }
+    }
+}
+/// Identifies a [`GlyphRaster`] in the glyph cache
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    /// The font file, by `fontdue`'s hash of its contents
+    font: usize,
+    /// The face's index within the font file, for collections
+    index: u32,
+    glyph: u16,
+    /// The font size, in [`SIZE_STEPS`] of a pixel, so that scaled text gets rasters of its own
+    size: u32,
+    /// Subpixel position; see [`SUBPIXEL_BINS`]
+    bin: u8,
+}
+
+/// Number of font sizes within a pixel that glyphs are rasterized at.
+/// Scaled text is rounded to the nearest of these, so that an animated scale
+/// doesn't fill the glyph cache with sizes that look the same
+const SIZE_STEPS: f32 = 8.0;
+
+impl Footprint for GlyphRaster {
+    fn bytes(&self) -> usize {
+        std::mem::size_of::<Self>() + self.bitmap.len()
+    }
+}
 
-        let bottom = (font_size as i32 - metrics.bounds.ymin as i32) as Pixel;
-        let top = bottom - metrics.bounds.height as Pixel;
-        //` metrics.bounds.ymin` instead of `metrics.ymin`;
-        // afaict, `metrics.ymin` is just `bounds.ymin` rounded up, which made it wonky
-        // it's still wonky but it's a bit better
+/// Default memory budget of the glyph cache, in bytes
+pub const GLYPH_CACHE_BUDGET: usize = 4 * 1024 * 1024;
 
-        //TODO: Account for xmin!!
+thread_local! {
+    /// Rasterized glyphs, shared by every text drawn on this thread across frames
+    static GLYPH_CACHE: RefCell<Cache<GlyphKey, GlyphRaster>> =
+        RefCell::new(Cache::new(GLYPH_CACHE_BUDGET));
+}
+
+/// Runs `f` with this thread's glyph cache,
+/// e.g. to change its memory budget or look at its statistics
+pub fn with_glyph_cache<R>(f: impl FnOnce(&mut Cache<GlyphKey, GlyphRaster>) -> R) -> R {
+    GLYPH_CACHE.with(|cache| f(&mut cache.borrow_mut()))
+}
+
+/// A glyph's vector outline, for text that's rotated or skewed,
+/// which can't be drawn from an upright bitmap
+struct GlyphOutline {
+    /// In font units, with the origin on the baseline and y pointing up
+    path: Path,
+    units_per_em: f32,
+}
+
+impl Footprint for GlyphOutline {
+    fn bytes(&self) -> usize {
+        std::mem::size_of::<Self>() + std::mem::size_of_val(self.path.segments())
+    }
+}
+
+/// Memory budget of the glyph outline cache, in bytes
+const OUTLINE_CACHE_BUDGET: usize = 1024 * 1024;
+
+thread_local! {
+    /// Glyph outlines by font file, face index and glyph, for every size they're drawn at
+    static OUTLINE_CACHE: RefCell<Cache<(usize, u32, u16), GlyphOutline>> =
+        RefCell::new(Cache::new(OUTLINE_CACHE_BUDGET));
+}
+
+/// Gets a glyph's outline from the outline cache, reading it from the font if it's not there.
+/// `None` for glyphs without one, like spaces
+fn glyph_outline(font: &Variant, id: u16) -> Option<Rc<GlyphOutline>> {
+    let key = (font.data.file_hash(), font.index, id);
+    OUTLINE_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        cache
+            .try_get_or_insert_with(key, || {
+                let face = ttf_parser::Face::from_slice(&font.bytes, font.index).map_err(|_| ())?;
+                let mut builder = OutlineBuilder(Path::new());
+                face.outline_glyph(ttf_parser::GlyphId(id), &mut builder)
+                    .ok_or(())?;
+                Ok::<_, ()>(GlyphOutline {
+                    path: builder.0,
+                    units_per_em: face.units_per_em() as f32,
+                })
+            })
+            .ok()
+    })
+}
+
+/// Collects a glyph's outline from `ttf-parser` into a [`Path`]
+struct OutlineBuilder(Path);
+impl ttf_parser::OutlineBuilder for OutlineBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.push(PathSegment::MoveTo(Point::new(x, y)));
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.push(PathSegment::LineTo(Point::new(x, y)));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.0
+            .push(PathSegment::QuadTo(Point::new(x1, y1), Point::new(x, y)));
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.0.push(PathSegment::CubicTo(
+            Point::new(x1, y1),
+            Point::new(x2, y2),
+            Point::new(x, y),
+        ));
+    }
+
+    fn close(&mut self) {
+        self.0.push(PathSegment::Close);
+    }
+}
+
+impl GlyphRaster {
+    /// Gets the rasterized glyph from the glyph cache, rasterizing it if it's not there
+    fn cached(font: &Variant, id: u16, font_size: f32, bin: u8) -> Rc<Self> {
+        let size = (font_size * SIZE_STEPS).round() as u32;
+        let key = GlyphKey {
+            font: font.data.file_hash(),
+            index: font.index,
+            glyph: id,
+            size,
+            bin,
+        };
+        let font_size = size as f32 / SIZE_STEPS;
+        with_glyph_cache(|cache| {
+            cache.get_or_insert_with(key, || Self::rasterize(font, id, font_size, bin))
+        })
+    }
+
+    /// Rasterizes a glyph's outline as if its origin was `bin` subpixels to the right of a whole pixel.
+    ///
+    /// `fontdue` can only rasterize glyphs with their origin on a whole pixel,
+    /// so we read the outline with `ttf_parser` and fill it with `ab_glyph_rasterizer` instead.
+    fn rasterize(font: &Variant, id: u16, font_size: f32, bin: u8) -> Self {
+        let empty = GlyphRaster {
+            bitmap: Vec::new(),
+            bitmap_width: 0,
+            top: 0,
+            bearing: 0,
+        };
+        let Ok(face) = ttf_parser::Face::from_slice(&font.bytes, font.index) else {
+            return empty;
+        };
+        let glyph = ttf_parser::GlyphId(id);
+        // Glyphs without an outline, like spaces, have no bounding box either
+        let Some(bounds) = face.glyph_bounding_box(glyph) else {
+            return empty;
+        };
+
+        let scale = font_size / face.units_per_em() as f32;
+        let shift = bin as f32 / SUBPIXEL_BINS as f32;
+
+        // The bitmap covers the outline's bounds, rounded outwards to whole pixels.
+        // Font units point up, so the top of the bitmap comes from the top of the outline
         // https://freetype.org/freetype2/docs/glyphs/glyphs-3.html
+        let left = (f32::from(bounds.x_min) * scale + shift).floor() as i32;
+        let right = (f32::from(bounds.x_max) * scale + shift).ceil() as i32;
+        let top = (-f32::from(bounds.y_max) * scale).floor() as i32;
+        let bottom = (-f32::from(bounds.y_min) * scale).ceil() as i32;
+        let width = (right - left) as usize;
+        let height = (bottom - top) as usize;
+
+        let mut outline = OutlineRasterizer {
+            rasterizer: Rasterizer::new(width, height),
+            scale,
+            origin: point(shift - left as f32, -top as f32),
+            start: point(0.0, 0.0),
+            last: point(0.0, 0.0),
+        };
+        face.outline_glyph(glyph, &mut outline);
 
-        Self {
+        let mut bitmap = vec![0; width * height];
+        outline.rasterizer.for_each_pixel(|index, alpha| {
+            bitmap[index] = (alpha.min(1.0) * 255.0).round() as u8;
+        });
+        GlyphRaster {
             bitmap,
-            bitmap_width: metrics.width,
-            bitmap_height: metrics.height,
-
-            baseline: font_size,
-            bottom,
+            bitmap_width: width,
             top,
-
-            advance: metrics.advance_width.round() as Pixel,
+            bearing: left,
         }
     }
-}
-impl Drawable for Raster {
-    fn draw_at(&self, handle: &mut DrawHandle, position: Position) {
+
+    /// Draws the glyph with its origin at `x`, on the `baseline`
+    fn draw_at(&self, handle: &mut DrawHandle, x: i32, baseline: i32, paint: &Paint) {
         (0..self.bitmap.len()).for_each(|bmp_i| {
             let bmp_x = bmp_i % self.bitmap_width;
             let bmp_y = bmp_i / self.bitmap_width;
 
-            //TODO: Account for xmin, here?
+            let x = x + self.bearing + bmp_x as i32;
+            let y = baseline + self.top + bmp_y as i32;
+            handle.paint(Position { x, y }, paint, self.bitmap[bmp_i]);
+        })
+    }
+}
 
-            let val = (255.0 * (self.bitmap[bmp_i] as f32 / 255.0)) as u8 as u32;
-            let val = val | (val << 8) | (val << 16);
+/// Feeds a glyph outline from `ttf_parser` to `ab_glyph_rasterizer`,
+/// scaling it from font units and placing it in the bitmap along the way
+struct OutlineRasterizer {
+    rasterizer: Rasterizer,
+    /// Pixels per font unit
+    scale: f32,
+    /// Where the glyph's origin is in the bitmap
+    origin: ab_glyph_rasterizer::Point,
+
+    /// The start of the current contour, and the last point drawn to
+    start: ab_glyph_rasterizer::Point,
+    last: ab_glyph_rasterizer::Point,
+}
+impl OutlineRasterizer {
+    fn point(&self, x: f32, y: f32) -> ab_glyph_rasterizer::Point {
+        point(
+            self.origin.x + x * self.scale,
+            self.origin.y - y * self.scale,
+        )
+    }
+}
+impl ttf_parser::OutlineBuilder for OutlineRasterizer {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.start = self.point(x, y);
+        self.last = self.start;
+    }
 
-            handle.set(
-                Position {
-                    x: position.x + bmp_x,
-                    y: position.y + self.top + bmp_y,
-                },
-                val,
-            );
-        })
+    fn line_to(&mut self, x: f32, y: f32) {
+        let to = self.point(x, y);
+        self.rasterizer.draw_line(self.last, to);
+        self.last = to;
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let to = self.point(x, y);
+        self.rasterizer.draw_quad(self.last, self.point(x1, y1), to);
+        self.last = to;
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let to = self.point(x, y);
+        self.rasterizer
+            .draw_cubic(self.last, self.point(x1, y1), self.point(x2, y2), to);
+        self.last = to;
+    }
+
+    fn close(&mut self) {
+        if self.last != self.start {
+            self.rasterizer.draw_line(self.last, self.start);
+        }
+        self.last = self.start;
     }
 }
 
@@ -186,95 +606,76 @@ impl Drawable for Raster {
 //     }
 // }
 
+/// Metrics of the default font of the text, which every line is at least as tall as
+fn strut(text: &RichString) -> LineMetrics {
+    let defaults = text.defaults();
+    let font = defaults
+        .typeface
+        .match_variant(defaults.weight, defaults.style);
+    LineMetrics::new(&font.data, defaults.size)
+}
+
 /// Returned by text wrapping functions;
 /// contains the resulting `TextLine` and the list of any `Unit`s chopped off by the wrapping operation.
 type TextLineSplit = (TextLine, Vec<Unit>);
 
-/// This iterates **backwards** from the end of the line.
-/// Instead of calling the main function `next`, we're calling it `advance`, to avoid confusion.
-/// The opposite function is `retreat`, which moves to the subsequent character.
-/// Those functions return a `LineAt`; see below.
-///
-/// The `confirm` and `cancel` functions convert this object into our final result.
-struct LineSplitIter {
-    line: TextLine,
-    position: usize,
-    width_at_position: Pixel,
+/// Snaps a fractional width to whole pixels, for checking it against a [`SizeConstraint`];
+/// anything that's even partly covered counts
+fn snap_width(width: f32) -> Pixel {
+    width.ceil().max(0.0) as Pixel
 }
 
-/// A `Unit` in the line, together with the width of the line
-/// **up to and including** that `Unit`, disregarding everything after it.
-type LineAt<'a> = (&'a Unit, Pixel);
+pub struct TextLine {
+    contents: Vec<Unit>,
+    width: f32,
 
-impl LineSplitIter {
-    fn new(line: TextLine) -> Self {
-        Self {
-            position: line.contents.len(),
-            width_at_position: line.width,
-            line,
-        }
-    }
+    /// Metrics of the text's default font, which every line is at least as tall as,
+    /// even an empty one; this is what CSS calls the _strut_
+    strut: LineMetrics,
+    line_height: LineHeight,
+}
 
-    /// Moves the pointer to the **previous** character in the line.
-    /// First call returns the last character in the line,
-    /// `None` if we've already reached the beginning of the line.
-    fn advance(&mut self) -> Option<LineAt> {
-        if self.position == 0 {
-            None
-        } else {
-            self.position -= 1;
-            let unit = &self.line.contents[self.position];
-            self.width_at_position -= unit.width;
-            Some((unit, self.width_at_position))
-        }
-    }
-    /// Moves the pointer to the **subsequent** character in the line.
-    /// `None` if we've already reached the end of the line.
-    fn retreat(&mut self) -> Option<LineAt> {
-        if self.position >= self.line.contents.len() {
-            None
-        } else {
-            self.position += 1;
-            let unit = &self.line.contents[self.position];
-            self.width_at_position += unit.width;
-            Some((unit, self.width_at_position))
+impl TextLine {
+    fn new(strut: LineMetrics, line_height: LineHeight) -> Self {
+        TextLine {
+            contents: Vec::new(),
+            width: 0.0,
+            strut,
+            line_height,
         }
     }
 
-    /// Returns the line split by the current position of this iterator.
-    fn confirm(mut self) -> TextLineSplit {
-        let split: Vec<_> = self.line.contents.drain(self.position..).collect();
-        (
-            TextLine {
-                contents: self.line.contents,
-                width: self.width_at_position,
-            },
-            split,
-        )
+    /// A new, empty line with the same metrics as this one
+    fn next(&self) -> Self {
+        Self::new(self.strut, self.line_height)
     }
 
-    /// Ignores the current position of this iterator,
-    /// returning the original line and an empty split fragment.
-    fn cancel(self) -> TextLineSplit {
-        (self.line, Vec::new())
+    /// Ends this line, leaving an empty one in its place
+    fn break_line(&mut self) -> Self {
+        let next = self.next();
+        std::mem::replace(self, next)
     }
-}
 
-pub struct TextLine {
-    contents: Vec<Unit>,
-    width: Pixel,
-}
+    /// Distances from the shared baseline of the line to its top and bottom, respectively.
+    /// With a small enough line height, these can be negative
+    fn extents(&self) -> (f32, f32) {
+        std::iter::once(self.strut)
+            .chain(self.contents.iter().map(|unit| unit.metrics))
+            .map(|metrics| metrics.extents(self.line_height))
+            .fold((f32::MIN, f32::MIN), |(above, below), (a, b)| {
+                (above.max(a), below.max(b))
+            })
+    }
 
-impl TextLine {
-    fn new() -> Self {
-        TextLine {
-            contents: Vec::new(),
-            width: 0,
-        }
+    /// Distance from the top of the line to its baseline
+    fn baseline(&self) -> Pixel {
+        self.extents().0.round().max(0.0) as Pixel
     }
 
+    /// The line advance, which depends on the tallest fonts in the line
     fn height(&self) -> Pixel {
-        self.contents.iter().fold(0, |res, val| res + val.height)
+        let (above, below) = self.extents();
+        (above + below).round().max(0.0) as Pixel
     }
 
     fn push(&mut self, unit: Unit) {
@@ -286,7 +687,7 @@ impl TextLine {
     /// subject to the `max_size` constraint.
     #[inline]
     fn would_fit(&self, max_size: SizeConstraint, unit: &Unit) -> bool {
-        max_size.fits_width(self.width + unit.width)
+        max_size.fits_width(snap_width(self.width + unit.width))
     }
 
     fn pop(&mut self) -> Option<Unit> {
@@ -298,87 +699,160 @@ impl TextLine {
         }
     }
 
-    /// Converts this line into a `TextLineSplit` by trying to split at the last word boundary,
+    /// Removes the hard line break this line ends with, if any
+    fn take_hard_break(&mut self) -> Option<Unit> {
+        match self.contents.last() {
+            Some(unit) if unit.break_kind.is_hard_break() => self.contents.pop(),
+            _ => None,
+        }
+    }
+
+    /// `true` if there's paragraph spacing after this line
+    fn ends_paragraph(&self) -> bool {
+        self.contents
+            .last()
+            .is_some_and(|unit| unit.break_kind == BreakKind::Paragraph)
+    }
+
+    /// Removes any whitespace from the end of the line, keeping its hard line break
+    fn trim_end(&mut self) {
+        let hard_break = self.take_hard_break();
+        while self.contents.last().is_some_and(Unit::is_whitespace) {
+            self.pop();
+        }
+        self.contents.extend(hard_break);
+    }
+
+    /// Pops units off the end of the line until an ellipsis fits after them, then adds it
+    fn push_ellipsis(&mut self, max_size: SizeConstraint) {
+        // Keep popping units until we can fit an ellipsis in this line
+        while let Some(unit) = self.pop() {
+            // Make an ellipsis with the same font and size as this last unit;
+            // if that's a fallback font without a period, use the string's own font instead
+            let font = match has_glyph(&unit.font.data, '.') {
+                true => unit.font,
+                false => unit.base_font,
+            };
+            let Some(period) = shaping::shape_run(".", font, unit.size).pop() else {
+                log::warn!("couldn't shape ellipsis");
+                return;
+            };
+            let period = Raster::new(&period);
+            let ellipsis_width = period.advance * 3.0;
+
+            let ellipsis = vec![period.clone(), period.clone(), period.clone()]
+                .into_iter()
+                .map(|raster| Unit {
+                    raster: Some(raster),
+                    width: period.advance,
+                    size: unit.size,
+                    metrics: LineMetrics::new(&font.data, unit.size),
+                    break_kind: BreakKind::Character,
+                    font,
+                    base_font: unit.base_font,
+                    color: unit.color,
+                    decoration: unit.decoration,
+                })
+                .collect::<Vec<Unit>>();
+
+            let trunc_size = Size {
+                width: snap_width(self.width + ellipsis_width),
+                height: self.height(),
+            };
+
+            if max_size.fits(trunc_size) {
+                ellipsis
+                    .into_iter()
+                    .for_each(|unit| self.contents.push(unit));
+
+                return;
+            }
+        }
+        log::warn!("ran out of units trying to fit ellipsis");
+    }
+
+    /// Converts this line into a `TextLineSplit` by trying to split at the last line break opportunity,
     /// unless [`WORD_WRAP_LENGTH_THRESHOLD`] is reached, in which case the line will be preserved
     /// and the split fragment will be empty.
+    /// Whitespace at the end of the resulting line is dropped.
     ///
     /// This function should be called when the line has already reached its maximum size.
-    fn split_at_word_boundary(self, max_size: SizeConstraint) -> TextLineSplit {
+    fn split_at_word_boundary(mut self, max_size: SizeConstraint) -> TextLineSplit {
         let min_width = max_size
             .width
-            .map(|mw| (mw as f32 * WORD_WRAP_LENGTH_THRESHOLD) as Pixel);
-
-        let mut cursor = LineSplitIter::new(self);
-        let mut breaking_whitespace = false;
-
-        loop {
-            // Iterating backwards from the end of the line.
-            // First, look for a character
-            let Some((unit, width_at_unit)) = cursor.advance() else {
-                // This shouldn't really ever happen, due to the `WORD_WRAP_LENGTH_THRESHOLD`
-                log::warn!("ran out of characters");
-                return cursor.confirm();
-            };
-
-            // Attempting to break a line here
-            if breaking_whitespace || unit.break_kind == BreakKind::WordBoundary {
-                if unit.is_whitespace() {
-                    // We will keep eating whitespace until we find a non-whitespace character to end the line with
-                    breaking_whitespace = true;
-                    continue;
-                } else {
-                    // This is a non-whitespace character.  If we
-                    //  a) are just now reaching a word boundary for the first time, or
-                    //  b) have already skipped past some whitespace,
-                    // keep this character in the line and split afterwards
-                    cursor.retreat();
-                    return cursor.confirm();
-                }
+            .map(|mw| mw as f32 * WORD_WRAP_LENGTH_THRESHOLD);
+
+        // Iterating backwards from the end of the line,
+        // keeping track of the width of the line up to and including each unit
+        let mut width = self.width;
+        for index in (0..self.contents.len()).rev() {
+            // If there's a minimum width threshold and we've hit it, cancel the entire operation
+            if matches!(min_width, Some(min_width) if width < min_width) {
+                break;
             }
 
-            // If we can't break a line here, but there's a minimum width threshold and we've hit it,
-            // cancel the entire operation
-            if matches!(min_width, Some(min_width) if width_at_unit < min_width) {
-                return cursor.cancel();
+            let unit = &self.contents[index];
+            if unit.break_kind != BreakKind::Character {
+                let split = self.contents.split_off(index + 1);
+                self.width = width;
+                self.trim_end();
+                return (self, split);
             }
 
-            // Otherwise we haven't found a line break or hit the minimum width, so just keep going
-            continue;
+            width -= unit.width;
         }
+
+        (self, Vec::new())
     }
 }
-impl Drawable for TextLine {
-    fn draw_at(&self, handle: &mut DrawHandle, position: Position) {
-        let mut cursor = position.x;
+impl TextLine {
+    /// Draws the line with `paint` instead of the colors of its units, if there is one
+    fn draw_with(&self, handle: &mut DrawHandle, position: Position, paint: Option<&Paint>) {
+        let baseline = position.y + self.baseline() as i32;
+        let mut pen = position.x as f32;
         self.contents.iter().for_each(|unit| {
-            if let Some(ref raster) = unit.raster {
-                raster.draw_at(
-                    handle,
-                    Position {
-                        x: position.x + cursor,
-                        y: position.y,
-                    },
-                );
-            }
-
-            cursor += unit.width;
+            unit.draw_at(handle, pen, baseline, paint);
+            pen += unit.width;
         });
     }
 }
+impl Drawable for TextLine {
+    fn draw_at(&self, handle: &mut DrawHandle, position: Position) {
+        self.draw_with(handle, position, None);
+    }
+}
 
 pub struct TextBody {
     lines: Vec<TextLine>,
 
+    /// Extra space after each line that ends a paragraph, from [`TextOptions::paragraph_spacing`]
+    paragraph_spacing: Pixel,
+
     /// `true` if the contents were **unintentionally** truncated;
     /// only if [`Wrapping::SingleLineTruncate`] was not used.
     ///
     /// Set by the `truncate` function
     truncation_warning: bool,
+
+    /// Overrides the colors of the text; see [`Self::with_paint`]
+    paint: Option<Paint>,
 }
 
 impl TextBody {
     fn height(&self) -> Pixel {
-        self.lines.iter().fold(0, |res, val| res + val.height())
+        // No spacing after the last line, even if it ends a paragraph
+        let paragraphs = self
+            .lines
+            .iter()
+            .rev()
+            .skip(1)
+            .filter(|line| line.ends_paragraph())
+            .count();
+        self.lines
+            .iter()
+            .fold(paragraphs * self.paragraph_spacing, |res, val| {
+                res + val.height()
+            })
     }
 
     fn size(&self) -> Size {
@@ -386,7 +860,7 @@ impl TextBody {
             width: self
                 .lines
                 .iter()
-                .fold(0, |res, val| Pixel::max(res, val.width)),
+                .fold(0, |res, val| Pixel::max(res, snap_width(val.width))),
             height: self.height(),
         }
     }
@@ -400,58 +874,34 @@ impl TextBody {
                 return;
             }
         }
-        if let Some(line) = self.lines.last_mut() {
-            // Keep popping units until we can fit an ellipsis in this line
-            while let Some(unit) = line.pop() {
-                // Make an ellipsis with the same font and size as this last unit
-                let period = Raster::rasterize(unit.font, unit.height, '.');
-                let ellipsis_width = period.advance * 3;
-                let ellipsis_height = period.bitmap_height as Pixel;
-
-                let ellipsis = vec![period.clone(), period.clone(), period.clone()]
-                    .into_iter()
-                    .map(|raster| Unit {
-                        raster: Some(raster),
-                        width: period.advance,
-                        height: unit.height,
-                        break_kind: BreakKind::Character,
-                        font: unit.font,
-                    })
-                    .collect::<Vec<Unit>>();
-
-                let trunc_size = Size {
-                    width: line.width + ellipsis_width,
-                    height: Pixel::max(line.height(), ellipsis_height),
-                };
-
-                if max_size.fits(trunc_size) {
-                    ellipsis
-                        .into_iter()
-                        .for_each(|unit| line.contents.push(unit));
-
-                    return;
-                }
-            }
-            log::warn!("ran out of units trying to fit ellipsis");
-        } else {
+        let Some(line) = self.lines.last_mut() else {
             log::warn!("no line to add ellipsis");
-        }
+            return;
+        };
+
+        // The ellipsis goes before the line's hard line break, so the break is kept
+        let hard_break = line.take_hard_break();
+        line.push_ellipsis(max_size);
+        line.contents.extend(hard_break);
     }
 
-    /// Lays out a **single-line** text label (no word wrapping).
+    /// Lays out a **single-line** text label (no word wrapping); hard line breaks are ignored.
     /// Also used to deal with intrinsic sizing
     pub fn new_label(text: RichString, direction: Direction) -> Self {
         let Direction::Horizontal = direction;
 
-        let mut line = TextLine::new();
-        let mut iter = text.rich_iter();
-        while let Some(rich_char) = iter.next() {
-            line.push(Unit::rasterize(rich_char));
+        let mut line = TextLine::new(strut(&text), LineHeight::Normal);
+        let line_breaks = text.line_breaks();
+        for shaped in shaping::shape(&text) {
+            let line_break = line_breaks.get(&shaped.shaped.range.end).copied();
+            line.push(Unit::rasterize(shaped, line_break));
         }
 
         TextBody {
             lines: vec![line],
+            paragraph_spacing: 0,
             truncation_warning: false,
+            paint: None,
         }
     }
 
@@ -460,26 +910,49 @@ impl TextBody {
     ///
     /// The output of this method is ultimately what is rendered to the buffer.
     /// Whitespace is not preserved when it causes a line break.
+    ///
+    /// Newlines, U+2028 LINE SEPARATOR and U+2029 PARAGRAPH SEPARATOR are hard line breaks,
+    /// except with the single-line [`Wrapping`] modes, where they're ignored.
+    /// Blank lines are kept as empty lines.
     pub fn layout(text: RichString, options: TextOptions, max_size: SizeConstraint) -> Self {
         let Direction::Horizontal = options.direction;
 
         let mut result = TextBody {
             lines: Vec::new(),
+            paragraph_spacing: options.paragraph_spacing,
             truncation_warning: false,
+            paint: None,
         };
+        let multi_line = matches!(options.wrapping, Wrapping::Word | Wrapping::Character);
 
-        let mut current_line = TextLine::new();
+        let mut current_line = TextLine::new(strut(&text), options.line_height);
 
         // We iterate over the text with the rich text iterator,
         // but we also maintain a backtrack queue because of word wrapping.
-        let mut iter = text.rich_iter();
+        let line_breaks = text.line_breaks();
+        let mut iter = shaping::shape(&text).into_iter().map(|shaped| {
+            let line_break = line_breaks.get(&shaped.shaped.range.end).copied();
+            Unit::rasterize(shaped, line_break)
+        });
         let mut queue = VecDeque::<Unit>::new();
 
-        while let Some(unit) = queue
-            .pop_front()
-            .or_else(|| iter.next().map(Unit::rasterize))
-        {
-            if current_line.would_fit(max_size, &unit) {
+        while let Some(unit) = queue.pop_front().or_else(|| iter.next()) {
+            // Hard line breaks always end the line, unless we only have the one line.
+            // They have no width, so they never cause wrapping themselves
+            if multi_line && unit.break_kind.is_hard_break() {
+                if !max_size.fits_height(result.height()) {
+                    result.truncate(max_size, true);
+                    return result;
+                }
+
+                current_line.push(unit);
+                current_line.trim_end();
+                result.lines.push(current_line.break_line());
+                continue;
+            }
+
+            // A unit that's wider than the whole line has to go somewhere
+            if current_line.would_fit(max_size, &unit) || current_line.contents.is_empty() {
                 current_line.push(unit);
                 continue;
             } else if unit.is_whitespace() {
@@ -504,26 +977,31 @@ impl TextBody {
                 match options.wrapping {
                     Wrapping::Character => {
                         // new line, continue
-
-                        result
-                            .lines
-                            .push(std::mem::replace(&mut current_line, TextLine::new()));
+                        current_line.trim_end();
+                        result.lines.push(current_line.break_line());
+                        queue.push_front(unit);
                     }
                     Wrapping::Word => {
-                        // wrap line
+                        // wrap line, and go back to the units that were split off before continuing
                         let (line_result, backqueue) =
                             current_line.split_at_word_boundary(max_size);
 
+                        current_line = line_result.next();
                         result.lines.push(line_result);
-                        current_line = TextLine::new();
 
-                        backqueue.into_iter().for_each(|unit| queue.push_back(unit));
+                        queue.push_front(unit);
+                        backqueue
+                            .into_iter()
+                            .rev()
+                            .for_each(|unit| queue.push_front(unit));
                     }
                     Wrapping::SingleLine => {
+                        result.lines.push(current_line);
                         result.truncate(max_size, true);
                         return result;
                     }
                     Wrapping::SingleLineTruncate => {
+                        result.lines.push(current_line);
                         result.truncate(max_size, false);
                         return result;
                     }
@@ -532,26 +1010,11 @@ impl TextBody {
         }
 
         // Handle last line
-        /*
-           We have to trim whitespace from the end of the text body as a result of our wrapping algorithm,
-           especially for the case where the whitespace overflows the max size.
-           I'm misusing our "LineSplitIter" construct from above to do this,
-           since it does what we need while updating the line's length,
-           and I'll just ignore the width and split fragment parts.
-        */
-        let mut split_iter = LineSplitIter::new(current_line);
-        while let Some((unit, _)) = split_iter.advance() {
-            if unit.is_whitespace() {
-                continue;
-            } else {
-                split_iter.retreat();
-                break;
-            }
-        }
+        // We have to trim whitespace from the end of the text body as a result of our wrapping algorithm,
+        // especially for the case where the whitespace overflows the max size.
         // If we cleared the entire line and it was nothing but whitespace;
         // for now, we're going to push an empty line anyways.  Maybe this changes?
-
-        let (current_line, _) = split_iter.confirm();
+        current_line.trim_end();
         result.lines.push(current_line);
 
         // one last truncation check; this is our final size
@@ -562,13 +1025,144 @@ impl TextBody {
 
         result
     }
+
+    /// Draws all of the text, including its decorations, with `paint` instead of the colors from its attributes.
+    /// Gradients are relative to the top left of the text body
+    pub fn with_paint(mut self, paint: impl Into<Paint>) -> Self {
+        self.paint = Some(paint.into());
+        self
+    }
 }
 
 impl Drawable for TextBody {
     fn draw_at(&self, handle: &mut DrawHandle, position: Position) {
-        //TODO: Multi line
-        if let Some(first) = self.lines.first() {
-            first.draw_at(handle, position);
+        let offset = Transform::translate(position.x as f32, position.y as f32);
+        let paint = self.paint.as_ref().map(|paint| paint.transformed(offset));
+
+        let mut y = position.y;
+        self.lines.iter().for_each(|line| {
+            line.draw_with(handle, Position { x: position.x, y }, paint.as_ref());
+
+            y += line.height() as i32;
+            if line.ends_paragraph() {
+                y += self.paragraph_spacing as i32;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+
+    use super::*;
+    use crate::graphics::typeface::{FontStyle, Typeface};
+
+    fn typeface() -> &'static Typeface {
+        static MEM: OnceLock<Typeface> = OnceLock::new();
+        MEM.get_or_init(|| Typeface::try_roboto().unwrap())
+    }
+
+    fn layout(text: &str, max_size: SizeConstraint) -> TextBody {
+        let text = RichString::new(text.to_string(), typeface());
+        TextBody::layout(text, TextOptions::default(), max_size)
+    }
+
+    /// The width of `text` on a single line
+    fn width(text: &str) -> f32 {
+        layout(text, SizeConstraint::auto()).lines[0].width
+    }
+
+    /// Lays out `text` just too narrow to fit `fits` on the first line,
+    /// and checks that it wraps right after `first`
+    fn assert_wraps(text: &str, fits: &str, first: &str) {
+        let body = layout(text, SizeConstraint::intrinsic_width(width(fits) as Pixel));
+        let widths: Vec<f32> = body.lines.iter().map(|line| line.width).collect();
+        assert_eq!(widths.len(), 2, "{text:?} was split into {widths:?}");
+        assert!(
+            (widths[0] - width(first)).abs() < 0.01,
+            "{text:?} didn't wrap after {first:?}"
+        );
+    }
+
+    #[test]
+    fn wraps_at_word_boundaries() {
+        assert_wraps("don't stop", "don't st", "don't");
+        assert_wraps("apple 3.14", "apple 3.1", "apple");
+        assert_wraps("go a\u{A0}b", "go a\u{A0}", "go");
+    }
+
+    #[test]
+    fn mandatory_breaks() {
+        let body = layout("a\nb\u{2028}c", SizeConstraint::auto());
+        assert_eq!(body.lines.len(), 3);
+        assert!(body.lines[0].ends_paragraph());
+        assert!(!body.lines[1].ends_paragraph());
+        assert!((body.lines[2].width - width("c")).abs() < 0.01);
+    }
+
+    /// Glyphs that reach past their origin or advance have to be placed by their bearings:
+    /// each bitmap's left edge lands on the outline's left edge, rounded down to a whole pixel.
+    /// This is checked against the font's own bounding boxes rather than a golden image,
+    /// so that it's pixel exact without depending on how each platform rounds the coverage
+    #[test]
+    fn overhanging_glyphs() {
+        let size = 40;
+        let cases = [
+            (FontStyle::Regular, "j"),
+            (FontStyle::Regular, "f"),
+            (FontStyle::Regular, "ƒ"),
+            (FontStyle::Italic, "f"),
+            (FontStyle::Italic, "j"),
+        ];
+        for (style, text) in cases {
+            let font = typeface().match_variant(400, style);
+            let face = ttf_parser::Face::from_slice(&font.bytes, font.index).unwrap();
+            let scale = size as f32 / face.units_per_em() as f32;
+
+            for cluster in shaping::shape_run(text, font, size) {
+                for glyph in &cluster.glyphs {
+                    let bounds = face
+                        .glyph_bounding_box(ttf_parser::GlyphId(glyph.id))
+                        .unwrap();
+                    let (x, bin) = subpixel(glyph.x);
+                    let placed = GlyphRaster::rasterize(font, glyph.id, size as f32, bin);
+                    let left = (glyph.x + bounds.x_min as f32 * scale).floor() as i32;
+                    assert_eq!(x + placed.bearing, left, "{style:?} {text:?}");
+                }
+            }
         }
+
+        // And they really do overhang
+        let j = typeface().match_variant(400, FontStyle::Regular);
+        let id = j.data.lookup_glyph_index('j');
+        assert!(GlyphRaster::rasterize(j, id, size as f32, 0).bearing < 0);
+    }
+
+    /// Where the left edge of an upright stem crosses the middle row of a glyph raster,
+    /// relative to the glyph's origin, from the coverage of the first pixel it touches
+    fn left_edge(raster: &GlyphRaster) -> f32 {
+        let rows = raster.bitmap.chunks(raster.bitmap_width);
+        let row = rows.clone().nth(rows.len() / 2).unwrap();
+        let (x, alpha) = row
+            .iter()
+            .enumerate()
+            .find(|(_, &alpha)| alpha > 0)
+            .unwrap();
+        (raster.bearing + x as i32 + 1) as f32 - *alpha as f32 / 255.0
+    }
+
+    #[test]
+    fn subpixel_bins_move_the_outline() {
+        let font = typeface().match_variant(400, FontStyle::Regular);
+        let id = font.data.lookup_glyph_index('l');
+        let whole = GlyphRaster::rasterize(font, id, 24.0, 0);
+        (1..SUBPIXEL_BINS).for_each(|bin| {
+            let shifted = GlyphRaster::rasterize(font, id, 24.0, bin);
+            let shift = bin as f32 / SUBPIXEL_BINS as f32;
+            assert!((left_edge(&shifted) - left_edge(&whole) - shift).abs() < 0.01);
+        });
+        assert_eq!(subpixel(10.3), (10, 1));
+        assert_eq!(subpixel(-0.25), (-1, 3));
     }
 }